@@ -0,0 +1,195 @@
+// 解析结果缓存：按路径、修改时间与版本号保存 AST，跳过重复解析
+// 缓存目录为 $LUME_CACHE_DIR，默认 <data_local_dir>/lumesh/cache
+// 总大小超过 MAX_CACHE_BYTES 时按写入时间淘汰最旧的文件
+
+use crate::expression::codec::{Decoder, Encoder};
+use crate::{Expression, ModuleInfo, ModuleUse, VERSION, with_ast_cache_enabled, with_cfm_enabled};
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const MAGIC: &[u8; 4] = b"LMAC";
+// 编码格式变化时递增，使旧缓存失效
+const FORMAT: u8 = 7;
+
+const KIND_SCRIPT: u8 = 0;
+const KIND_MODULE: u8 = 1;
+
+const MAX_CACHE_BYTES: u64 = 64 << 20;
+
+thread_local! {
+    /// 指定的缓存目录，优先于 LUME_CACHE_DIR
+    static CACHE_DIR: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+pub fn set_cache_dir(dir: Option<PathBuf>) {
+    CACHE_DIR.with_borrow_mut(|d| *d = dir);
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = CACHE_DIR.with_borrow(|d| d.clone()) {
+        return Some(dir);
+    }
+    match std::env::var_os("LUME_CACHE_DIR") {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => dirs::data_local_dir().map(|d| d.join("lumesh/cache")),
+    }
+}
+
+/// 缓存总大小超过上限时，按修改时间删除最旧的文件，直到降到上限的四分之三
+pub fn evict(dir: &Path, max_bytes: u64) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut files = entries
+        .filter_map(|e| {
+            let path = e.ok()?.path();
+            if path.extension()? != "ast" {
+                return None;
+            }
+            let meta = fs::metadata(&path).ok()?;
+            Some((meta.modified().ok()?, meta.len(), path))
+        })
+        .collect::<Vec<_>>();
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    if total <= max_bytes {
+        return;
+    }
+    files.sort();
+    for (_, len, path) in files {
+        if total <= max_bytes / 4 * 3 {
+            break;
+        }
+        if fs::remove_file(path).is_ok() {
+            total -= len;
+        }
+    }
+}
+
+/// FNV-1a 64 位哈希：缓存文件名须在不同 Rust 版本间保持一致，不用 DefaultHasher
+pub(crate) fn fnv1a<'a>(bytes: impl Iterator<Item = &'a u8>) -> u64 {
+    bytes.fold(0xcbf29ce484222325, |h, b| {
+        (h ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// 缓存头：源文件路径、修改时间、大小、版本、解析模式
+fn header(path: &Path, kind: u8) -> Option<(PathBuf, Encoder)> {
+    let path = path.canonicalize().ok()?;
+    let meta = fs::metadata(&path).ok()?;
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+    let name = fnv1a(path.as_os_str().as_encoded_bytes().iter().chain([&kind]));
+    let file = cache_dir()?.join(format!("{name:016x}.ast"));
+
+    let mut enc = Encoder::new();
+    enc.buf.extend_from_slice(MAGIC);
+    enc.u8(FORMAT);
+    enc.str(VERSION);
+    enc.str(&path.to_string_lossy());
+    enc.u8(kind);
+    enc.u64(mtime.as_secs());
+    enc.u64(mtime.subsec_nanos() as u64);
+    enc.u64(meta.len());
+    enc.bool(with_cfm_enabled(|cfm| cfm));
+    Some((file, enc))
+}
+
+fn load<T>(path: &Path, kind: u8, decode: impl FnOnce(&mut Decoder) -> Option<T>) -> Option<T> {
+    if !with_ast_cache_enabled(|v| v) {
+        return None;
+    }
+    let (file, head) = header(path, kind)?;
+    let data = fs::read(file).ok()?;
+    let body = data.strip_prefix(head.buf.as_slice())?;
+    let mut dec = Decoder::new(body);
+    let value = decode(&mut dec)?;
+    dec.is_end().then_some(value)
+}
+
+fn store(path: &Path, kind: u8, encode: impl FnOnce(&mut Encoder) -> Option<()>) {
+    if !with_ast_cache_enabled(|v| v) {
+        return;
+    }
+    let Some((file, mut enc)) = header(path, kind) else {
+        return;
+    };
+    if encode(&mut enc).is_none() {
+        return;
+    }
+    // 先写临时文件再改名，避免并发读到半截内容
+    let tmp = file.with_extension(format!("tmp{}", std::process::id()));
+    let written = file
+        .parent()
+        .is_some_and(|dir| fs::create_dir_all(dir).is_ok())
+        && fs::write(&tmp, &enc.buf).is_ok()
+        && fs::rename(&tmp, &file).is_ok();
+    if !written {
+        let _ = fs::remove_file(&tmp);
+    }
+    if let Some(dir) = file.parent() {
+        evict(dir, MAX_CACHE_BYTES);
+    }
+}
+
+pub fn load_script(path: &Path) -> Option<Expression> {
+    load(path, KIND_SCRIPT, |dec| dec.expr())
+}
+
+pub fn store_script(path: &Path, expr: &Expression) {
+    store(path, KIND_SCRIPT, |enc| enc.expr(expr))
+}
+
+pub fn load_module(path: &Path) -> Option<ModuleInfo> {
+    load(path, KIND_MODULE, |dec| {
+        let use_statements = dec.list(|d| {
+            Some(ModuleUse {
+                alias: d.opt_str()?,
+                path: d.str()?,
+                names: match d.bool()? {
+                    true => Some(d.strs()?),
+                    false => None,
+                },
+                public: d.bool()?,
+            })
+        })?;
+        let functions = dec.list(|d| Some((d.str()?, d.expr()?)))?;
+        let exports = match dec.bool()? {
+            true => Some(dec.strs()?.into_iter().collect()),
+            false => None,
+        };
+        Some(ModuleInfo {
+            use_statements,
+            functions: functions.into_iter().collect(),
+            exports,
+        })
+    })
+}
+
+pub fn store_module(path: &Path, module: &ModuleInfo) {
+    store(path, KIND_MODULE, |enc| {
+        enc.list(module.use_statements.iter(), |e, u| {
+            e.opt_str(&u.alias);
+            e.str(&u.path);
+            e.bool(u.names.is_some());
+            if let Some(names) = &u.names {
+                e.strs(names);
+            }
+            e.bool(u.public);
+            Some(())
+        })?;
+        enc.list(module.functions.iter(), |e, (name, func)| {
+            e.str(name);
+            e.expr(func)
+        })?;
+        enc.bool(module.exports.is_some());
+        if let Some(exports) = &module.exports {
+            enc.list(exports.iter(), |e, name| {
+                e.str(name);
+                Some(())
+            })?;
+        }
+        Some(())
+    })
+}
@@ -1,4 +1,4 @@
-use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering};
 use std::sync::{Arc, LazyLock, Mutex};
 
 static CURRENT_CHILD_PID: LazyLock<Arc<Mutex<Option<u32>>>> =
@@ -58,32 +58,42 @@ pub fn clear_child() {
     CHILD_PID_FOR_SIGNAL.store(-1, Ordering::SeqCst);
 }
 
+/// shell 的 SIGINT 处理器（unix）
+#[cfg(unix)]
+extern "C" fn handle_sigint(_: i32) {
+    SIGINT_RECEIVED.store(true, Ordering::SeqCst);
+    if CAUGHT_SIGNALS.load(Ordering::SeqCst) & (1 << libc::SIGINT) != 0 {
+        PENDING_SIGNALS.fetch_or(1 << libc::SIGINT, Ordering::SeqCst);
+    }
+    let pid = CHILD_PID_FOR_SIGNAL.load(Ordering::SeqCst);
+    if pid > 0 {
+        unsafe {
+            libc::kill(pid, libc::SIGTERM);
+        }
+    }
+}
+
+/// 设置 SIGINT 处理器；restart 为 false 时阻塞的系统调用会被 Ctrl-C 打断（EINTR）
+#[cfg(unix)]
+fn set_sigint_action(restart: bool) {
+    use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet};
+    let flags = match restart {
+        true => SaFlags::SA_RESTART,
+        false => SaFlags::empty(),
+    };
+    let action = SigAction::new(SigHandler::Handler(handle_sigint), flags, SigSet::empty());
+    unsafe {
+        let _ = signal::sigaction(signal::Signal::SIGINT, &action);
+    }
+}
+
 /// 安装 SIGINT 处理器：
 /// - 设置标志位（供 REPL 检测）
 /// - 如果当前有活跃的子进程，发送 SIGTERM 终止它（确保 sleep/cat/gui 程序等都能退出）
 pub fn install_sigint_handler() {
+    SIGINT_INSTALLED.store(true, Ordering::SeqCst);
     #[cfg(unix)]
-    {
-        use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet};
-        // 使用非局部函数，避免 extern "C" fn 嵌套闭包
-        extern "C" fn handle_sigint(_: i32) {
-            SIGINT_RECEIVED.store(true, Ordering::SeqCst);
-            let pid = CHILD_PID_FOR_SIGNAL.load(Ordering::SeqCst);
-            if pid > 0 {
-                unsafe {
-                    libc::kill(pid, libc::SIGTERM);
-                }
-            }
-        }
-        let action = SigAction::new(
-            SigHandler::Handler(handle_sigint),
-            SaFlags::SA_RESTART,
-            SigSet::empty(),
-        );
-        unsafe {
-            let _ = signal::sigaction(signal::Signal::SIGINT, &action);
-        }
-    }
+    set_sigint_action(true);
     #[cfg(windows)]
     {
         extern "system" fn handle_sigint(_: u32) -> i32 {
@@ -113,3 +123,602 @@ pub fn install_sigint_handler() {
 pub fn check_and_clear_sigint() -> bool {
     SIGINT_RECEIVED.swap(false, Ordering::SeqCst)
 }
+
+// ============== 最近一次命令状态 ==============
+
+/// 外部命令的退出信息，每次外部命令结束后更新
+#[derive(Debug, Clone, Default)]
+pub struct ExitInfo {
+    /// 正常退出时的退出码
+    pub code: Option<i32>,
+    /// 被信号终止时的信号编号
+    pub signal: Option<i32>,
+    pub duration: std::time::Duration,
+    pub pid: u32,
+    pub argv: Vec<String>,
+}
+
+impl ExitInfo {
+    /// 进程回收前 code 与 signal 均为 None
+    pub fn new(argv: Vec<String>, pid: u32, started: std::time::Instant) -> Self {
+        Self {
+            code: None,
+            signal: None,
+            duration: started.elapsed(),
+            pid,
+            argv,
+        }
+    }
+
+    /// shell 风格的状态码：被信号终止时为 128+信号
+    pub fn status_code(&self) -> i32 {
+        match (self.code, self.signal) {
+            (Some(code), _) => code,
+            (None, Some(sig)) => 128 + sig,
+            (None, None) => -1,
+        }
+    }
+
+    pub fn with_status(mut self, status: &std::process::ExitStatus) -> Self {
+        self.code = status.code();
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            self.signal = status.signal();
+        }
+        self
+    }
+}
+
+thread_local! {
+    static LAST_STATUS: std::cell::RefCell<ExitInfo> = std::cell::RefCell::new(ExitInfo::default());
+    /// 最近一条管道各段的状态码
+    static PIPE_STATUS: std::cell::RefCell<Vec<i32>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+pub fn set_last_status(info: ExitInfo) {
+    LAST_STATUS.with_borrow_mut(|last| *last = info);
+}
+
+pub fn last_status() -> ExitInfo {
+    LAST_STATUS.with_borrow(|last| last.clone())
+}
+
+pub fn set_pipe_status(codes: Vec<i32>) {
+    PIPE_STATUS.with_borrow_mut(|st| *st = codes);
+}
+
+pub fn pipe_status() -> Vec<i32> {
+    PIPE_STATUS.with_borrow(|st| st.clone())
+}
+
+// ============== 作业控制 ==============
+
+/// 作业状态
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    /// 结束，保存原始 wait 状态
+    Done(i32),
+}
+
+/// 作业表中的一项：一个进程组（后台命令或被挂起的前台管道）
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: usize,
+    pub pgid: u32,
+    /// 管道末段进程，其退出状态即作业状态
+    pub pid: u32,
+    pub cmd: String,
+    pub state: JobState,
+    last_status: i32,
+}
+
+impl Job {
+    pub fn state_str(&self) -> String {
+        match self.state {
+            JobState::Running => "Running".into(),
+            JobState::Stopped => "Stopped".into(),
+            JobState::Done(status) => {
+                #[cfg(unix)]
+                {
+                    if libc::WIFSIGNALED(status) {
+                        return format!("Killed({})", libc::WTERMSIG(status));
+                    }
+                    match libc::WEXITSTATUS(status) {
+                        0 => "Done".into(),
+                        code => format!("Exit {code}"),
+                    }
+                }
+                #[cfg(windows)]
+                match status {
+                    0 => "Done".into(),
+                    code => format!("Exit {code}"),
+                }
+            }
+        }
+    }
+}
+
+static JOBS: LazyLock<Mutex<Vec<Job>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// 交互式终端下启用作业控制
+static JOB_CONTROL: AtomicBool = AtomicBool::new(false);
+
+/// shell 自身的进程组，前台作业结束后终端交还给它
+static SHELL_PGID: AtomicI32 = AtomicI32::new(-1);
+
+/// 初始化作业控制（仅在 stdin 为终端时生效）：
+/// - shell 成为独立进程组并占有终端
+/// - 忽略 SIGTSTP/SIGTTIN/SIGTTOU，Ctrl-Z 只挂起前台作业
+pub fn init_job_control() {
+    #[cfg(unix)]
+    unsafe {
+        if libc::isatty(libc::STDIN_FILENO) != 1 {
+            return;
+        }
+        // 会话首进程（登录 shell）无法 setpgid，忽略错误
+        libc::setpgid(0, 0);
+        let pgid = libc::getpgrp();
+        libc::signal(libc::SIGTSTP, libc::SIG_IGN);
+        libc::signal(libc::SIGTTIN, libc::SIG_IGN);
+        libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+        libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+        SHELL_PGID.store(pgid, Ordering::SeqCst);
+        JOB_CONTROL.store(true, Ordering::SeqCst);
+    }
+}
+
+pub fn job_control_enabled() -> bool {
+    JOB_CONTROL.load(Ordering::SeqCst)
+}
+
+/// 让命令进入指定进程组（None 表示新建），并恢复 shell 忽略的作业信号
+pub fn prepare_job(cmd: &mut std::process::Command, pgid: Option<u32>) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(pgid.map_or(0, |p| p as i32));
+        unsafe {
+            cmd.pre_exec(|| {
+                libc::signal(libc::SIGTSTP, libc::SIG_DFL);
+                libc::signal(libc::SIGTTIN, libc::SIG_DFL);
+                libc::signal(libc::SIGTTOU, libc::SIG_DFL);
+                Ok(())
+            });
+        }
+    }
+    #[cfg(windows)]
+    let _ = (cmd, pgid);
+}
+
+/// 将终端交给指定进程组
+pub fn give_terminal(pgid: u32) {
+    #[cfg(unix)]
+    if job_control_enabled() {
+        unsafe {
+            libc::tcsetpgrp(libc::STDIN_FILENO, pgid as i32);
+        }
+    }
+    #[cfg(windows)]
+    let _ = pgid;
+}
+
+/// 终端交还 shell
+pub fn reclaim_terminal() {
+    #[cfg(unix)]
+    if job_control_enabled() {
+        unsafe {
+            libc::tcsetpgrp(libc::STDIN_FILENO, SHELL_PGID.load(Ordering::SeqCst));
+        }
+    }
+}
+
+/// 前台等待：终端交给作业进程组，直到进程结束或被 Ctrl-Z 挂起。
+/// 挂起时登记为作业并返回 None。
+#[cfg(unix)]
+pub fn wait_foreground(
+    pid: u32,
+    pgid: u32,
+    cmd: &str,
+) -> std::io::Result<Option<std::process::ExitStatus>> {
+    use std::os::unix::process::ExitStatusExt;
+    give_terminal(pgid);
+    set_child(pid);
+    let mut status = 0;
+    let r = loop {
+        let r = unsafe { libc::waitpid(pid as i32, &mut status, libc::WUNTRACED) };
+        if r == -1 && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+            continue;
+        }
+        break r;
+    };
+    clear_child();
+    reclaim_terminal();
+    if r == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    if libc::WIFSTOPPED(status) {
+        let id = add_job(pgid, pid, cmd.to_string(), JobState::Stopped);
+        eprintln!("\n[{id}]+  Stopped\t{cmd}");
+        return Ok(None);
+    }
+    Ok(Some(std::process::ExitStatus::from_raw(status)))
+}
+
+/// 登记作业；同一进程组再次挂起时沿用原编号
+pub fn add_job(pgid: u32, pid: u32, cmd: String, state: JobState) -> usize {
+    let mut jobs = JOBS.lock().unwrap();
+    if let Some(job) = jobs.iter_mut().find(|j| j.pgid == pgid) {
+        job.state = state;
+        return job.id;
+    }
+    let id = jobs.iter().map(|j| j.id).max().unwrap_or(0) + 1;
+    jobs.push(Job {
+        id,
+        pgid,
+        pid,
+        cmd,
+        state,
+        last_status: 0,
+    });
+    id
+}
+
+/// 进程组是否已转为作业（后台运行或已挂起）
+pub fn is_job(pgid: u32) -> bool {
+    JOBS.lock().unwrap().iter().any(|j| j.pgid == pgid)
+}
+
+/// 非阻塞地更新各作业状态
+#[cfg(unix)]
+fn refresh_jobs(jobs: &mut [Job]) {
+    for job in jobs.iter_mut() {
+        if let JobState::Done(_) = job.state {
+            continue;
+        }
+        loop {
+            let mut status = 0;
+            let r = unsafe {
+                libc::waitpid(
+                    -(job.pgid as i32),
+                    &mut status,
+                    libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED,
+                )
+            };
+            if r == 0 {
+                break;
+            }
+            if r < 0 {
+                // 进程组已全部回收
+                job.state = JobState::Done(job.last_status);
+                break;
+            }
+            if libc::WIFSTOPPED(status) {
+                job.state = JobState::Stopped;
+                break;
+            } else if libc::WIFCONTINUED(status) {
+                job.state = JobState::Running;
+            } else if r as u32 == job.pid {
+                job.last_status = status;
+            }
+        }
+    }
+}
+
+/// 当前作业列表（已结束的作业在返回后移除）
+pub fn list_jobs() -> Vec<Job> {
+    let mut jobs = JOBS.lock().unwrap();
+    #[cfg(unix)]
+    refresh_jobs(&mut jobs);
+    let snapshot = jobs.clone();
+    jobs.retain(|j| !matches!(j.state, JobState::Done(_)));
+    snapshot
+}
+
+/// 在提示符前报告已结束的后台作业
+pub fn report_jobs() {
+    for job in list_jobs() {
+        if let JobState::Done(_) = job.state {
+            eprintln!("[{}]  {}\t{}", job.id, job.state_str(), job.cmd);
+        }
+    }
+}
+
+/// 按 `%n` / `%+` / `%-` / `%%` 或进程号查找作业；空串表示当前作业
+pub fn find_job(spec: &str) -> Option<Job> {
+    let jobs = JOBS.lock().unwrap();
+    match spec {
+        "" | "%" | "%%" | "%+" => jobs.last().cloned(),
+        "%-" => jobs.iter().rev().nth(1).cloned(),
+        s => match s.strip_prefix('%') {
+            Some(n) => n
+                .parse::<usize>()
+                .ok()
+                .and_then(|id| jobs.iter().find(|j| j.id == id).cloned()),
+            None => s
+                .parse::<u32>()
+                .ok()
+                .and_then(|pid| jobs.iter().find(|j| j.pid == pid || j.pgid == pid).cloned()),
+        },
+    }
+}
+
+pub fn remove_job(id: usize) {
+    JOBS.lock().unwrap().retain(|j| j.id != id);
+}
+
+/// 向作业的整个进程组发送信号
+pub fn signal_job(job: &Job, sig: i32) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        if unsafe { libc::kill(-(job.pgid as i32), sig) } == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    }
+    #[cfg(windows)]
+    {
+        let _ = (job, sig);
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+}
+
+/// 继续运行作业。前台继续时等待其结束（或再次挂起），返回退出状态。
+#[cfg(unix)]
+pub fn continue_job(
+    job: &Job,
+    foreground: bool,
+) -> std::io::Result<Option<std::process::ExitStatus>> {
+    if foreground {
+        give_terminal(job.pgid);
+    }
+    signal_job(job, libc::SIGCONT)?;
+    add_job(job.pgid, job.pid, job.cmd.clone(), JobState::Running);
+    if !foreground {
+        return Ok(None);
+    }
+    let status = wait_foreground(job.pid, job.pgid, &job.cmd)?;
+    if status.is_some() {
+        reap_group(job.pgid);
+        remove_job(job.id);
+    }
+    Ok(status)
+}
+
+/// 阻塞等待作业结束，返回末段进程的原始 wait 状态。
+/// Ctrl-C 时提前返回 None，作业继续保留在作业表中。
+#[cfg(unix)]
+pub fn wait_job(job: &Job) -> Option<i32> {
+    let status = wait_target(-(job.pgid as i32), job.pid, Some(job.last_status))
+        .unwrap_or(Some(job.last_status))?;
+    remove_job(job.id);
+    Some(status)
+}
+
+/// 阻塞等待不在作业表中的子进程
+#[cfg(unix)]
+pub fn wait_pid(pid: u32) -> std::io::Result<Option<i32>> {
+    wait_target(pid as i32, pid, None)
+}
+
+/// 阻塞等待 waitpid 目标（进程或负的进程组号）全部结束，返回 main 进程的状态。
+/// 等待期间 Ctrl-C 会打断 waitpid，此时返回 Ok(None)。
+#[cfg(unix)]
+fn wait_target(target: i32, main: u32, mut last: Option<i32>) -> std::io::Result<Option<i32>> {
+    let interruptible = SIGINT_INSTALLED.load(Ordering::SeqCst);
+    if interruptible {
+        set_sigint_action(false);
+    }
+    let result = loop {
+        let mut status = 0;
+        let r = unsafe { libc::waitpid(target, &mut status, 0) };
+        if r > 0 {
+            if r as u32 == main {
+                last = Some(status);
+            }
+            continue;
+        }
+        let e = std::io::Error::last_os_error();
+        match e.kind() {
+            std::io::ErrorKind::Interrupted if check_and_clear_sigint() => break Ok(None),
+            std::io::ErrorKind::Interrupted => continue,
+            // 已全部回收
+            _ => break last.map(Some).ok_or(e),
+        }
+    };
+    if interruptible {
+        set_sigint_action(true);
+    }
+    result
+}
+
+/// 回收进程组中剩余的进程
+#[cfg(unix)]
+fn reap_group(pgid: u32) {
+    let mut status = 0;
+    while unsafe { libc::waitpid(-(pgid as i32), &mut status, 0) } > 0 {}
+}
+
+// ============== 超时监视 ==============
+
+/// 超时监视共享的状态：登记的进程组、时限与是否已到期
+#[derive(Default)]
+struct WatchShared {
+    groups: Mutex<Vec<u32>>,
+    limit: std::time::Duration,
+    expired: AtomicBool,
+}
+
+#[cfg(unix)]
+impl WatchShared {
+    /// 向全部登记的进程组发送信号，返回仍存在的组数
+    fn signal_all(&self, sig: i32) -> usize {
+        let groups = self.groups.lock().unwrap();
+        groups
+            .iter()
+            .filter(|pgid| signal_group(**pgid, sig))
+            .count()
+    }
+}
+
+#[cfg(unix)]
+fn signal_group(pgid: u32, sig: i32) -> bool {
+    let ok = unsafe { libc::kill(-(pgid as i32), sig) } == 0;
+    // 被停止的进程需继续运行才能处理 SIGTERM
+    if ok && sig == libc::SIGTERM {
+        unsafe { libc::kill(-(pgid as i32), libc::SIGCONT) };
+    }
+    ok
+}
+
+thread_local! {
+    /// 当前生效的超时监视（可嵌套）；其间启动的外部命令各自成组并登记
+    static WATCHDOGS: std::cell::RefCell<Vec<Arc<WatchShared>>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// 超时监视：到期后向期间启动的进程组发送 SIGTERM，宽限期后仍未退出则 SIGKILL。
+/// 未到期时随 Drop 取消。
+pub struct Watchdog {
+    shared: Arc<WatchShared>,
+    cancel: Option<std::sync::mpsc::Sender<()>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub fn start(limit: std::time::Duration, grace: std::time::Duration) -> Self {
+        use std::sync::mpsc::{RecvTimeoutError, channel};
+        let shared = Arc::new(WatchShared {
+            limit,
+            ..Default::default()
+        });
+        let (cancel, rx) = channel::<()>();
+        let watched = shared.clone();
+        let thread = std::thread::spawn(move || {
+            if rx.recv_timeout(limit) != Err(RecvTimeoutError::Timeout) {
+                return;
+            }
+            watched.expired.store(true, Ordering::SeqCst);
+            #[cfg(unix)]
+            {
+                watched.signal_all(libc::SIGTERM);
+                let deadline = std::time::Instant::now() + grace;
+                while std::time::Instant::now() < deadline {
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                    if watched.signal_all(0) == 0 {
+                        return;
+                    }
+                }
+                watched.signal_all(libc::SIGKILL);
+            }
+            #[cfg(windows)]
+            let _ = grace;
+        });
+        WATCHDOGS.with_borrow_mut(|w| w.push(shared.clone()));
+        Self {
+            shared,
+            cancel: Some(cancel),
+            thread: Some(thread),
+        }
+    }
+
+    pub fn expired(&self) -> bool {
+        self.shared.expired.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        WATCHDOGS.with_borrow_mut(|w| {
+            w.retain(|s| !Arc::ptr_eq(s, &self.shared));
+        });
+        // 已到期时由监视线程完成宽限期后的 SIGKILL，不再等待
+        if !self.expired() {
+            self.cancel.take();
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+/// 是否处于超时监视之下
+pub fn watchdog_active() -> bool {
+    WATCHDOGS.with_borrow(|w| !w.is_empty())
+}
+
+/// 已到期的超时监视的时限；求值安全点据此中断纯脚本的执行
+pub fn expired_watchdog() -> Option<std::time::Duration> {
+    WATCHDOGS.with_borrow(|w| {
+        w.iter()
+            .find(|s| s.expired.load(Ordering::Relaxed))
+            .map(|s| s.limit)
+    })
+}
+
+/// 将新建的进程组登记到当前所有超时监视；已到期的立即终止
+pub fn watch_group(pgid: u32) {
+    WATCHDOGS.with_borrow(|w| {
+        for shared in w.iter() {
+            shared.groups.lock().unwrap().push(pgid);
+            #[cfg(unix)]
+            if shared.expired.load(Ordering::SeqCst) {
+                signal_group(pgid, libc::SIGTERM);
+            }
+        }
+    });
+}
+
+// ============== 信号陷阱 ==============
+
+/// 由 shell 捕获的信号掩码（按信号编号置位）
+static CAUGHT_SIGNALS: AtomicU32 = AtomicU32::new(0);
+/// 已收到、等待在求值安全点处理的信号
+static PENDING_SIGNALS: AtomicU32 = AtomicU32::new(0);
+/// SIGINT 处理器是否已安装
+static SIGINT_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// 捕获信号（暂存，待安全点处理），或恢复默认处理
+#[cfg(unix)]
+pub fn catch_signal(sig: i32, catch: bool) {
+    use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal};
+    extern "C" fn handle_caught(sig: i32) {
+        PENDING_SIGNALS.fetch_or(1 << sig, Ordering::SeqCst);
+    }
+    match catch {
+        true => CAUGHT_SIGNALS.fetch_or(1 << sig, Ordering::SeqCst),
+        false => {
+            PENDING_SIGNALS.fetch_and(!(1 << sig), Ordering::SeqCst);
+            CAUGHT_SIGNALS.fetch_and(!(1 << sig), Ordering::SeqCst)
+        }
+    };
+    // SIGINT 沿用 shell 的处理器，只切换是否暂存
+    if sig == libc::SIGINT {
+        if catch && !SIGINT_INSTALLED.load(Ordering::SeqCst) {
+            install_sigint_handler();
+        }
+        return;
+    }
+    let Ok(signal) = Signal::try_from(sig) else {
+        return;
+    };
+    let handler = match catch {
+        true => SigHandler::Handler(handle_caught),
+        false => SigHandler::SigDfl,
+    };
+    let action = SigAction::new(handler, SaFlags::SA_RESTART, SigSet::empty());
+    unsafe {
+        let _ = signal::sigaction(signal, &action);
+    }
+}
+
+pub fn has_pending_signals() -> bool {
+    PENDING_SIGNALS.load(Ordering::Relaxed) != 0
+}
+
+/// 取走 mask 中待处理的信号，其余留给设置了对应陷阱的线程
+pub fn take_pending_signals(mask: u32) -> u32 {
+    PENDING_SIGNALS.fetch_and(!mask, Ordering::SeqCst) & mask
+}
@@ -3,6 +3,7 @@
 // mod binary;
 use clap::Parser;
 use lumesh::parse_and_eval;
+use lumesh::profiler;
 use lumesh::repl;
 use lumesh::runtime::init_config;
 use lumesh::runtime::knock_validate;
@@ -11,6 +12,7 @@ use lumesh::runtime::knock_validate;
 // use lumesh::ENV;
 // use lumesh::STRICT;
 use lumesh::runtime::run_file;
+use lumesh::set_ast_cache_enabled;
 use lumesh::set_cfm_enabled;
 use lumesh::set_print_direct;
 use lumesh::set_strict_enabled;
@@ -35,6 +37,15 @@ struct Cli {
     #[arg(short = 'p', long, num_args = 1)]
     profile: Option<String>,
 
+    /// print call counts and timings of functions, builtins and commands at exit
+    /// (not -p/--profile, which selects the config file)
+    #[arg(long)]
+    prof: bool,
+
+    /// also write folded stacks for flamegraph tools to FILE
+    #[arg(long, num_args = 1, value_name = "FILE")]
+    prof_folded: Option<PathBuf>,
+
     /// strict mode
     #[arg(short = 's', long, conflicts_with = "no_strict")]
     strict: bool,
@@ -61,6 +72,10 @@ struct Cli {
     #[arg(short = 'H', long)]
     no_history: bool,
 
+    /// NO parsed-AST cache for scripts and modules
+    #[arg(long)]
+    no_cache: bool,
+
     /// command to eval
     #[arg(short = 'c', long, num_args = 1)]
     cmd: Option<String>,
@@ -134,9 +149,15 @@ fn main() {
     if let Some(profile) = cli.profile {
         cli_env.define("LUME_PROFILE", Expression::String(profile));
     }
+    if cli.prof || cli.prof_folded.is_some() {
+        profiler::enable(cli.prof_folded);
+    }
     if cli.no_history {
         cli_env.define("LUME_NO_HISTORY", Expression::Boolean(true));
     }
+    if cli.no_cache {
+        set_ast_cache_enabled(false);
+    }
 
     // 命令执行模式
     if let Some(cmd) = cli.cmd {
@@ -208,6 +229,8 @@ fn main() {
         }
         repl::run_repl(&mut cli_env);
     }
+    lumesh::libs::run_exit_trap(&mut cli_env);
+    profiler::report();
 }
 
 fn env_config(env: &mut Environment, aioff: bool) {
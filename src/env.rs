@@ -10,7 +10,7 @@ pub type DefaultHasher = BuildHasherDefault<FxHasher>;
 #[derive(Debug, Clone, PartialEq)]
 pub struct Environment {
     pub bindings: Rc<HashMap<String, Expression, DefaultHasher>>,
-    pub parent: Option<Box<Self>>,
+    pub parent: Option<Rc<Self>>, // 共享父链，fork 无需逐层复制
 }
 
 impl Default for Environment {
@@ -53,13 +53,13 @@ impl Environment {
     }
 
     pub fn get_parent_mut(&mut self) -> Option<&mut Self> {
-        self.parent.as_mut().map(|p| p.as_mut())
+        self.parent.as_mut().map(Rc::make_mut)
     }
 
     pub fn fork(&self) -> Self {
         Self {
             bindings: Rc::new(HashMap::with_hasher(DefaultHasher::default())),
-            parent: Some(Box::new(self.clone())),
+            parent: Some(Rc::new(self.clone())),
         }
     }
 
@@ -99,13 +99,13 @@ impl Environment {
     }
 
     pub fn define_in_root(&mut self, name: &str, expr: Expression) {
-        match self.parent.as_mut() {
+        match self.get_parent_mut() {
             Some(p) => p.define_in_root(name, expr),
             None => self.define(name, expr),
         }
     }
     pub fn undefine_in_root(&mut self, name: &str) {
-        match self.parent.as_mut() {
+        match self.get_parent_mut() {
             Some(p) => p.undefine_in_root(name),
             None => self.undefine(name),
         }
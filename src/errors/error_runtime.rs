@@ -19,12 +19,21 @@ pub enum RuntimeErrorKind {
     SymbolNotDefined(String),
     #[error("symbol `{0}` not defined in module {1}\npath trace: {2}")]
     SymbolNotDefinedInModule(String, String, String),
+    #[error("`{0}` is private in module `{1}`")]
+    PrivateMember(String, String),
     #[error("symbol `{0}` is not a module, but a `{1}` in {2}\npath trace: {3}")]
     SymbolNotModule(String, String, Cow<'static, str>, String),
     #[error("command `{0}` failed with args:\n  {1:?}")]
     CommandFailed(String, Vec<Expression>),
     #[error("command `{0}` failed:\n  {1}")]
     CommandFailed2(String, String),
+    #[error("command `{cmd}` failed:\n  {msg}")]
+    CommandExited {
+        cmd: String,
+        msg: String,
+        code: Option<i32>,
+        signal: Option<i32>,
+    },
     #[error("attempted to iterate over non-list `{0:?}`")]
     ForNonList(Expression),
     #[error("recursion depth exceeded")]
@@ -83,12 +92,21 @@ pub enum RuntimeErrorKind {
         sym: String,
         found: String,
     },
+    #[error("type error in function `{name}`: {target} expects `{expected}`, found `{found}`")]
+    ArgTypeError {
+        name: String,
+        target: String, // 如 argument `port`、return value
+        expected: String,
+        found: String,
+    },
     #[error("illegal return outside function")]
     EarlyReturn(Expression),
     #[error("illegal break outside loop")]
     EarlyBreak(Expression),
     #[error("illegal continue outside loop")]
     EarlyContinue,
+    #[error("illegal tail call outside function")]
+    TailCall(Vec<Expression>),
     #[error("overflowed when: `{0}`")]
     Overflow(String),
     #[error("wildcard not matched: `{0}`")]
@@ -97,6 +115,8 @@ pub enum RuntimeErrorKind {
     BuiltinFailed(String, String),
     #[error("terminated")]
     Terminated,
+    #[error("timed out after {0:?}")]
+    Timeout(std::time::Duration),
     #[error("IO Error during {operation}:\n  {kind}: {message}")]
     IoDetailed {
         operation: Cow<'static, str>,
@@ -196,6 +216,7 @@ impl RuntimeError {
     pub const ERROR_CODE_KEY_NOT_FOUND: Int = 18; // Added for KeyNotFound
     pub const ERROR_CODE_TYPE_ERROR: Int = 19; // Added for TypeError
     pub const ERROR_CODE_EARLY_RETURN: Int = 20; // Added for EarlyReturn
+    pub const ERROR_CODE_TIMEOUT: Int = 21;
 
     pub fn codes() -> Expression {
         Expression::from(b_tree_map! {
@@ -218,6 +239,7 @@ impl RuntimeError {
             String::from("key_not_found") => Expression::Integer(Self::ERROR_CODE_KEY_NOT_FOUND),
             String::from("type_error") => Expression::Integer(Self::ERROR_CODE_TYPE_ERROR),
             String::from("early_return") => Expression::Integer(Self::ERROR_CODE_EARLY_RETURN),
+            String::from("timeout") => Expression::Integer(Self::ERROR_CODE_TIMEOUT),
         })
     }
 
@@ -225,9 +247,9 @@ impl RuntimeError {
         match self.kind {
             RuntimeErrorKind::CannotApply(..) => Self::ERROR_CODE_CANNOT_APPLY,
             RuntimeErrorKind::SymbolNotDefined(..) => Self::ERROR_CODE_SYMBOL_NOT_DEFINED,
-            RuntimeErrorKind::CommandFailed(..) | RuntimeErrorKind::CommandFailed2(..) => {
-                Self::ERROR_CODE_COMMAND_FAILED
-            }
+            RuntimeErrorKind::CommandFailed(..)
+            | RuntimeErrorKind::CommandFailed2(..)
+            | RuntimeErrorKind::CommandExited { .. } => Self::ERROR_CODE_COMMAND_FAILED,
             RuntimeErrorKind::ForNonList(..) => Self::ERROR_CODE_FOR_NON_LIST,
             RuntimeErrorKind::RecursionDepth(..) => Self::ERROR_CODE_RECURSION_DEPTH,
             RuntimeErrorKind::PermissionDenied(..) => Self::ERROR_CODE_PERMISSION_DENIED,
@@ -241,9 +263,31 @@ impl RuntimeError {
             RuntimeErrorKind::InvalidOperator(..) => Self::ERROR_CODE_INVALID_OPERATOR,
             RuntimeErrorKind::IndexOutOfBounds { .. } => Self::ERROR_CODE_INDEX_OUT_OF_BOUNDS,
             RuntimeErrorKind::KeyNotFound(..) => Self::ERROR_CODE_KEY_NOT_FOUND,
-            RuntimeErrorKind::TypeError { .. } => Self::ERROR_CODE_TYPE_ERROR,
+            RuntimeErrorKind::TypeError { .. } | RuntimeErrorKind::ArgTypeError { .. } => {
+                Self::ERROR_CODE_TYPE_ERROR
+            }
             RuntimeErrorKind::EarlyReturn(..) => Self::ERROR_CODE_EARLY_RETURN,
+            RuntimeErrorKind::Timeout(..) => Self::ERROR_CODE_TIMEOUT,
             _ => Self::ERROR_CODE_CUSTOM_ERROR,
         }
     }
+
+    /// 外部命令失败时的退出码与终止信号
+    pub fn exit_status(&self) -> (Option<i32>, Option<i32>) {
+        match self.kind {
+            RuntimeErrorKind::CommandExited { code, signal, .. } => (code, signal),
+            _ => (None, None),
+        }
+    }
+
+    /// return/break/continue/尾调用 借助错误向上传递，不应被 catch 捕获
+    pub fn is_control_flow(&self) -> bool {
+        matches!(
+            self.kind,
+            RuntimeErrorKind::EarlyReturn(_)
+                | RuntimeErrorKind::EarlyBreak(_)
+                | RuntimeErrorKind::EarlyContinue
+                | RuntimeErrorKind::TailCall(_)
+        )
+    }
 }
@@ -8,6 +8,8 @@ use thiserror::Error;
 
 // ============== 顶级错误类型 ==============
 
+// 与 result_large_err 同理，运行时错误携带上下文表达式，体积较大
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Error)]
 pub enum LmError {
     #[error(transparent)]
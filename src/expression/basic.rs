@@ -1,5 +1,5 @@
 use super::{CatchType, Expression};
-use crate::expression::{ChainCall, DestructurePattern};
+use crate::expression::{ChainCall, DestructurePattern, MatchPattern};
 use crate::libs::{is_lib, is_top_or_se};
 use crate::{RuntimeError, RuntimeErrorKind};
 use std::borrow::Cow;
@@ -13,6 +13,41 @@ impl fmt::Display for DestructurePattern {
             Self::Rest(s) => write!(f, "*{s}"),
             Self::Identifier(s) => write!(f, "{s}"),
             Self::Renamed((k, n)) => write!(f, "{k}:{n}"),
+            Self::Nested(k, ps) => {
+                if let Some(k) = k {
+                    write!(f, "{k}:")?;
+                }
+                let ps = ps.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+                write!(f, "({})", ps.join(", "))
+            }
+            Self::Default(p, d) => write!(f, "{p}={d}"),
+        }
+    }
+}
+impl fmt::Display for MatchPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value(e) => write!(f, "{e}"),
+            Self::Bind(s) => write!(f, "{s}"),
+            Self::Type(bind, ty) => write!(f, "{}: {ty}", bind.as_deref().unwrap_or("_")),
+            Self::List(items, rest) => {
+                let mut items = items.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+                if let Some(rest) = rest {
+                    items.push(format!("...{rest}"));
+                }
+                write!(f, "[{}]", items.join(", "))
+            }
+            Self::Map(entries) => {
+                let entries = entries
+                    .iter()
+                    .map(|(k, p)| match p {
+                        Self::Bind(n) if n == k => k.clone(),
+                        p => format!("{k}: {p}"),
+                    })
+                    .collect::<Vec<_>>();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            Self::Guard(p, cond) => write!(f, "{p} if {cond}"),
         }
     }
 }
@@ -61,8 +96,10 @@ impl Expression {
         match self {
             // 基础类型 - 支持缩进
             Self::Symbol(name) => write!(f, "{}{name}", idt(i)),
-            Self::Variable(name) => write!(f, "{}${name}", idt(i)),
+            Self::Variable(name) | Self::LocalVar(_, name, false) => write!(f, "{}${name}", idt(i)),
+            Self::LocalVar(_, name, true) => write!(f, "{}{name}", idt(i)),
             Self::Integer(it) => write!(f, "{}{it}", idt(i)),
+            Self::BigInt(it) => write!(f, "{}{it}", idt(i)),
             Self::Float(n) => write!(f, "{}{n}", idt(i)),
             Self::String(s) => write!(f, "{}{s}", idt(i)),
             Self::StringTemplate(segments) => {
@@ -70,7 +107,7 @@ impl Expression {
                 for seg in segments.iter() {
                     match seg {
                         Self::String(s) => write!(f, "{s}")?,
-                        Self::Variable(v) => write!(f, "${v}")?,
+                        Self::Variable(v) | Self::LocalVar(_, v, false) => write!(f, "${v}")?,
                         other => write!(f, "${{{other}}}")?,
                     }
                 }
@@ -123,7 +160,7 @@ impl Expression {
                 Ok(())
             }
             // 声明和赋值
-            Self::Declare(name, expr) => {
+            Self::Declare(name, expr) | Self::LocalSet(_, name, true, expr) => {
                 if f.alternate() {
                     write!(f, "{}let {} = ", idt(i), name)?;
                     expr.fmt_display_indent(f, i + 1)
@@ -143,7 +180,7 @@ impl Expression {
                 write!(f, " = ")?;
                 expr.fmt_display_indent(f, if f.alternate() { i + 1 } else { 0 })
             }
-            Self::Assign(name, expr) => {
+            Self::Assign(name, expr) | Self::LocalSet(_, name, false, expr) => {
                 if f.alternate() {
                     write!(f, "{}{} = ", idt(i), name)?;
                     expr.fmt_display_indent(f, i + 1)
@@ -293,45 +330,14 @@ impl Expression {
                 }
             }
 
-            Self::Function(name, params, collector, body, _) => {
+            Self::Function(name, _, _, body, ..) => {
+                let sig = self.fn_signature().unwrap_or_default();
                 if f.alternate() {
-                    write!(f, "{}fn {}(", idt(i), name)?;
-                    for (i, (param, default)) in params.iter().enumerate() {
-                        if i > 0 {
-                            write!(f, ", ")?;
-                        }
-                        write!(f, "{param}")?;
-                        if let Some(def) = default {
-                            write!(f, " = {def}")?;
-                        }
-                    }
-                    if let Some(coll) = collector {
-                        if !params.is_empty() {
-                            write!(f, ", ")?;
-                        }
-                        write!(f, "*{coll}")?;
-                    }
-                    writeln!(f, ") {{")?;
+                    writeln!(f, "{}fn {}{} {{", idt(i), name, sig)?;
                     body.fmt_display_indent(f, i + 1)?;
                     write!(f, "\n{}}}", idt(i))
                 } else {
-                    write!(f, "{}fn {}(", idt(i), name)?;
-                    for (i, (param, default)) in params.iter().enumerate() {
-                        if i > 0 {
-                            write!(f, ", ")?;
-                        }
-                        write!(f, "{param}")?;
-                        if let Some(def) = default {
-                            write!(f, " = {def}")?;
-                        }
-                    }
-                    if let Some(coll) = collector {
-                        if !params.is_empty() {
-                            write!(f, ", ")?;
-                        }
-                        write!(f, "*{coll}")?;
-                    }
-                    write!(f, ") {{ ")?;
+                    write!(f, "{}fn {}{} {{ ", idt(i), name, sig)?;
                     body.fmt_display_indent(f, 0)?;
                     write!(f, " }}")
                 }
@@ -406,6 +412,7 @@ impl Expression {
                         match v {
                             Self::Symbol(_)
                             | Self::Integer(_)
+                            | Self::BigInt(_)
                             | Self::Float(_)
                             | Self::Boolean(_)
                             | Self::String(_) => {
@@ -613,7 +620,34 @@ impl Expression {
                 }
             }
 
-            Self::Use(name, path) => {
+            Self::Try(body, var, handler, finally) => {
+                write!(f, "{}try {{ ", idt(i))?;
+                body.fmt_display_indent(f, 0)?;
+                write!(f, " }}")?;
+                if let Some(handler) = handler {
+                    match var {
+                        Some(v) => write!(f, " catch {v} {{ ")?,
+                        None => write!(f, " catch {{ ")?,
+                    }
+                    handler.fmt_display_indent(f, 0)?;
+                    write!(f, " }}")?;
+                }
+                if let Some(finally) = finally {
+                    write!(f, " finally {{ ")?;
+                    finally.fmt_display_indent(f, 0)?;
+                    write!(f, " }}")?;
+                }
+                Ok(())
+            }
+            Self::Defer(expr) => {
+                write!(f, "{}defer ", idt(i))?;
+                expr.fmt_display_indent(f, 0)
+            }
+
+            Self::Use(_, path, Some(names)) => {
+                write!(f, "{}use {}::{{{}}}", idt(i), path, names.join(", "))
+            }
+            Self::Use(name, path, None) => {
                 write!(
                     f,
                     "{}use {} as {}",
@@ -637,6 +671,20 @@ impl Expression {
 
             Self::RegexDef(s) => write!(f, "{}r'{s}'", idt(i)),
             Self::Regex(r) => write!(f, "{}r'{}'", idt(i), r.regex.as_str()),
+            Self::Stream(_) => write!(f, "{}<stream>", idt(i)),
+            Self::StructDef(def) => {
+                write!(
+                    f,
+                    "{}struct {} {{{}}}",
+                    idt(i),
+                    def.name,
+                    def.members().join(", ")
+                )
+            }
+            Self::Record(def, fields) => {
+                write!(f, "{}{} ", idt(i), def.name)?;
+                Self::Map(fields.clone()).fmt_display_indent(f, 0)
+            }
             Self::TimeDef(t) => write!(f, "{}t'{t}'", idt(i)),
             Self::Blank => write!(f, "{}_", idt(i)),
             Self::Table(t) => write!(f, "{}{t:?}", idt(i)),
@@ -651,9 +699,13 @@ impl Expression {
         match &self {
             // 基础类型 - 统一格式
             Self::Symbol(s) => write!(f, "{}Symbol〈{s:?}〉", prefix),
-            Self::Variable(s) => write!(f, "{}Variable〈{s:?}〉", prefix),
+            Self::Variable(s) | Self::LocalVar(_, s, false) => {
+                write!(f, "{}Variable〈{s:?}〉", prefix)
+            }
+            Self::LocalVar(_, s, true) => write!(f, "{}Symbol〈{s:?}〉", prefix),
             Self::String(s) => write!(f, "{}String〈{s:?}〉", prefix),
             Self::Integer(s) => write!(f, "{}Integer〈{s:?}〉", prefix),
+            Self::BigInt(s) => write!(f, "{}BigInt〈{s}〉", prefix),
             Self::Float(s) => write!(f, "{}Float〈{s:?}〉", prefix),
             Self::Boolean(s) => write!(f, "{}Boolean〈{s:?}〉", prefix),
             Self::DateTime(s) => write!(f, "{}DateTime〈{s:?}〉", prefix),
@@ -668,7 +720,7 @@ impl Expression {
                 for seg in segments.iter() {
                     match seg {
                         Self::String(s) => write!(f, "{s}")?,
-                        Self::Variable(v) => write!(f, "${v}")?,
+                        Self::Variable(v) | Self::LocalVar(_, v, false) => write!(f, "${v}")?,
                         other => write!(f, "${{{other}}}")?,
                     }
                 }
@@ -677,6 +729,23 @@ impl Expression {
             Self::Bytes(b) => write!(f, "{}Bytes〈{:?}〉", prefix, String::from_utf8_lossy(b)),
             Self::RegexDef(s) => write!(f, "{}RegexDef〈{s:?}〉", prefix),
             Self::Regex(s) => write!(f, "{}Regex〈{:?}〉", prefix, s.regex.as_str()),
+            Self::Stream(_) => write!(f, "{}Stream", prefix),
+            Self::StructDef(def) => {
+                write!(f, "{}StructDef〈{}〉", prefix, def.name)?;
+                for member in def.members() {
+                    write!(f, "\n{}{member}", idt(indent + 1))?;
+                }
+                Ok(())
+            }
+            Self::Record(def, fields) => {
+                writeln!(f, "{}Record〈{}〉", prefix, def.name)?;
+                for (k, v) in fields.iter() {
+                    writeln!(f, "{}{}:", idt(indent + 1), k)?;
+                    v.fmt_indent(f, indent + 2)?;
+                    writeln!(f)?;
+                }
+                Ok(())
+            }
             Self::TimeDef(s) => write!(f, "{}TimeDef〈{s:?}〉", prefix),
 
             // 复合表达式
@@ -689,7 +758,7 @@ impl Expression {
             Self::Quote(inner) => write!(f, "{}Quote〈{:?}〉", prefix, inner),
 
             // 声明和赋值
-            Self::Declare(name, expr) => {
+            Self::Declare(name, expr) | Self::LocalSet(_, name, true, expr) => {
                 write!(f, "{}Declare〈{}〉 = ", prefix, name)?;
                 expr.fmt_indent(f, indent + 1)
             }
@@ -697,7 +766,7 @@ impl Expression {
                 write!(f, "{}DestructureAssign〈{:?}〉 = ", prefix, pattern)?;
                 expr.fmt_indent(f, indent + 1)
             }
-            Self::Assign(name, expr) => {
+            Self::Assign(name, expr) | Self::LocalSet(_, name, false, expr) => {
                 write!(f, "{}Assign〈{}〉 = ", prefix, name)?;
                 expr.fmt_indent(f, indent + 1)
             }
@@ -767,7 +836,7 @@ impl Expression {
                 writeln!(f, "{}Lambda〈{}〉", prefix, params.join(", "))?;
                 body.fmt_indent(f, indent + 1)
             }
-            Self::Function(name, params, collector, body, decorators) => {
+            Self::Function(name, params, collector, body, decorators, ret) => {
                 // 装饰器
                 for (deco, args) in decorators {
                     writeln!(
@@ -793,18 +862,28 @@ impl Expression {
 
                 writeln!(
                     f,
-                    "{}Function〈{}({}{})〉",
+                    "{}Function〈{}({}{}){}〉",
                     prefix,
                     name,
                     params
                         .iter()
-                        .map(|(p, v)| match v {
-                            Some(vv) => format!("{p}={vv}"),
-                            _ => p.to_string(),
+                        .map(|(p, v, t)| {
+                            let p = match t {
+                                Some(t) => format!("{p}:{t}"),
+                                _ => p.to_string(),
+                            };
+                            match v {
+                                Some(vv) => format!("{p}={vv}"),
+                                _ => p,
+                            }
                         })
                         .collect::<Vec<String>>()
                         .join(","),
-                    collector_str
+                    collector_str,
+                    match ret {
+                        Some(r) => format!("->{r}"),
+                        _ => String::new(),
+                    }
                 )?;
                 body.fmt_indent(f, indent + 1)
             }
@@ -972,17 +1051,42 @@ impl Expression {
                 }
                 Ok(())
             }
+            Self::Try(body, var, handler, finally) => {
+                writeln!(f, "{}Try", prefix)?;
+                body.fmt_indent(f, indent + 1)?;
+                if let Some(handler) = handler {
+                    write!(
+                        f,
+                        "\n{}Catch〈{}〉",
+                        idt(indent + 1),
+                        var.as_deref().unwrap_or("_")
+                    )?;
+                    handler.fmt_indent(f, indent + 2)?;
+                }
+                if let Some(finally) = finally {
+                    write!(f, "\n{}Finally", idt(indent + 1))?;
+                    finally.fmt_indent(f, indent + 2)?;
+                }
+                Ok(())
+            }
+            Self::Defer(expr) => {
+                writeln!(f, "{}Defer", prefix)?;
+                expr.fmt_indent(f, indent + 1)
+            }
             Self::AliasDef(name, cmd) => {
                 writeln!(f, "{}AliasDef〈{}〉", prefix, name)?;
                 cmd.fmt_indent(f, indent + 1)
             }
-            Self::Use(name, path) => {
+            Self::Use(name, path, names) => {
                 writeln!(
                     f,
-                    "{}Use〈{} as {}〉",
+                    "{}Use〈{} as {}{}〉",
                     prefix,
                     path,
-                    name.as_ref().map_or("_", |n| n.as_str())
+                    name.as_ref().map_or("_", |n| n.as_str()),
+                    names
+                        .as_ref()
+                        .map_or(String::new(), |n| format!(" {{{}}}", n.join(", ")))
                 )?;
                 Ok(())
             }
@@ -994,6 +1098,34 @@ impl Expression {
         }
     }
 
+    /// 函数签名，如 `(host: String, port: Int = 22, *rest) -> Map`
+    pub fn fn_signature(&self) -> Option<String> {
+        let Self::Function(_, params, collector, _, _, ret) = self else {
+            return None;
+        };
+        let mut items = params
+            .iter()
+            .map(|(p, default, ty)| {
+                let mut s = p.to_string();
+                if let Some(ty) = ty {
+                    s.push_str(&format!(": {ty}"));
+                }
+                if let Some(def) = default {
+                    s.push_str(&format!(" = {def}"));
+                }
+                s
+            })
+            .collect::<Vec<_>>();
+        if let Some(coll) = collector {
+            items.push(format!("*{coll}"));
+        }
+        let mut sig = format!("({})", items.join(", "));
+        if let Some(ret) = ret {
+            sig.push_str(&format!(" -> {ret}"));
+        }
+        Some(sig)
+    }
+
     pub fn type_name(&self) -> String {
         match self {
             Self::List(_) => "List".into(),
@@ -1003,10 +1135,11 @@ impl Expression {
             Self::Map(_) => "Map".into(),
             Self::String(_) => "String".into(),
             Self::StringTemplate(_) => "StringTemplate".into(),
-            Self::Integer(_) => "Integer".into(),
+            Self::Integer(_) | Self::BigInt(_) => "Integer".into(),
             Self::DateTime(_) => "DateTime".into(),
             Self::Symbol(_) => "Symbol".into(),
-            Self::Variable(_) => "Variable".into(),
+            Self::Variable(_) | Self::LocalVar(_, _, false) => "Variable".into(),
+            Self::LocalVar(_, _, true) => "Symbol".into(),
 
             Self::Float(_) => "Float".into(),
             Self::Boolean(_) => "Boolean".into(),
@@ -1019,10 +1152,10 @@ impl Expression {
             Self::Index(_, _) => "Index".into(),
             Self::Property(_, _) => "Property".into(),
             Self::Del(_) => "Del".into(),
-            Self::Declare(_, _) => "Declare".into(),
+            Self::Declare(_, _) | Self::LocalSet(_, _, true, _) => "Declare".into(),
             Self::SetParent(_, _) => "Set".into(),
             Self::Export(_, _) => "Export".into(),
-            Self::Assign(_, _) => "Assign".into(),
+            Self::Assign(_, _) | Self::LocalSet(_, _, false, _) => "Assign".into(),
             Self::For(..) => "For".into(),
             Self::While(_, _) => "While".into(),
             Self::Loop(_) => "Loop".into(),
@@ -1042,6 +1175,8 @@ impl Expression {
             Self::Sequence(_) => "Sequence".into(),
             Self::Quote(_) => "Quote".into(),
             Self::Catch(..) => "Catch".into(),
+            Self::Try(..) => "Try".into(),
+            Self::Defer(_) => "Defer".into(),
 
             Self::AliasDef(..) => "AliasDef".into(),
             Self::Range(..) => "Range".into(),
@@ -1054,6 +1189,9 @@ impl Expression {
             Self::TimeDef(..) => "TimeDef".into(),
             Self::RegexDef(..) => "RegexDef".into(),
             Self::Regex(..) => "Regex".into(),
+            Self::Stream(..) => "Stream".into(),
+            Self::StructDef(..) => "Struct".into(),
+            Self::Record(def, _) => def.name.clone(),
 
             Self::None => "None".into(),
             Self::Blank => "Blank".into(),
@@ -1368,6 +1506,7 @@ impl Expression {
     pub fn is_truthy(&self) -> bool {
         match self {
             Self::Integer(i) => *i != 0,
+            Self::BigInt(_) => true, // 大整数总在 i64 之外，不为零
             Self::Float(f) => *f != 0.0,
             Self::String(s) => !s.is_empty(),
             Self::Bytes(b) => !b.is_empty(),
@@ -1379,6 +1518,8 @@ impl Expression {
             Self::Map(exprs) => !exprs.is_empty(),
             Self::Range(exprs, _) => !exprs.is_empty(),
             Self::Regex(r) => !r.regex.as_str().is_empty(),
+            Self::Stream(_) => true,
+            Self::StructDef(_) | Self::Record(..) => true,
             Self::Lambda(..) => true,
             Self::Function(..) => true,
             Self::DateTime(..) => true,
@@ -0,0 +1,97 @@
+// 大整数：i64 运算溢出时自动提升为 BigInt，结果落回 i64 范围时再降级为 Integer
+
+use std::rc::Rc;
+
+use num_bigint::BigInt;
+use num_traits::{Pow, ToPrimitive};
+
+use super::Expression;
+
+/// 至少一侧为大整数时的操作数：两侧皆整数按大整数算，另一侧为浮点则都转浮点
+pub enum BigOperands {
+    Int(BigInt, BigInt),
+    Float(f64, f64),
+}
+
+impl From<BigInt> for Expression {
+    /// 能放进 i64 时降级为 Integer
+    fn from(n: BigInt) -> Self {
+        match n.to_i64() {
+            Some(i) => Self::Integer(i),
+            None => Self::BigInt(Rc::new(n)),
+        }
+    }
+}
+
+impl Expression {
+    /// 整数值（含大整数），其余类型返回 None
+    pub fn to_bigint(&self) -> Option<BigInt> {
+        match self {
+            Self::Integer(i) => Some(BigInt::from(*i)),
+            Self::BigInt(n) => Some(n.as_ref().clone()),
+            _ => None,
+        }
+    }
+
+    /// 数值转浮点，大整数超出 f64 范围时为无穷
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Integer(i) => Some(*i as f64),
+            Self::BigInt(n) => n.to_f64(),
+            Self::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    /// 整数字面量，超出 i64 时解析为大整数
+    pub fn parse_int(s: &str) -> Option<Self> {
+        match s.parse::<i64>() {
+            Ok(i) => Some(Self::Integer(i)),
+            Err(_) => s.parse::<BigInt>().ok().map(Self::from),
+        }
+    }
+}
+
+pub fn big_operands(m: &Expression, n: &Expression) -> Option<BigOperands> {
+    use Expression::{BigInt as Big, Float, Integer};
+    match (m, n) {
+        (Big(_), Big(_) | Integer(_)) | (Integer(_), Big(_)) => {
+            Some(BigOperands::Int(m.to_bigint()?, n.to_bigint()?))
+        }
+        (Big(a), Float(b)) => Some(BigOperands::Float(a.to_f64()?, *b)),
+        (Float(a), Big(b)) => Some(BigOperands::Float(*a, b.to_f64()?)),
+        _ => None,
+    }
+}
+
+/// 至少一侧为大整数时按 int/float 计算，否则返回 None 交由常规分支处理
+pub fn big_arith(
+    m: &Expression,
+    n: &Expression,
+    int: impl FnOnce(BigInt, BigInt) -> BigInt,
+    float: impl FnOnce(f64, f64) -> f64,
+) -> Option<Expression> {
+    Some(match big_operands(m, n)? {
+        BigOperands::Int(a, b) => Expression::from(int(a, b)),
+        BigOperands::Float(a, b) => Expression::Float(float(a, b)),
+    })
+}
+
+/// 幂、移位结果的位数上限，防止 2 ^ 4000000000 之类耗尽内存
+pub const MAX_BIGINT_BITS: u64 = 1 << 24;
+
+/// 整数幂，溢出时提升为大整数；base 非整数或结果过大时返回 None
+pub fn int_pow(base: &Expression, exponent: u32) -> Option<Expression> {
+    let big = match base {
+        Expression::Integer(b) => match b.checked_pow(exponent) {
+            Some(n) => return Some(Expression::Integer(n)),
+            None => BigInt::from(*b),
+        },
+        Expression::BigInt(b) => b.as_ref().clone(),
+        _ => return None,
+    };
+    if big.bits().saturating_mul(exponent as u64) > MAX_BIGINT_BITS {
+        return None;
+    }
+    Some(Expression::from(Pow::pow(big, exponent)))
+}
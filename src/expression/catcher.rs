@@ -6,6 +6,21 @@ use super::{CatchType, Expression, eval::State};
 use common_macros::b_tree_map;
 // use common_macros::hash_map;
 
+/// 传给错误处理函数的错误信息；外部命令失败时带有退出码和信号
+pub fn error_map(e: &RuntimeError) -> Expression {
+    let (exit_code, signal) = e.exit_status();
+    Expression::from(b_tree_map! {
+        String::from("code") => Expression::Integer(e.code()),
+        String::from("msg") => Expression::String(e.kind.to_string()),
+        String::from("expr") => Expression::String(e.context.to_string()),
+        String::from("ast") => Expression::String(format!("{:?}",e.context)),
+        String::from("type") => Expression::String(e.context.type_name()),
+        String::from("depth") => Expression::Integer(e.depth as i64),
+        String::from("exit_code") => exit_code.map_or(Expression::None, |c| Expression::Integer(c as i64)),
+        String::from("signal") => signal.map_or(Expression::None, |s| Expression::Integer(s as i64)),
+    })
+}
+
 pub fn catch_error(
     e: RuntimeError,
     typ: &CatchType,
@@ -21,15 +36,7 @@ pub fn catch_error(
                     // dbg!(&deel.type_name());
 
                     deel.as_ref()
-                        .apply(vec![Expression::from(b_tree_map! {
-                            String::from("code") => Expression::Integer(e.code()),
-                            String::from("msg") => Expression::String(e.kind.to_string()),
-                            String::from("expr") => Expression::String(e.context.to_string()),
-                            String::from("ast") => Expression::String(format!("{:?}",e.context)),
-                            String::from("type") => Expression::String(e.context.type_name()),
-                            String::from("depth") => Expression::Integer(e.depth as i64),
-                            // String::from("expr") => Expression::Quote(body.clone())
-                        })])
+                        .apply(vec![error_map(&e)])
                         .eval_mut(state, env, depth + 1)
                 }
                 _ => deel.as_ref().eval_mut(state, env, depth + 1),
@@ -46,15 +53,7 @@ pub fn catch_error(
             eprintln!("\x1b[38;5;9m{e:?}\x1b[0m");
             Ok(Expression::None)
         }
-        CatchType::PrintOver => Ok(Expression::from(b_tree_map! {
-            String::from("code") => Expression::Integer(e.code()),
-            String::from("msg") => Expression::String(e.kind.to_string()),
-            String::from("expr") => Expression::String(e.context.to_string()),
-            String::from("ast") => Expression::String(format!("{:?}",e.context)),
-            String::from("type") => Expression::String(e.context.type_name()),
-            String::from("depth") => Expression::Integer(e.depth as i64),
-            // String::from("expr") => Expression::Quote(body.clone())
-        })),
+        CatchType::PrintOver => Ok(error_map(&e)),
         CatchType::Terminate => Err(RuntimeError::new(
             RuntimeErrorKind::Terminated,
             e.context,
@@ -1,8 +1,11 @@
 use crate::{
-    Environment, Expression, RuntimeError, RuntimeErrorKind, childman,
-    expression::pty::exec_in_pty,
+    Environment, Expression, RuntimeError, RuntimeErrorKind,
+    childman::{self, ExitInfo, JobState},
+    expression::{LumeStream, StreamItem, alias, pty::exec_in_pty},
+    libs::is_top_or_se,
+    profiler::{self, Kind},
     runtime::{IFS_CMD, ifs_contains},
-    utils::{expand_home, get_current_path},
+    utils::{abs, expand_home, get_current_path},
 };
 
 use super::eval::State;
@@ -11,69 +14,729 @@ use glob::glob;
 // use portable_pty::ChildKiller;
 // use portable_pty::{CommandBuilder, PtySize, native_pty_system};
 use std::{
-    io::Write,
-    process::{Command, Stdio},
+    cell::RefCell,
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, PipeReader, PipeWriter, Read, Write},
+    process::{Child, Command, ExitStatus, Stdio},
+    rc::Rc,
+    thread::JoinHandle,
+    time::Instant,
 };
 
-/// mode: 1=null_stdout, 2=null_err, 4=err_to_stdout,
-/// 8=background, 11=background,shutdown_all
-/// 16=pty
-/// 执行单个命令（支持管道）
-#[allow(clippy::too_many_arguments)]
-fn exec_single_cmd(
+#[cfg(unix)]
+const PTY_CMDS: [&str; 16] = [
+    "lume", "bash", "sh", "fish", "top", "btop", "vi", "passwd", "ssh", "script", "expect",
+    "telnet", "screen", "tmux", "ftp", "sftp",
+];
+#[cfg(windows)]
+const PTY_CMDS: [&str; 11] = [
+    "lume",
+    "fish",
+    "ssh",
+    "telnet",
+    "screen",
+    "tmux",
+    "cmd.exe",
+    "PowerShell",
+    "Cygwin",
+    "WinPTY",
+    "ConPTY",
+];
+
+/// 流式管道：上一段外部命令的 stdout，以及仍在运行的上游进程
+pub struct PipeStream {
+    stdout: PipeReader,
+    upstream: Upstream,
+}
+
+impl PipeStream {
+    /// 读尽剩余输出并回收上游进程（下游为内置函数/lambda 时使用）
+    pub fn collect(self) -> Vec<u8> {
+        let PipeStream {
+            mut stdout,
+            mut upstream,
+        } = self;
+        let mut buf = Vec::new();
+        match upstream.feed.take() {
+            // 惰性流输入：线程读取输出，当前线程写入
+            Some((writer, stream)) => {
+                let reading = std::thread::spawn(move || {
+                    let mut buf = Vec::new();
+                    stdout.read_to_end(&mut buf).map(|_| buf)
+                });
+                let _ = feed_stream(writer, &stream);
+                buf = reading.join().ok().and_then(|r| r.ok()).unwrap_or_default();
+            }
+            None => {
+                let _ = stdout.read_to_end(&mut buf);
+                drop(stdout);
+            }
+        }
+        let stages = upstream.wait_all();
+        // 下游为内置函数时，以最后一段外部命令作为最近命令状态
+        if let Some((_, st)) = stages.last() {
+            childman::set_last_status(st.info.clone());
+        }
+        childman::set_pipe_status(stages.iter().map(|(_, st)| st.code()).collect());
+        buf
+    }
+}
+
+/// 管道中一段的退出状态
+struct StageStatus {
+    /// 该段的命令表达式，用作错误上下文
+    job: Expression,
+    info: ExitInfo,
+    status: ExitStatus,
+}
+
+impl StageStatus {
+    /// 退出码，被信号终止时为 128+信号
+    fn code(&self) -> i32 {
+        self.info.status_code()
+    }
+}
+
+/// 流式管道中尚未回收的上游进程。
+/// 异常退出时（如下游启动失败）由 Drop 终止并回收，避免遗留进程。
+#[derive(Default)]
+struct Upstream {
+    children: Vec<Child>,
+    /// 各段的命令行、命令表达式与调用信息，与 children 一一对应
+    stages: Vec<(String, Expression, Invocation)>,
+    /// 作业控制下整条管道共用的进程组
+    pgid: Option<u32>,
+    /// 已启动各段的命令行，用于作业表
+    cmdline: String,
+    /// 各段参数中的进程替换，随管道结束而回收
+    subs: Vec<ProcSub>,
+    /// 首段的惰性流输入，待末段启动后再写入
+    feed: Option<(PipeWriter, LumeStream)>,
+}
+
+impl Upstream {
+    /// 等待各段结束，按顺序返回各段的命令行与退出信息
+    fn wait_all(&mut self) -> Vec<(String, StageStatus)> {
+        let stages = self
+            .children
+            .drain(..)
+            .zip(self.stages.drain(..))
+            .filter_map(|(mut child, (stage, job, inv))| {
+                let status = child.wait().ok()?;
+                let info = inv.exit_info(child.id(), Some(&status));
+                Some((stage, StageStatus { job, info, status }))
+            })
+            .collect();
+        self.subs.clear();
+        stages
+    }
+    /// 转为后台/挂起作业后，由作业表接管上游进程
+    fn detach(&mut self) {
+        self.children.clear();
+        self.stages.clear();
+        self.subs.iter_mut().for_each(ProcSub::detach);
+        self.subs.clear();
+    }
+}
+
+impl Drop for Upstream {
+    fn drop(&mut self) {
+        for child in self.children.iter_mut() {
+            if let Ok(None) = child.try_wait() {
+                let _ = child.kill();
+            }
+            let _ = child.wait();
+        }
+    }
+}
+
+/// 进程替换交给外部命令的管道一端
+enum PipeEnd {
+    Read(PipeReader),
+    Write(PipeWriter),
+}
+
+/// 进程替换 `<(cmd)` `>(cmd)`：管道一端以 `/dev/fd/N` 传给外部命令，
+/// 命令结束后关闭该端并回收替换进程。
+struct ProcSub {
+    end: Option<PipeEnd>,
+    upstream: Upstream,
+    /// 回填捕获的输出，或转发 `>(cmd)` 的输出
+    worker: Option<JoinHandle<()>>,
+}
+
+impl ProcSub {
+    #[cfg(unix)]
+    fn raw_fd(&self) -> i32 {
+        use std::os::fd::AsRawFd;
+        match &self.end {
+            Some(PipeEnd::Read(r)) => r.as_raw_fd(),
+            Some(PipeEnd::Write(w)) => w.as_raw_fd(),
+            None => -1,
+        }
+    }
+    /// 外部命令转入后台时不再等待替换进程
+    fn detach(&mut self) {
+        self.end = None;
+        self.upstream.children.clear();
+        self.worker = None;
+    }
+}
+
+impl Drop for ProcSub {
+    fn drop(&mut self) {
+        // 先关闭本端：`<(cmd)` 写入失败退出，`>(cmd)` 读到 EOF 退出
+        self.end = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+        self.upstream.wait_all();
+    }
+}
+
+thread_local! {
+    /// 参数求值期间创建的进程替换，由所属命令取走
+    static PROC_SUBS: RefCell<Vec<ProcSub>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 命令参数求值的作用域：取走其间登记的进程替换，提前返回时一并清理
+struct ProcSubScope(usize);
+
+impl ProcSubScope {
+    fn new() -> Self {
+        Self(PROC_SUBS.with_borrow(Vec::len))
+    }
+    fn take(self) -> Vec<ProcSub> {
+        PROC_SUBS.with_borrow_mut(|subs| subs.split_off(self.0.min(subs.len())))
+    }
+}
+
+impl Drop for ProcSubScope {
+    fn drop(&mut self) {
+        let stale = PROC_SUBS.with_borrow_mut(|subs| subs.split_off(self.0.min(subs.len())));
+        drop(stale);
+    }
+}
+
+/// 求值进程替换 `<(cmd)` / `>(cmd)`，返回可供外部命令打开的路径 `/dev/fd/N`。
+/// 外部命令以流式管道连接；`<(..)` 中的其他表达式先求值，再将结果写入管道。
+#[cfg(unix)]
+pub fn process_subst(
     job: &Expression,
-    cmdstr: &String,
-    args: Option<Vec<String>>,
+    op: &str,
+    inner: &Expression,
+    state: &mut State,
     env: &mut Environment,
-    input: Option<Vec<u8>>, // 前一条命令的输出（None 表示第一个命令）
-    pipe_out: bool,
-    mode: u8,
     depth: usize,
-) -> Result<Option<Vec<u8>>, RuntimeError> {
-    // dbg!("------ exec:------", &cmdstr, &args);
-    // dbg!(&mode, &pipe_out, &input.is_some());
-    // dbg!(&input);
-    if mode & 16 != 0 {
-        // spawn_in_pty(cmdstr, args, env, input);
-        return exec_in_pty(cmdstr, args, env, input)
-            .map_err(|e| RuntimeError::new(e, job.clone(), depth));
+) -> Result<Expression, RuntimeError> {
+    let inner = match inner {
+        Expression::Group(e) => e.as_ref(),
+        e => e,
+    };
+    let pipe_error =
+        |e| RuntimeError::from_io_error(e, "process substitution".into(), job.clone(), depth);
+    let is_stream = is_stream_source(inner);
+    let sub = match op {
+        "<" => {
+            let (result, stream) = eval_subst(inner, None, is_stream, state, env, depth);
+            match (result?, stream) {
+                (_, Some(PipeStream { stdout, upstream })) => ProcSub {
+                    end: Some(PipeEnd::Read(stdout)),
+                    upstream,
+                    worker: None,
+                },
+                (output, None) => {
+                    let (r, mut w) = std::io::pipe().map_err(pipe_error)?;
+                    let bytes = to_bytes(Some(output)).unwrap_or_default();
+                    ProcSub {
+                        end: Some(PipeEnd::Read(r)),
+                        upstream: Upstream::default(),
+                        worker: Some(std::thread::spawn(move || {
+                            let _ = w.write_all(&bytes);
+                        })),
+                    }
+                }
+            }
+        }
+        _ => {
+            if !is_stream {
+                return Err(RuntimeError::common(
+                    "process substitution `>(..)` requires an external command".into(),
+                    job.clone(),
+                    depth,
+                ));
+            }
+            let (r, w) = std::io::pipe().map_err(pipe_error)?;
+            let input = PipeStream {
+                stdout: r,
+                upstream: Upstream::default(),
+            };
+            let (result, stream) = eval_subst(inner, Some(input), true, state, env, depth);
+            result?;
+            let PipeStream {
+                mut stdout,
+                upstream,
+            } = stream
+                .filter(|s| !s.upstream.children.is_empty())
+                .ok_or_else(|| {
+                    RuntimeError::common(
+                        format!("process substitution `>({inner})` did not start").into(),
+                        job.clone(),
+                        depth,
+                    )
+                })?;
+            ProcSub {
+                end: Some(PipeEnd::Write(w)),
+                upstream,
+                worker: Some(std::thread::spawn(move || {
+                    let _ = std::io::copy(&mut stdout, &mut std::io::stdout());
+                })),
+            }
+        }
+    };
+    let path = format!("/dev/fd/{}", sub.raw_fd());
+    PROC_SUBS.with_borrow_mut(|subs| subs.push(sub));
+    Ok(Expression::String(path))
+}
+
+#[cfg(windows)]
+pub fn process_subst(
+    job: &Expression,
+    _op: &str,
+    _inner: &Expression,
+    _state: &mut State,
+    _env: &mut Environment,
+    depth: usize,
+) -> Result<Expression, RuntimeError> {
+    Err(RuntimeError::from_io_error(
+        std::io::ErrorKind::Unsupported.into(),
+        "process substitution".into(),
+        job.clone(),
+        depth,
+    ))
+}
+
+/// 以独立的输入求值替换命令，不占用外层命令的管道数据；
+/// 求值结果及其交出的流一并返回
+fn eval_subst(
+    inner: &Expression,
+    input: Option<PipeStream>,
+    stream_out: bool,
+    state: &mut State,
+    env: &mut Environment,
+    depth: usize,
+) -> (Result<Expression, RuntimeError>, Option<PipeStream>) {
+    let saved_stream = state.take_stream();
+    let saved_pipe = state.pipe_out();
+    if let Some(input) = input {
+        state.set_stream(input);
     }
-    let mut cmd = Command::new(cmdstr);
+    if stream_out {
+        state.set(State::STREAM_OUT);
+    }
+    let result = match inner.eval_mut(state, env, depth + 1) {
+        Ok(Expression::Symbol(name)) if stream_out => Expression::Command(
+            Rc::new(Expression::Symbol(name)),
+            Rc::new(vec![]),
+        )
+        .eval_mut(state, env, depth + 1),
+        other => other,
+    };
+    state.clear(State::STREAM_OUT);
+    let stream = state.take_stream();
+    if let Some(data) = saved_pipe {
+        state.pipe_in(data);
+    }
+    if let Some(saved) = saved_stream {
+        state.set_stream(saved);
+    }
+    (result, stream)
+}
 
-    let ar = args.unwrap_or_default();
+/// 子进程中取消替换管道的 close-on-exec，使 `/dev/fd/N` 可被打开
+#[cfg(unix)]
+fn pass_fds(cmd: &mut Command, subs: &[ProcSub]) {
+    use std::os::unix::process::CommandExt;
+    if subs.is_empty() {
+        return;
+    }
+    let fds: Vec<i32> = subs.iter().map(ProcSub::raw_fd).collect();
+    unsafe {
+        cmd.pre_exec(move || {
+            for fd in fds.iter() {
+                if libc::fcntl(*fd, libc::F_SETFD, 0) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
 
-    cmd.args(ar)
-        .envs(env.get_root().get_bindings_string())
-        .current_dir(get_current_path(env));
+/// 命令的标准输入来源
+enum CmdInput {
+    Inherit,
+    Bytes(Vec<u8>),
+    Stream(PipeStream),
+    /// `< file`
+    File(File),
+    /// 惰性流，逐项按行写入
+    Items(LumeStream),
+}
 
-    // 设置 stdin
-    if input.is_some() {
-        cmd.stdin(Stdio::piped());
-    } else {
-        cmd.stdin(Stdio::inherit());
+/// 待写入子进程 stdin 的输入
+enum Feed {
+    Bytes(Vec<u8>),
+    Items(PipeWriter, LumeStream),
+}
+
+/// 将惰性流逐项写入子进程 stdin；下游提前退出（管道断开）时停止拉取
+fn feed_stream(mut writer: PipeWriter, stream: &LumeStream) -> Result<(), RuntimeError> {
+    while let Some(item) = stream.pull() {
+        let line = format!("{}\n", item?);
+        if writer.write_all(line.as_bytes()).is_err() {
+            break;
+        }
     }
+    Ok(())
+}
 
-    // 设置 stdout（如果是交互式命令，直接接管终端）
-    if pipe_out {
-        cmd.stdout(Stdio::piped());
-    } else if mode & 1 != 0 {
-        cmd.stdout(Stdio::null());
-    } else {
-        // if mode == 0 {
-        cmd.stdout(Stdio::inherit());
+/// 接到 stdin 上的管道，写入端留待命令启动后使用
+fn stdin_pipe(
+    cmd: &mut Command,
+    job: &Expression,
+    depth: usize,
+) -> Result<PipeWriter, RuntimeError> {
+    let (reader, writer) = std::io::pipe().map_err(|e| {
+        RuntimeError::from_io_error(e, "create stdin pipe".into(), job.clone(), depth)
+    })?;
+    cmd.stdin(Stdio::from(reader));
+    Ok(writer)
+}
+
+/// 标准错误的去向
+enum ErrTarget {
+    File(File),
+    /// `2>&1`
+    Stdout,
+}
+
+/// 命令上的 fd 重定向：`< f` `<<< s` `2> f` `2>> f` `2>&1` `1>&2` `&> f` `&>> f`
+#[derive(Default)]
+struct Redirects {
+    stdin: Option<CmdInput>,
+    stdout: Option<File>,
+    /// `1>&2`
+    stdout_to_err: bool,
+    stderr: Option<ErrTarget>,
+    /// 参数中的进程替换
+    subs: Vec<ProcSub>,
+}
+
+impl Redirects {
+    fn is_empty(&self) -> bool {
+        self.stdin.is_none()
+            && self.stdout.is_none()
+            && !self.stdout_to_err
+            && self.stderr.is_none()
+            && self.subs.is_empty()
     }
+}
+
+/// stdout 未被重定向时的默认去向
+enum OutKind {
+    Pipe,
+    Null,
+    Inherit,
+}
 
-    // 设置 stderr
-    if mode & 2 != 0 {
-        cmd.stderr(Stdio::null());
-    } else if mode & 4 != 0 {
-        cmd.stderr(Stdio::piped());
+/// 从参数中取出重定向标记及其目标
+fn take_redirects(
+    args: &mut Vec<String>,
+    env: &mut Environment,
+    job: &Expression,
+    depth: usize,
+) -> Result<Redirects, RuntimeError> {
+    let mut redir = Redirects::default();
+    let mut rest = Vec::with_capacity(args.len());
+    let mut it = std::mem::take(args).into_iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "2>&1" => redir.stderr = Some(ErrTarget::Stdout),
+            "1>&2" => redir.stdout_to_err = true,
+            "<" | "<<<" | "2>" | "2>>" | "&>" | "&>>" => {
+                let target = it.next().ok_or_else(|| {
+                    RuntimeError::common(
+                        format!("missing target for redirection `{arg}`").into(),
+                        job.clone(),
+                        depth,
+                    )
+                })?;
+                match arg.as_str() {
+                    "<<<" => {
+                        redir.stdin = Some(CmdInput::Bytes(format!("{target}\n").into_bytes()))
+                    }
+                    "<" => {
+                        let file = open_redirect(&target, None, env, job, depth)?;
+                        redir.stdin = Some(CmdInput::File(file));
+                    }
+                    "2>" | "2>>" => {
+                        let file = open_redirect(&target, Some(arg == "2>>"), env, job, depth)?;
+                        redir.stderr = Some(ErrTarget::File(file));
+                    }
+                    _ => {
+                        let file = open_redirect(&target, Some(arg == "&>>"), env, job, depth)?;
+                        redir.stdout = Some(file);
+                        redir.stderr = Some(ErrTarget::Stdout);
+                    }
+                }
+            }
+            _ => rest.push(arg),
+        }
+    }
+    *args = rest;
+    Ok(redir)
+}
+
+/// append 为 None 时以只读打开，否则创建/截断或追加写入
+fn open_redirect(
+    target: &str,
+    append: Option<bool>,
+    env: &mut Environment,
+    job: &Expression,
+    depth: usize,
+) -> Result<File, RuntimeError> {
+    let path = abs(target, env);
+    match append {
+        None => File::open(&path),
+        Some(append) => OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(append)
+            .truncate(!append)
+            .open(&path),
+    }
+    .map_err(|e| {
+        RuntimeError::from_io_error(e, format!("redirect `{target}`").into(), job.clone(), depth)
+    })
+}
+
+/// 按重定向设置 stdout/stderr。
+/// stdout 需要捕获时返回管道读端；`2>&1` 或 mode 4 时 stderr 一并写入 stdout 的去向。
+fn set_output(
+    cmd: &mut Command,
+    redir: &mut Redirects,
+    out: OutKind,
+    mode: u8,
+) -> std::io::Result<Option<PipeReader>> {
+    let merge = match redir.stderr {
+        Some(ErrTarget::Stdout) => true,
+        Some(ErrTarget::File(_)) => false,
+        None => mode & 4 != 0,
+    };
+    let mut reader = None;
+    if let Some(file) = redir.stdout.take() {
+        if merge {
+            cmd.stderr(file.try_clone()?);
+        }
+        cmd.stdout(file);
+    } else if redir.stdout_to_err {
+        cmd.stdout(std::io::stderr());
+        if merge {
+            cmd.stderr(std::io::stderr());
+        }
     } else {
-        cmd.stderr(Stdio::inherit());
+        match out {
+            OutKind::Pipe => {
+                let (r, w) = std::io::pipe()?;
+                if merge {
+                    cmd.stderr(w.try_clone()?);
+                }
+                cmd.stdout(w);
+                reader = Some(r);
+            }
+            OutKind::Null => {
+                cmd.stdout(Stdio::null());
+                if merge {
+                    cmd.stderr(Stdio::null());
+                }
+            }
+            OutKind::Inherit => {
+                cmd.stdout(Stdio::inherit());
+                if merge {
+                    cmd.stderr(std::io::stdout());
+                }
+            }
+        }
     }
+    if !merge {
+        match redir.stderr.take() {
+            Some(ErrTarget::File(file)) => cmd.stderr(file),
+            _ if mode & 2 != 0 => cmd.stderr(Stdio::null()),
+            _ => cmd.stderr(Stdio::inherit()),
+        };
+    }
+    Ok(reader)
+}
 
-    // 执行命令
-    let mut child = cmd.spawn().map_err(|e| match &e.kind() {
+/// `cmd < file` 改写为带 `<` 重定向参数的命令；左侧须为未被变量遮蔽的外部命令
+pub fn as_stdin_redirect(
+    lhs: &Expression,
+    rhs: &Rc<Expression>,
+    env: &Environment,
+) -> Option<Expression> {
+    let (name, mut args) = match lhs {
+        Expression::Symbol(name) => (name, vec![]),
+        Expression::Command(cmd, args) => match cmd.as_ref() {
+            Expression::Symbol(name) => (name, args.as_ref().clone()),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    if is_top_or_se(name) || alias::get_alias(name).is_some() || env.is_defined(name) {
+        return None;
+    }
+    args.push(Expression::String("<".into()));
+    args.push(rhs.as_ref().clone());
+    Some(Expression::Command(
+        Rc::new(Expression::Symbol(name.clone())),
+        Rc::new(args),
+    ))
+}
+
+/// 管道右侧是否为可直接流式连接的外部命令：
+/// 非内置函数、非别名、非 pty 命令，且不通过 `_` 接收管道数据。
+pub fn is_external_stage(expr: &Expression) -> bool {
+    match expr {
+        Expression::Symbol(name) => is_external_cmd(name),
+        Expression::Command(cmd, args) => match cmd.as_ref() {
+            Expression::Symbol(name) => {
+                is_external_cmd(name) && !args.iter().any(|a| a == &Expression::Blank)
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// 管道左侧是否会产生流式输出：外部命令，或以外部命令结尾的管道。
+pub fn is_stream_source(expr: &Expression) -> bool {
+    match expr {
+        Expression::Pipe(op, _, rhs) if op == "|" => is_external_stage(rhs),
+        other => is_external_stage(other),
+    }
+}
+
+fn is_external_cmd(name: &str) -> bool {
+    !is_top_or_se(name) && alias::get_alias(name).is_none() && !PTY_CMDS.contains(&name)
+}
+
+/// 一次外部命令调用，结束时写入最近命令状态
+struct Invocation {
+    argv: Vec<String>,
+    started: Instant,
+}
+
+impl Invocation {
+    /// 退出信息；status 为 None 表示进程尚未回收
+    fn exit_info(self, pid: u32, status: Option<&ExitStatus>) -> ExitInfo {
+        let info = ExitInfo::new(self.argv, pid, self.started);
+        match status {
+            Some(status) => info.with_status(status),
+            None => info,
+        }
+    }
+
+    fn finish(self, pid: u32, status: Option<&ExitStatus>) -> ExitInfo {
+        let info = self.exit_info(pid, status);
+        childman::set_last_status(info.clone());
+        childman::set_pipe_status(vec![info.status_code()]);
+        info
+    }
+}
+
+fn exit_error(
+    cmdstr: &str,
+    msg: String,
+    info: &ExitInfo,
+    job: &Expression,
+    depth: usize,
+) -> RuntimeError {
+    RuntimeError::new(
+        RuntimeErrorKind::CommandExited {
+            cmd: cmdstr.to_owned(),
+            msg,
+            code: info.code,
+            signal: info.signal,
+        },
+        job.clone(),
+        depth,
+    )
+}
+
+fn format_cmdline(cmdstr: &str, args: &Option<Vec<String>>) -> String {
+    match args {
+        Some(a) if !a.is_empty() => format!("{cmdstr} {}", a.join(" ")),
+        _ => cmdstr.to_string(),
+    }
+}
+
+fn build_command(cmdstr: &String, args: Option<Vec<String>>, env: &mut Environment) -> Command {
+    let mut cmd = Command::new(cmdstr);
+    cmd.args(args.unwrap_or_default())
+        .envs(env.get_root().get_bindings_string())
+        .current_dir(get_current_path(env));
+    for (k, v) in env_overrides() {
+        match v {
+            Some(v) => cmd.env(k, v),
+            None => cmd.env_remove(k),
+        };
+    }
+    cmd
+}
+
+// ============== 临时环境变量 ==============
+
+thread_local! {
+    /// with_env 设置的临时环境变量，逐层叠加；None 表示移除
+    static ENV_OVERRIDES: RefCell<Vec<BTreeMap<String, Option<String>>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 临时环境变量的作用域，只影响其间启动的外部命令，离开作用域时撤销
+pub struct EnvOverride(());
+
+impl EnvOverride {
+    pub fn push(vars: BTreeMap<String, Option<String>>) -> Self {
+        ENV_OVERRIDES.with_borrow_mut(|stack| stack.push(vars));
+        Self(())
+    }
+}
+
+impl Drop for EnvOverride {
+    fn drop(&mut self) {
+        ENV_OVERRIDES.with_borrow_mut(|stack| stack.pop());
+    }
+}
+
+/// 合并各层临时环境变量，内层优先
+pub fn env_overrides() -> BTreeMap<String, Option<String>> {
+    ENV_OVERRIDES.with_borrow(|stack| {
+        stack
+            .iter()
+            .flat_map(|vars| vars.iter().map(|(k, v)| (k.clone(), v.clone())))
+            .collect()
+    })
+}
+
+/// 启动命令。cmd 在此处被消费，以便及时关闭父进程持有的管道端。
+fn spawn_command(
+    mut cmd: Command,
+    cmdstr: &String,
+    job: &Expression,
+    depth: usize,
+) -> Result<Child, RuntimeError> {
+    cmd.spawn().map_err(|e| match &e.kind() {
         std::io::ErrorKind::NotFound => RuntimeError::new(
             RuntimeErrorKind::ProgramNotFound(cmdstr.clone()),
             job.clone(),
@@ -90,16 +753,322 @@ fn exec_single_cmd(
             job.clone(),
             depth,
         ),
+    })
+}
+
+/// 流式管道的中间段：启动后不等待，stdout 直接交给下一段。
+/// job_ctl 为真时，首段新建进程组并取得终端，后续各段加入该组。
+#[allow(clippy::too_many_arguments)]
+fn spawn_stream_stage(
+    job: &Expression,
+    cmdstr: &String,
+    args: Option<Vec<String>>,
+    env: &mut Environment,
+    input: CmdInput,
+    mut redir: Redirects,
+    mode: u8,
+    job_ctl: bool,
+    depth: usize,
+) -> Result<PipeStream, RuntimeError> {
+    let cmdline = format_cmdline(cmdstr, &args);
+    let argv = std::iter::once(cmdstr.clone())
+        .chain(args.iter().flatten().cloned())
+        .collect();
+    let mut cmd = build_command(cmdstr, args, env);
+    let (bytes_in, mut upstream) = match input {
+        CmdInput::Inherit => {
+            cmd.stdin(Stdio::inherit());
+            (None, Upstream::default())
+        }
+        CmdInput::Bytes(b) => {
+            cmd.stdin(Stdio::piped());
+            (Some(b), Upstream::default())
+        }
+        CmdInput::Stream(PipeStream { stdout, upstream }) => {
+            cmd.stdin(Stdio::from(stdout));
+            (None, upstream)
+        }
+        CmdInput::File(file) => {
+            cmd.stdin(Stdio::from(file));
+            (None, Upstream::default())
+        }
+        CmdInput::Items(stream) => {
+            let writer = stdin_pipe(&mut cmd, job, depth)?;
+            let mut upstream = Upstream::default();
+            upstream.feed = Some((writer, stream));
+            (None, upstream)
+        }
+    };
+    let stdout = set_output(&mut cmd, &mut redir, OutKind::Pipe, mode)
+        .map_err(|e| {
+            RuntimeError::from_io_error(
+                e,
+                format!("pipe stdout of `{cmdstr}`").into(),
+                job.clone(),
+                depth,
+            )
+        })?
+        .ok_or_else(|| {
+            RuntimeError::common(
+                format!("capture stdout of `{cmdstr}`").into(),
+                job.clone(),
+                depth,
+            )
+        })?;
+
+    // 超时监视下各管道自成一组，便于整体终止
+    let new_group = (job_ctl || childman::watchdog_active()) && upstream.children.is_empty();
+    if new_group || upstream.pgid.is_some() {
+        childman::prepare_job(&mut cmd, upstream.pgid);
+    }
+    #[cfg(unix)]
+    pass_fds(&mut cmd, &redir.subs);
+
+    let inv = Invocation {
+        argv,
+        started: Instant::now(),
+    };
+    let mut child = spawn_command(cmd, cmdstr, job, depth)?;
+    upstream.subs.append(&mut redir.subs);
+    if new_group {
+        upstream.pgid = Some(child.id());
+        childman::watch_group(child.id());
+        if job_ctl {
+            childman::give_terminal(child.id());
+        }
+    }
+    if let Some(input) = bytes_in
+        && let Some(mut stdin) = child.stdin.take()
+    {
+        // 独立线程写入：下游尚未启动时，避免管道写满而死锁
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(&input);
+        });
+    }
+    upstream.children.push(child);
+    upstream.stages.push((cmdline.clone(), job.clone(), inv));
+    if upstream.cmdline.is_empty() {
+        upstream.cmdline = cmdline;
+    } else {
+        upstream.cmdline = format!("{} | {cmdline}", upstream.cmdline);
+    }
+    Ok(PipeStream { stdout, upstream })
+}
+
+/// mode: 1=null_stdout, 2=null_err, 4=err_to_stdout,
+/// 8=background, 11=background,shutdown_all
+/// 16=pty
+/// 执行单个命令（支持管道）
+#[allow(clippy::too_many_arguments)]
+fn exec_single_cmd(
+    job: &Expression,
+    cmdstr: &String,
+    args: Option<Vec<String>>,
+    env: &mut Environment,
+    input: CmdInput, // 前一条命令的输出（Inherit 表示第一个命令）
+    mut redir: Redirects,
+    pipe_out: bool,
+    mode: u8,
+    depth: usize,
+) -> Result<Option<Vec<u8>>, RuntimeError> {
+    // dbg!("------ exec:------", &cmdstr, &args);
+    // dbg!(&mode, &pipe_out, &input.is_some());
+    // dbg!(&input);
+    let _span = profiler::span(Kind::Command, || cmdstr.clone());
+    if mode & 16 != 0 {
+        let input = match input {
+            CmdInput::Inherit => None,
+            CmdInput::Bytes(b) => Some(b),
+            CmdInput::Stream(s) => Some(s.collect()),
+            CmdInput::Items(stream) => {
+                let mut buf = Vec::new();
+                while let Some(item) = stream.pull() {
+                    buf.extend(format!("{}\n", item?).into_bytes());
+                }
+                Some(buf)
+            }
+            CmdInput::File(mut f) => {
+                let mut buf = Vec::new();
+                f.read_to_end(&mut buf).map_err(|e| {
+                    RuntimeError::from_io_error(e, "read redirect".into(), job.clone(), depth)
+                })?;
+                Some(buf)
+            }
+        };
+        // spawn_in_pty(cmdstr, args, env, input);
+        return exec_in_pty(cmdstr, args, env, input)
+            .map_err(|e| RuntimeError::new(e, job.clone(), depth));
+    }
+    let background = mode & 8 != 0;
+    let mut cmdline = format_cmdline(cmdstr, &args);
+    let argv = std::iter::once(cmdstr.clone())
+        .chain(args.iter().flatten().cloned())
+        .collect();
+    let mut cmd = build_command(cmdstr, args, env);
+
+    // 设置 stdin
+    let (input, mut upstream) = match input {
+        // 无作业控制时，后台命令不读取终端
+        CmdInput::Inherit if background && !childman::job_control_enabled() => {
+            cmd.stdin(Stdio::null());
+            (None, None)
+        }
+        CmdInput::Inherit => {
+            cmd.stdin(Stdio::inherit());
+            (None, None)
+        }
+        CmdInput::Bytes(b) => {
+            cmd.stdin(Stdio::piped());
+            (Some(Feed::Bytes(b)), None)
+        }
+        CmdInput::Stream(PipeStream {
+            stdout,
+            mut upstream,
+        }) => {
+            cmd.stdin(Stdio::from(stdout));
+            let feed = upstream.feed.take().map(|(w, s)| Feed::Items(w, s));
+            (feed, Some(upstream))
+        }
+        CmdInput::File(file) => {
+            cmd.stdin(Stdio::from(file));
+            (None, None)
+        }
+        CmdInput::Items(stream) => {
+            let writer = stdin_pipe(&mut cmd, job, depth)?;
+            (Some(Feed::Items(writer, stream)), None)
+        }
+    };
+
+    // 设置 stdout/stderr
+    let out = if pipe_out {
+        OutKind::Pipe
+    } else if mode & 1 != 0 {
+        OutKind::Null
+    } else {
+        OutKind::Inherit
+    };
+    let reader = set_output(&mut cmd, &mut redir, out, mode).map_err(|e| {
+        RuntimeError::from_io_error(
+            e,
+            format!("redirect output of `{cmdstr}`").into(),
+            job.clone(),
+            depth,
+        )
     })?;
 
-    // 写入输入
-    if let Some(input) = input {
-        child
-            .stdin
-            .as_mut()
-            .unwrap()
-            .write_all(&input)
-            .map_err(|e| {
+    // 进程组：沿用管道已有的组；否则后台命令、超时监视下或交互前台命令新建一组
+    let up_pgid = upstream.as_ref().and_then(|u| u.pgid);
+    let new_group = up_pgid.is_none()
+        && (background
+            || childman::watchdog_active()
+            || (childman::job_control_enabled()
+                && !pipe_out
+                && upstream.as_ref().is_none_or(|u| u.children.is_empty())));
+    if new_group || up_pgid.is_some() {
+        childman::prepare_job(&mut cmd, up_pgid);
+    }
+    if let Some(u) = upstream.as_ref() {
+        cmdline = format!("{} | {cmdline}", u.cmdline);
+    }
+    #[cfg(unix)]
+    pass_fds(&mut cmd, &redir.subs);
+
+    // 执行命令
+    let inv = Invocation {
+        argv,
+        started: Instant::now(),
+    };
+    let child = spawn_command(cmd, cmdstr, job, depth)?;
+    let pgid = up_pgid.or(new_group.then(|| child.id()));
+    if new_group {
+        childman::watch_group(child.id());
+    }
+
+    let result = wait_child(
+        job, cmdstr, &cmdline, child, inv, input, reader, pipe_out, mode, pgid, depth,
+    );
+    if pgid.is_some() && !background {
+        childman::reclaim_terminal();
+    }
+    // 末段结束后回收上游进程；已转为作业的由作业表接管
+    let mut result = result;
+    if let Some(upstream) = upstream.as_mut() {
+        if pgid.is_some_and(childman::is_job) || background {
+            upstream.detach();
+        } else {
+            let stages = upstream.wait_all();
+            let last = childman::last_status().status_code();
+            childman::set_pipe_status(
+                stages
+                    .iter()
+                    .map(|(_, st)| st.code())
+                    .chain(std::iter::once(last))
+                    .collect(),
+            );
+            // pipefail：末段成功时，以最后一个失败的上游段作为管道的结果
+            if result.is_ok()
+                && crate::with_pipefail_enabled(|b| b)
+                && let Some((stage, st)) = stages.iter().rev().find(|(_, st)| st.code() != 0)
+            {
+                result = Err(exit_error(
+                    stage,
+                    st.status.to_string(),
+                    &st.info,
+                    &st.job,
+                    depth,
+                ));
+            }
+        }
+    }
+    if pgid.is_some_and(childman::is_job) || background {
+        redir.subs.iter_mut().for_each(ProcSub::detach);
+    }
+    result
+}
+
+/// 写入输入并等待命令结束，按 mode 处理输出
+#[allow(clippy::too_many_arguments)]
+fn wait_child(
+    job: &Expression,
+    cmdstr: &String,
+    cmdline: &str,
+    mut child: Child,
+    inv: Invocation,
+    input: Option<Feed>,
+    mut reader: Option<PipeReader>,
+    pipe_out: bool,
+    mode: u8,
+    pgid: Option<u32>,
+    depth: usize,
+) -> Result<Option<Vec<u8>>, RuntimeError> {
+    // 惰性流输入：当前线程逐项写入，捕获的输出改由线程读取
+    let mut reading = None;
+    let mut feed_err = None;
+    let input = match input {
+        Some(Feed::Items(writer, stream)) => {
+            reading = reader.take().map(|mut r| {
+                std::thread::spawn(move || {
+                    let mut buf = Vec::new();
+                    r.read_to_end(&mut buf).map(|_| buf)
+                })
+            });
+            feed_err = feed_stream(writer, &stream).err();
+            None
+        }
+        Some(Feed::Bytes(b)) => Some(b),
+        None => None,
+    };
+    // 写入输入，写完即关闭 stdin
+    if let Some(input) = input
+        && let Some(mut stdin) = child.stdin.take()
+    {
+        // 捕获输出时独立线程写入，避免输入输出同时写满管道而死锁
+        if reader.is_some() {
+            std::thread::spawn(move || {
+                let _ = stdin.write_all(&input);
+            });
+        } else {
+            stdin.write_all(&input).map_err(|e| {
                 RuntimeError::from_io_error(
                     e,
                     format!("pipe stdin to `{cmdstr}`").into(),
@@ -107,27 +1076,28 @@ fn exec_single_cmd(
                     depth,
                 )
             })?;
-    }
-
-    // TODO not work yet
-    // 合并 stderr 和 stdout 的流
-    if mode & 4 != 0
-        && let Some(mut stderr) = child.stderr.take()
-    {
-        // if let Some(mut stdout) = child.stdout.take() {
-        std::io::copy(&mut stderr, &mut std::io::stdout()).unwrap(); // 将 stderr 合并到 stdout
-        // }
+        }
     }
 
     // 中断信号处理：SIGINT 由全局 handler 捕获（在 repl.rs 中安装），
     // 仅设置标志位，不会杀死 lume 自身。
     // 子进程会收到终端发送的 SIGINT 并退出，wait 随后返回。
-    childman::set_child(child.id());
+    let pid = child.id();
+    childman::set_child(pid);
 
     // 获取输出
     if pipe_out {
-        // 管道捕获
-        let output = child.wait_with_output().map_err(|e| {
+        // 管道捕获（stdout 被重定向到文件时无输出可读）
+        let mut stdout = Vec::new();
+        let read = match (reader, reading) {
+            (Some(mut r), _) => r.read_to_end(&mut stdout).map(|_| ()),
+            (None, Some(reading)) => reading
+                .join()
+                .unwrap_or_else(|_| Err(std::io::Error::other("output reader panicked")))
+                .map(|buf| stdout = buf),
+            (None, None) => Ok(()),
+        };
+        let status = child.wait().and_then(|s| read.map(|_| s)).map_err(|e| {
             RuntimeError::from_io_error(
                 e,
                 format!("wait output of cmd `{cmdstr}`").into(),
@@ -136,59 +1106,71 @@ fn exec_single_cmd(
             )
         })?;
         childman::clear_child();
+        let info = inv.finish(pid, Some(&status));
+        if let Some(e) = feed_err {
+            return Err(e);
+        }
 
-        if output.status.success() {
+        if status.success() {
             if mode & 1 == 0 {
                 //未关闭标准输出才返回结果
-                Ok(Some(output.stdout))
+                Ok(Some(stdout))
             } else {
                 Ok(None)
             }
         } else if mode & 4 != 0 {
-            //错误输出>标准输出
-            let mut combined = Vec::new();
-            combined.extend(output.stdout);
-            // println!(
-            //     "err output: {}",
-            //     String::from_utf8_lossy(&output.stderr.clone().as_ref())
-            // );
-            combined.extend(output.stderr);
-            // println!("Combined output: {}", String::from_utf8_lossy(&combined));
-            Ok(Some(combined))
+            //错误输出>标准输出：已合并在 stdout 中
+            Ok(Some(stdout))
+        } else if mode & 2 == 0 {
+            //未关闭错误输出才返回错误
+            Err(exit_error(cmdstr, status.to_string(), &info, job, depth))
+        } else if mode & 1 == 0 {
+            // 如果关闭了错误输出，则尝试返回标准输出，二者可能同时存在。
+            Ok(Some(stdout))
         } else {
-            if mode & 2 == 0 {
-                //未关闭错误输出才返回错误
-                let stderr = String::from_utf8_lossy(output.stderr.as_ref())
-                    .trim()
-                    .to_string();
-                if stderr.is_empty() {
-                    // return Err(RuntimeError::CommandFailed2(cmdstr.to_owned(), stderr));
-                    return Err(RuntimeError::new(
-                        RuntimeErrorKind::CommandFailed2(cmdstr.to_owned(), stderr),
-                        job.clone(),
-                        depth,
-                    ));
-                }
-            } else if mode & 1 == 0 {
-                // 如果关闭了错误输出，则尝试返回标准输出，二者可能同时存在。
-                return Ok(Some(output.stdout));
-            }
             Ok(None)
         }
     } else if mode & 8 != 0 {
-        // 后台运行
+        // 后台运行：登记为作业
+        childman::clear_child();
+        inv.finish(pid, None);
+        let pgid = pgid.unwrap_or(child.id());
+        let id = childman::add_job(pgid, child.id(), cmdline.to_string(), JobState::Running);
+        if childman::job_control_enabled() {
+            eprintln!("[{id}] {pgid}");
+        }
         Ok(None)
     } else {
         // 正常模式
-        let status = child.wait().map_err(|e| {
-            RuntimeError::from_io_error(
-                e,
-                format!("wait cmd `{cmdstr}`").into(),
-                job.clone(),
-                depth,
-            )
-        })?;
+        let status = match pgid {
+            // 作业控制：等待期间可被 Ctrl-Z 挂起
+            #[cfg(unix)]
+            Some(pgid) => match childman::wait_foreground(child.id(), pgid, cmdline) {
+                Ok(Some(status)) => status,
+                Ok(None) => return Ok(None),
+                Err(e) => {
+                    return Err(RuntimeError::from_io_error(
+                        e,
+                        format!("wait cmd `{cmdstr}`").into(),
+                        job.clone(),
+                        depth,
+                    ));
+                }
+            },
+            _ => child.wait().map_err(|e| {
+                RuntimeError::from_io_error(
+                    e,
+                    format!("wait cmd `{cmdstr}`").into(),
+                    job.clone(),
+                    depth,
+                )
+            })?,
+        };
         childman::clear_child();
+        let info = inv.finish(pid, Some(&status));
+        if let Some(e) = feed_err {
+            return Err(e);
+        }
 
         if status.success() {
             Ok(None)
@@ -207,43 +1189,31 @@ fn exec_single_cmd(
                     _ if code > 128 => "Fatal signal",
                     _ => return Ok(None),
                 };
-                return Err(RuntimeError::new(
-                    RuntimeErrorKind::CommandFailed2(
-                        cmdstr.to_owned(),
-                        format!("{emsg}\n{}", status.to_string()),
-                    ),
-                    job.clone(),
+                return Err(exit_error(
+                    cmdstr,
+                    format!("{emsg}\n{}", status.to_string()),
+                    &info,
+                    job,
                     depth,
                 ));
             }
 
-            Err(RuntimeError::new(
-                RuntimeErrorKind::CommandFailed2(cmdstr.to_owned(), status.to_string()),
-                job.clone(),
-                depth,
-            ))
+            Err(exit_error(cmdstr, status.to_string(), &info, job, depth))
         } else {
             Ok(None)
         }
     }
 }
 
-// 管道
-pub fn handle_command(
+/// 求值命令参数：展开通配符与 `~`，按 IFS 拆分字符串，列表逐项展开
+fn eval_args(
     job: &Expression,
-    cmd: &String,
     args: &[Expression],
     state: &mut State,
     env: &mut Environment,
     depth: usize,
-) -> Result<Expression, RuntimeError> {
-    // dbg!("   3.--->handle_command:", &cmd, &args);
-
-    let is_in_assign = state.contains(State::IN_ASSIGN);
-    let pipe_out = is_in_assign || state.contains(State::IN_PIPE);
+) -> Result<Vec<String>, RuntimeError> {
     let mut cmd_args = vec![];
-    // state.set(State::SKIP_BUILTIN_SEEK | State::IN_ASSIGN);
-    state.set(State::IN_ASSIGN);
 
     for arg in args {
         // for flattened_arg in Expression::flatten(vec![arg.eval_mut(env, depth + 1)?]) {
@@ -297,31 +1267,12 @@ pub fn handle_command(
             _ => cmd_args.push(format!("{e_arg}")),
         }
     }
-    // state.clear(State::SKIP_BUILTIN_SEEK);
-    if !is_in_assign {
-        state.clear(State::IN_ASSIGN);
-    }
+    Ok(cmd_args)
+}
 
-    #[cfg(unix)]
-    let pty_cmds = [
-        "lume", "bash", "sh", "fish", "top", "btop", "vi", "passwd", "ssh", "script", "expect",
-        "telnet", "screen", "tmux", "ftp", "sftp",
-    ];
-    #[cfg(windows)]
-    let pty_cmds = [
-        "lume",
-        "fish",
-        "ssh",
-        "telnet",
-        "screen",
-        "tmux",
-        "cmd.exe",
-        "PowerShell",
-        "Cygwin",
-        "WinPTY",
-        "ConPTY",
-    ];
-    let cmd_mode: u8 = match state.contains(State::PTY_MODE) || pty_cmds.contains(&cmd.as_str()) {
+/// 取出命令末尾的执行方式后缀 `&` `&-` `&?` `&.` `&+`；pty 命令为 16
+fn take_mode(cmd: &str, cmd_args: &mut Vec<String>, state: &State) -> u8 {
+    match state.contains(State::PTY_MODE) || PTY_CMDS.contains(&cmd) {
         true => 16,
         false => match cmd_args.last() {
             Some(s) => match s.as_str() {
@@ -349,16 +1300,85 @@ pub fn handle_command(
             },
             _ => 0,
         },
-    };
+    }
+}
+
+// 管道
+pub fn handle_command(
+    job: &Expression,
+    cmd: &String,
+    args: &[Expression],
+    state: &mut State,
+    env: &mut Environment,
+    depth: usize,
+) -> Result<Expression, RuntimeError> {
+    // dbg!("   3.--->handle_command:", &cmd, &args);
+
+    let is_in_assign = state.contains(State::IN_ASSIGN);
+    let pipe_out = is_in_assign || state.contains(State::IN_PIPE);
+    let stream_out = state.contains(State::STREAM_OUT);
+    state.clear(State::STREAM_OUT);
+    // 先取走上游的流，避免被参数中的子命令读走
+    let stream_in = state.take_stream();
+    let subs_scope = ProcSubScope::new();
+    // state.set(State::SKIP_BUILTIN_SEEK | State::IN_ASSIGN);
+    state.set(State::IN_ASSIGN);
+    let mut cmd_args = eval_args(job, args, state, env, depth)?;
+    // state.clear(State::SKIP_BUILTIN_SEEK);
+    if !is_in_assign {
+        state.clear(State::IN_ASSIGN);
+    }
+
+    let mut cmd_mode = take_mode(cmd, &mut cmd_args, state);
+    let mut redir = take_redirects(&mut cmd_args, env, job, depth)?;
+    redir.subs = subs_scope.take();
+    // 有重定向或进程替换时不经过 pty
+    if cmd_mode == 16 && !redir.is_empty() {
+        cmd_mode = 0;
+    }
     // dbg!(args, &cmd_args);
     let last_input = state.pipe_out();
-    let pipe_input = to_bytes(last_input);
+    let input = match (redir.stdin.take(), stream_in) {
+        (Some(input), _) => input,
+        (None, Some(stream)) => CmdInput::Stream(stream),
+        (None, None) => match last_input {
+            Some(Expression::Stream(stream)) => CmdInput::Items(stream),
+            last_input => match to_bytes(last_input) {
+                Some(bytes) => CmdInput::Bytes(bytes),
+                None => CmdInput::Inherit,
+            },
+        },
+    };
+    // 下游也是外部命令：不等待，交出 stdout
+    if stream_out && cmd_mode & (1 | 8 | 16) == 0 && redir.stdout.is_none() && !redir.stdout_to_err
+    {
+        let job_ctl = childman::job_control_enabled() && !is_in_assign;
+        // 该段自身的命令表达式，pipefail 报错时指向失败的这一段
+        let stage = Expression::Command(
+            Rc::new(Expression::Symbol(cmd.clone())),
+            Rc::new(args.to_vec()),
+        );
+        let stream = spawn_stream_stage(
+            &stage,
+            cmd,
+            Some(cmd_args),
+            env,
+            input,
+            redir,
+            cmd_mode,
+            job_ctl,
+            depth,
+        )?;
+        state.set_stream(stream);
+        return Ok(Expression::None);
+    }
     let result = exec_single_cmd(
         job,
         cmd,
         Some(cmd_args),
         env,
-        pipe_input,
+        input,
+        redir,
         pipe_out,
         cmd_mode,
         depth,
@@ -366,6 +1386,330 @@ pub fn handle_command(
     Ok(to_expr(result))
 }
 
+/// 外部命令 stdout 的逐行读取，供惰性流使用；提前丢弃时终止命令
+struct StdoutLines {
+    lines: std::io::Lines<BufReader<std::process::ChildStdout>>,
+    child: Option<Child>,
+    cmdstr: String,
+    job: Expression,
+}
+
+impl Iterator for StdoutLines {
+    type Item = StreamItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.lines.next() {
+            Some(Ok(line)) => Some(Ok(Expression::String(line))),
+            Some(Err(e)) => Some(Err(RuntimeError::from_io_error(
+                e,
+                format!("read output of `{}`", self.cmdstr).into(),
+                self.job.clone(),
+                0,
+            ))),
+            None => {
+                let status = self.child.take()?.wait().ok()?;
+                if status.success() {
+                    return None;
+                }
+                let info = ExitInfo::default().with_status(&status);
+                Some(Err(exit_error(
+                    &self.cmdstr,
+                    status.to_string(),
+                    &info,
+                    &self.job,
+                    0,
+                )))
+            }
+        }
+    }
+}
+
+impl Drop for StdoutLines {
+    fn drop(&mut self) {
+        if let Some(child) = self.child.as_mut() {
+            if let Ok(None) = child.try_wait() {
+                let _ = child.kill();
+            }
+            let _ = child.wait();
+        }
+    }
+}
+
+/// 启动外部命令，按行惰性读取其 stdout
+pub fn stdout_lines(
+    cmdstr: &String,
+    args: Vec<String>,
+    env: &mut Environment,
+    job: &Expression,
+) -> Result<LumeStream, RuntimeError> {
+    let mut cmd = build_command(cmdstr, Some(args), env);
+    cmd.stdin(Stdio::null()).stdout(Stdio::piped());
+    let mut child = spawn_command(cmd, cmdstr, job, 0)?;
+    let stdout = child.stdout.take().unwrap();
+    Ok(LumeStream::new(StdoutLines {
+        lines: BufReader::new(stdout).lines(),
+        child: Some(child),
+        cmdstr: cmdstr.clone(),
+        job: job.clone(),
+    }))
+}
+
+/// 分别捕获 stdout 与 stderr 的一次运行
+pub struct Captured {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub info: ExitInfo,
+}
+
+/// 运行外部命令并分别捕获 stdout/stderr；非零退出不视为错误。
+/// stderr 由独立线程读取，避免任一管道写满而死锁。
+pub fn run_captured(
+    job: &Expression,
+    cmdstr: &String,
+    args: Vec<String>,
+    env: &mut Environment,
+    depth: usize,
+) -> Result<Captured, RuntimeError> {
+    let argv = std::iter::once(cmdstr.clone())
+        .chain(args.iter().cloned())
+        .collect();
+    let mut cmd = build_command(cmdstr, Some(args), env);
+    cmd.stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let watched = childman::watchdog_active();
+    if watched {
+        childman::prepare_job(&mut cmd, None);
+    }
+    let inv = Invocation {
+        argv,
+        started: Instant::now(),
+    };
+    let mut child = spawn_command(cmd, cmdstr, job, depth)?;
+    if watched {
+        childman::watch_group(child.id());
+    }
+    let stderr_reader = child.stderr.take().map(|mut err| {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = err.read_to_end(&mut buf);
+            buf
+        })
+    });
+    let mut stdout = Vec::new();
+    if let Some(mut out) = child.stdout.take() {
+        let _ = out.read_to_end(&mut stdout);
+    }
+    let stderr = stderr_reader
+        .and_then(|r| r.join().ok())
+        .unwrap_or_default();
+    let status = child.wait().map_err(|e| {
+        RuntimeError::from_io_error(e, format!("wait `{cmdstr}`").into(), job.clone(), depth)
+    })?;
+    let info = inv.finish(child.id(), Some(&status));
+    Ok(Captured {
+        stdout,
+        stderr,
+        info,
+    })
+}
+
+// ============== 并行分发 ==============
+
+/// 并行分发中的一项：参数已在各自环境中求值、尚未启动的外部命令
+pub struct ParCmd {
+    job: Expression,
+    cmdstr: String,
+    argv: Vec<String>,
+    cmd: Command,
+    mode: u8,
+    redir: Redirects,
+}
+
+/// 表达式为外部命令调用时，求值其参数并构造命令；否则返回 None
+pub fn prepare_par_cmd(
+    expr: &Expression,
+    state: &mut State,
+    env: &mut Environment,
+    depth: usize,
+) -> Option<Result<ParCmd, RuntimeError>> {
+    let Expression::Command(cmd, args) = expr else {
+        return None;
+    };
+    let Expression::Symbol(name) = cmd.as_ref() else {
+        return None;
+    };
+    if !is_external_stage(expr) || env.is_defined(name) {
+        return None;
+    }
+    let scope = ProcSubScope::new();
+    let prepared = eval_args(expr, args, state, env, depth).and_then(|mut args| {
+        let mode = take_mode(name, &mut args, state);
+        let mut redir = take_redirects(&mut args, env, expr, depth)?;
+        redir.subs = scope.take();
+        let argv = std::iter::once(name.clone())
+            .chain(args.iter().cloned())
+            .collect();
+        Ok(ParCmd {
+            job: expr.clone(),
+            cmdstr: name.clone(),
+            argv,
+            cmd: build_command(name, Some(args), env),
+            mode,
+            redir,
+        })
+    });
+    Some(prepared)
+}
+
+/// 已启动的并行命令：由独立线程写入输入、读尽输出并等待结束
+struct ParChild {
+    job: Expression,
+    cmdstr: String,
+    inv: Invocation,
+    pid: u32,
+    mode: u8,
+    worker: JoinHandle<(Vec<u8>, std::io::Result<ExitStatus>)>,
+    _subs: Vec<ProcSub>,
+}
+
+impl ParChild {
+    fn finish(self, depth: usize) -> Result<Expression, RuntimeError> {
+        let (out, status) = self
+            .worker
+            .join()
+            .unwrap_or_else(|_| (vec![], Err(std::io::ErrorKind::Other.into())));
+        let status = status.map_err(|e| {
+            RuntimeError::from_io_error(
+                e,
+                format!("wait `{}`", self.cmdstr).into(),
+                self.job.clone(),
+                depth,
+            )
+        })?;
+        let info = self.inv.finish(self.pid, Some(&status));
+        match status.success() {
+            true if self.mode & 1 != 0 => Ok(Expression::None),
+            true => Ok(to_expr(Some(out))),
+            false => Err(exit_error(
+                &self.cmdstr,
+                status.to_string(),
+                &info,
+                &self.job,
+                depth,
+            )),
+        }
+    }
+}
+
+fn spawn_par(
+    par: ParCmd,
+    done: std::sync::mpsc::Sender<()>,
+    depth: usize,
+) -> Result<ParChild, RuntimeError> {
+    let ParCmd {
+        job,
+        cmdstr,
+        argv,
+        mut cmd,
+        mode,
+        mut redir,
+    } = par;
+    // 并行的子进程不读取终端
+    let input = match redir.stdin.take() {
+        Some(CmdInput::File(file)) => {
+            cmd.stdin(Stdio::from(file));
+            None
+        }
+        Some(CmdInput::Bytes(b)) => {
+            cmd.stdin(Stdio::piped());
+            Some(b)
+        }
+        _ => {
+            cmd.stdin(Stdio::null());
+            None
+        }
+    };
+    let out = match mode & 1 != 0 {
+        true => OutKind::Null,
+        false => OutKind::Pipe,
+    };
+    let reader = set_output(&mut cmd, &mut redir, out, mode).map_err(|e| {
+        RuntimeError::from_io_error(
+            e,
+            format!("redirect output of `{cmdstr}`").into(),
+            job.clone(),
+            depth,
+        )
+    })?;
+    #[cfg(unix)]
+    pass_fds(&mut cmd, &redir.subs);
+    let watched = childman::watchdog_active();
+    if watched {
+        childman::prepare_job(&mut cmd, None);
+    }
+    let inv = Invocation {
+        argv,
+        started: Instant::now(),
+    };
+    let mut child = spawn_command(cmd, &cmdstr, &job, depth)?;
+    if watched {
+        childman::watch_group(child.id());
+    }
+    let pid = child.id();
+    let worker = std::thread::spawn(move || {
+        if let Some(input) = input
+            && let Some(mut stdin) = child.stdin.take()
+        {
+            std::thread::spawn(move || {
+                let _ = stdin.write_all(&input);
+            });
+        }
+        let mut out = Vec::new();
+        if let Some(mut reader) = reader {
+            let _ = reader.read_to_end(&mut out);
+        }
+        let status = child.wait();
+        let _ = done.send(());
+        (out, status)
+    });
+    Ok(ParChild {
+        job,
+        cmdstr,
+        inv,
+        pid,
+        mode,
+        worker,
+        _subs: std::mem::take(&mut redir.subs),
+    })
+}
+
+/// 以至多 jobs 个并发子进程运行各命令，按输入顺序返回各自的结果
+pub fn run_parallel(
+    cmds: Vec<ParCmd>,
+    jobs: usize,
+    depth: usize,
+) -> Vec<Result<Expression, RuntimeError>> {
+    let (done, finished) = std::sync::mpsc::channel();
+    let mut running = 0;
+    let mut children = Vec::with_capacity(cmds.len());
+    for par in cmds {
+        if running >= jobs.max(1) {
+            let _ = finished.recv();
+            running -= 1;
+        }
+        let child = spawn_par(par, done.clone(), depth);
+        if child.is_ok() {
+            running += 1;
+        }
+        children.push(child);
+    }
+    children
+        .into_iter()
+        .map(|child| child.and_then(|c| c.finish(depth)))
+        .collect()
+}
+
 pub fn to_expr(bytes_out: Option<Vec<u8>>) -> Expression {
     match bytes_out {
         Some(b) => Expression::String(String::from_utf8_lossy(&b).trim().to_string()),
@@ -0,0 +1,771 @@
+// AST 二进制编码，供解析缓存使用
+// 运行期才出现的值（流、表格、结构体实例）不可编码，遇到时放弃缓存
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+
+use chrono::DateTime;
+use regex_lite::Regex;
+
+use super::record::RecordType;
+use super::slot::slot_of;
+use super::{
+    CatchType, ChainCall, DestructurePattern, Expression, FileSize, LumeRegex, MatchPattern,
+    SizeUnit,
+};
+
+/// 编码器：定长小端整数，字符串与序列带长度前缀
+#[derive(Default)]
+pub struct Encoder {
+    pub buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    pub fn u64(&mut self, v: u64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn bool(&mut self, v: bool) {
+        self.u8(v as u8);
+    }
+
+    pub fn str(&mut self, s: &str) {
+        self.u64(s.len() as u64);
+        self.buf.extend_from_slice(s.as_bytes());
+    }
+
+    pub fn opt_str(&mut self, s: &Option<String>) {
+        self.bool(s.is_some());
+        if let Some(s) = s {
+            self.str(s);
+        }
+    }
+
+    pub fn strs(&mut self, v: &[String]) {
+        self.u64(v.len() as u64);
+        v.iter().for_each(|s| self.str(s));
+    }
+
+    pub fn list<T>(
+        &mut self,
+        items: impl ExactSizeIterator<Item = T>,
+        mut f: impl FnMut(&mut Self, T) -> Option<()>,
+    ) -> Option<()> {
+        self.u64(items.len() as u64);
+        items.into_iter().try_for_each(|item| f(self, item))
+    }
+
+    fn opt<T>(&mut self, v: Option<T>, f: impl FnOnce(&mut Self, T) -> Option<()>) -> Option<()> {
+        self.bool(v.is_some());
+        match v {
+            Some(v) => f(self, v),
+            None => Some(()),
+        }
+    }
+
+    fn exprs(&mut self, v: &[Expression]) -> Option<()> {
+        self.list(v.iter(), Self::expr)
+    }
+
+    fn map<'a>(
+        &mut self,
+        m: impl ExactSizeIterator<Item = (&'a String, &'a Expression)>,
+    ) -> Option<()> {
+        self.list(m, |e, (k, v)| {
+            e.str(k);
+            e.expr(v)
+        })
+    }
+
+    pub fn expr(&mut self, expr: &Expression) -> Option<()> {
+        match expr {
+            Expression::Group(e) => {
+                self.u8(0);
+                self.expr(e)
+            }
+            Expression::BinaryOp(op, l, r) => {
+                self.u8(1);
+                self.str(op);
+                self.expr(l)?;
+                self.expr(r)
+            }
+            Expression::UnaryOp(op, e, prefix) => {
+                self.u8(2);
+                self.str(op);
+                self.expr(e)?;
+                self.bool(*prefix);
+                Some(())
+            }
+            Expression::RangeOp(op, l, r, step) => {
+                self.u8(3);
+                self.str(op);
+                self.expr(l)?;
+                self.expr(r)?;
+                self.opt(step.as_deref(), Self::expr)
+            }
+            Expression::Pipe(op, l, r) => {
+                self.u8(4);
+                self.str(op);
+                self.expr(l)?;
+                self.expr(r)
+            }
+            Expression::Symbol(s) => {
+                self.u8(5);
+                self.str(s);
+                Some(())
+            }
+            Expression::Variable(s) => {
+                self.u8(6);
+                self.str(s);
+                Some(())
+            }
+            Expression::Integer(n) => {
+                self.u8(7);
+                self.u64(*n as u64);
+                Some(())
+            }
+            Expression::Float(f) => {
+                self.u8(8);
+                self.u64(f.to_bits());
+                Some(())
+            }
+            Expression::Bytes(b) => {
+                self.u8(9);
+                self.u64(b.len() as u64);
+                self.buf.extend_from_slice(b);
+                Some(())
+            }
+            Expression::String(s) => {
+                self.u8(10);
+                self.str(s);
+                Some(())
+            }
+            Expression::StringTemplate(parts) => {
+                self.u8(11);
+                self.exprs(parts)
+            }
+            Expression::RegexDef(s) => {
+                self.u8(12);
+                self.str(s);
+                Some(())
+            }
+            Expression::TimeDef(s) => {
+                self.u8(13);
+                self.str(s);
+                Some(())
+            }
+            Expression::Regex(r) => {
+                self.u8(14);
+                self.str(r.regex.as_str());
+                Some(())
+            }
+            Expression::Boolean(b) => {
+                self.u8(15);
+                self.bool(*b);
+                Some(())
+            }
+            Expression::None => {
+                self.u8(16);
+                Some(())
+            }
+            Expression::List(v) => {
+                self.u8(17);
+                self.exprs(v)
+            }
+            Expression::BSet(s) => {
+                self.u8(18);
+                self.list(s.iter(), Self::expr)
+            }
+            Expression::HMap(m) => {
+                self.u8(19);
+                self.map(m.iter())
+            }
+            Expression::Map(m) => {
+                self.u8(20);
+                self.map(m.iter())
+            }
+            Expression::Index(a, b) => {
+                self.u8(21);
+                self.expr(a)?;
+                self.expr(b)
+            }
+            Expression::Property(a, b) => {
+                self.u8(22);
+                self.expr(a)?;
+                self.expr(b)
+            }
+            Expression::Del(s) => {
+                self.u8(23);
+                self.str(s);
+                Some(())
+            }
+            Expression::Declare(s, e) => {
+                self.u8(24);
+                self.str(s);
+                self.expr(e)
+            }
+            Expression::Assign(s, e) => {
+                self.u8(25);
+                self.str(s);
+                self.expr(e)
+            }
+            Expression::SetParent(s, e) => {
+                self.u8(26);
+                self.str(s);
+                self.expr(e)
+            }
+            Expression::Export(s, e) => {
+                self.u8(27);
+                self.str(s);
+                self.opt(e.as_deref(), Self::expr)
+            }
+            Expression::AliasDef(s, e) => {
+                self.u8(28);
+                self.str(s);
+                self.expr(e)
+            }
+            Expression::For(var, index, iter, body) => {
+                self.u8(29);
+                self.str(var);
+                self.opt_str(index);
+                self.expr(iter)?;
+                self.expr(body)
+            }
+            Expression::While(cond, body) => {
+                self.u8(30);
+                self.expr(cond)?;
+                self.expr(body)
+            }
+            Expression::Loop(body) => {
+                self.u8(31);
+                self.expr(body)
+            }
+            Expression::Match(value, arms) => {
+                self.u8(32);
+                self.expr(value)?;
+                self.list(arms.iter(), |e, (pats, body)| {
+                    e.list(pats.iter(), Self::pattern)?;
+                    e.expr(body)
+                })
+            }
+            Expression::If(cond, then, els) => {
+                self.u8(33);
+                self.expr(cond)?;
+                self.expr(then)?;
+                self.expr(els)
+            }
+            Expression::Apply(f, args) => {
+                self.u8(34);
+                self.expr(f)?;
+                self.exprs(args)
+            }
+            Expression::Command(f, args) => {
+                self.u8(35);
+                self.expr(f)?;
+                self.exprs(args)
+            }
+            Expression::CommandRaw(f, args) => {
+                self.u8(36);
+                self.expr(f)?;
+                self.exprs(args)
+            }
+            Expression::Lambda(params, body, captured) => {
+                self.u8(37);
+                self.strs(params);
+                self.expr(body)?;
+                self.opt(captured.as_ref(), |e, m| e.map(m.iter()))
+            }
+            Expression::Function(name, params, rest, body, decorators, ret) => {
+                self.u8(38);
+                self.str(name);
+                self.list(params.iter(), |e, (p, def, ty)| {
+                    e.destructure(p)?;
+                    e.opt(def.as_ref(), Self::expr)?;
+                    e.opt_str(ty);
+                    Some(())
+                })?;
+                self.opt_str(rest);
+                self.expr(body)?;
+                self.list(decorators.iter(), |e, (d, args)| {
+                    e.str(d);
+                    e.opt(args.as_ref(), |e, a| e.exprs(a))
+                })?;
+                self.opt_str(ret);
+                Some(())
+            }
+            Expression::StructDef(def) => {
+                self.u8(39);
+                self.str(&def.name);
+                self.list(def.fields.iter(), |e, (f, def, ty)| {
+                    e.str(f);
+                    e.opt(def.as_ref(), Self::expr)?;
+                    e.opt_str(ty);
+                    Some(())
+                })?;
+                self.map(def.methods.iter())
+            }
+            Expression::Return(e) => {
+                self.u8(40);
+                self.expr(e)
+            }
+            Expression::Break(e) => {
+                self.u8(41);
+                self.expr(e)
+            }
+            Expression::Continue => {
+                self.u8(42);
+                Some(())
+            }
+            Expression::Sequence(v) => {
+                self.u8(43);
+                self.exprs(v)
+            }
+            Expression::Block(v) => {
+                self.u8(44);
+                self.exprs(v)
+            }
+            Expression::Quote(e) => {
+                self.u8(45);
+                self.expr(e)
+            }
+            Expression::Catch(body, ty, handler) => {
+                self.u8(46);
+                self.expr(body)?;
+                self.u8(match ty {
+                    CatchType::Ignore => 0,
+                    CatchType::PrintStd => 1,
+                    CatchType::PrintErr => 2,
+                    CatchType::PrintOver => 3,
+                    CatchType::Terminate => 4,
+                    CatchType::Deel => 5,
+                    CatchType::ToBoolean => 6,
+                });
+                self.opt(handler.as_deref(), Self::expr)
+            }
+            Expression::Try(body, var, catch, finally) => {
+                self.u8(47);
+                self.expr(body)?;
+                self.opt_str(var);
+                self.opt(catch.as_deref(), Self::expr)?;
+                self.opt(finally.as_deref(), Self::expr)
+            }
+            Expression::Defer(e) => {
+                self.u8(48);
+                self.expr(e)
+            }
+            Expression::Range(r, step) => {
+                self.u8(49);
+                self.u64(r.start as u64);
+                self.u64(r.end as u64);
+                self.u64(*step as u64);
+                Some(())
+            }
+            Expression::DateTime(t) => {
+                self.u8(50);
+                let t = t.and_utc();
+                self.u64(t.timestamp() as u64);
+                self.u64(t.timestamp_subsec_nanos() as u64);
+                Some(())
+            }
+            Expression::FileSize(fs) => {
+                self.u8(51);
+                self.u64(fs.size);
+                self.u8(match fs.unit {
+                    SizeUnit::B => 0,
+                    SizeUnit::K => 1,
+                    SizeUnit::M => 2,
+                    SizeUnit::G => 3,
+                    SizeUnit::T => 4,
+                    SizeUnit::P => 5,
+                    SizeUnit::None => 6,
+                });
+                Some(())
+            }
+            Expression::Chain(base, calls) => {
+                self.u8(52);
+                self.expr(base)?;
+                self.list(calls.iter(), |e, call| {
+                    e.str(&call.method);
+                    e.exprs(&call.args)
+                })
+            }
+            Expression::PipeMethod(method, args) => {
+                self.u8(53);
+                self.str(method);
+                self.exprs(args)
+            }
+            Expression::DestructureAssign(pats, e) => {
+                self.u8(54);
+                self.list(pats.iter(), Self::destructure)?;
+                self.expr(e)
+            }
+            Expression::Use(alias, path, names) => {
+                self.u8(55);
+                self.opt_str(alias);
+                self.str(path);
+                self.opt(names.as_ref(), |e, n| {
+                    e.strs(n);
+                    Some(())
+                })
+            }
+            Expression::ModuleCall(modules, e) => {
+                self.u8(56);
+                self.strs(modules);
+                self.expr(e)
+            }
+            Expression::Blank => {
+                self.u8(57);
+                Some(())
+            }
+            // 槽位只在本进程内有效，按变量名保存，载入时重新分配
+            Expression::LocalVar(_, name, sym) => {
+                self.u8(58);
+                self.str(name);
+                self.bool(*sym);
+                Some(())
+            }
+            Expression::LocalSet(_, name, declare, e) => {
+                self.u8(60);
+                self.str(name);
+                self.bool(*declare);
+                self.expr(e)
+            }
+            Expression::BigInt(n) => {
+                self.u8(59);
+                self.str(&n.to_string());
+                Some(())
+            }
+            Expression::Stream(_) | Expression::Table(_) | Expression::Record(..) => None,
+        }
+    }
+
+    fn pattern(&mut self, pat: &MatchPattern) -> Option<()> {
+        match pat {
+            MatchPattern::Value(e) => {
+                self.u8(0);
+                self.expr(e)
+            }
+            MatchPattern::Bind(s) => {
+                self.u8(1);
+                self.str(s);
+                Some(())
+            }
+            MatchPattern::Type(bind, ty) => {
+                self.u8(2);
+                self.opt_str(bind);
+                self.str(ty);
+                Some(())
+            }
+            MatchPattern::List(items, rest) => {
+                self.u8(3);
+                self.list(items.iter(), Self::pattern)?;
+                self.opt(rest.as_deref(), Self::pattern)
+            }
+            MatchPattern::Map(fields) => {
+                self.u8(4);
+                self.list(fields.iter(), |e, (k, p)| {
+                    e.str(k);
+                    e.pattern(p)
+                })
+            }
+            MatchPattern::Guard(p, cond) => {
+                self.u8(5);
+                self.pattern(p)?;
+                self.expr(cond)
+            }
+        }
+    }
+
+    fn destructure(&mut self, pat: &DestructurePattern) -> Option<()> {
+        match pat {
+            DestructurePattern::Identifier(s) => {
+                self.u8(0);
+                self.str(s);
+            }
+            DestructurePattern::Renamed((k, v)) => {
+                self.u8(1);
+                self.str(k);
+                self.str(v);
+            }
+            DestructurePattern::Rest(s) => {
+                self.u8(2);
+                self.str(s);
+            }
+            DestructurePattern::Nested(key, items) => {
+                self.u8(3);
+                self.opt_str(key);
+                self.list(items.iter(), Self::destructure)?;
+            }
+            DestructurePattern::Default(p, e) => {
+                self.u8(4);
+                self.destructure(p)?;
+                self.expr(e)?;
+            }
+        }
+        Some(())
+    }
+}
+
+/// 解码器：数据损坏或版本不符时返回 None
+pub struct Decoder<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub fn is_end(&self) -> bool {
+        self.pos == self.buf.len()
+    }
+
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.buf.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    pub fn u64(&mut self) -> Option<u64> {
+        self.take(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap_or_default()))
+    }
+
+    pub fn bool(&mut self) -> Option<bool> {
+        self.u8().map(|b| b != 0)
+    }
+
+    fn len(&mut self) -> Option<usize> {
+        usize::try_from(self.u64()?).ok()
+    }
+
+    pub fn str(&mut self) -> Option<String> {
+        let n = self.len()?;
+        String::from_utf8(self.take(n)?.to_vec()).ok()
+    }
+
+    pub fn opt_str(&mut self) -> Option<Option<String>> {
+        self.opt(Self::str)
+    }
+
+    pub fn strs(&mut self) -> Option<Vec<String>> {
+        self.list(Self::str)
+    }
+
+    pub fn list<T>(&mut self, mut f: impl FnMut(&mut Self) -> Option<T>) -> Option<Vec<T>> {
+        let n = self.len()?;
+        // 长度前缀不可信，预分配不超过剩余字节数
+        let mut items = Vec::with_capacity(n.min(self.buf.len() - self.pos));
+        for _ in 0..n {
+            items.push(f(self)?);
+        }
+        Some(items)
+    }
+
+    fn opt<T>(&mut self, f: impl FnOnce(&mut Self) -> Option<T>) -> Option<Option<T>> {
+        match self.bool()? {
+            true => f(self).map(Some),
+            false => Some(None),
+        }
+    }
+
+    fn exprs(&mut self) -> Option<Vec<Expression>> {
+        self.list(Self::expr)
+    }
+
+    fn rc(&mut self) -> Option<Rc<Expression>> {
+        self.expr().map(Rc::new)
+    }
+
+    fn opt_rc(&mut self) -> Option<Option<Rc<Expression>>> {
+        self.opt(Self::rc)
+    }
+
+    fn pairs(&mut self) -> Option<Vec<(String, Expression)>> {
+        self.list(|d| Some((d.str()?, d.expr()?)))
+    }
+
+    pub fn expr(&mut self) -> Option<Expression> {
+        let expr = match self.u8()? {
+            0 => Expression::Group(self.rc()?),
+            1 => Expression::BinaryOp(self.str()?, self.rc()?, self.rc()?),
+            2 => Expression::UnaryOp(self.str()?, self.rc()?, self.bool()?),
+            3 => Expression::RangeOp(self.str()?, self.rc()?, self.rc()?, self.opt_rc()?),
+            4 => Expression::Pipe(self.str()?, self.rc()?, self.rc()?),
+            5 => Expression::Symbol(self.str()?),
+            6 => Expression::Variable(self.str()?),
+            7 => Expression::Integer(self.u64()? as i64),
+            8 => Expression::Float(f64::from_bits(self.u64()?)),
+            9 => {
+                let n = self.len()?;
+                Expression::Bytes(self.take(n)?.to_vec())
+            }
+            10 => Expression::String(self.str()?),
+            11 => Expression::StringTemplate(self.exprs()?),
+            12 => Expression::RegexDef(self.str()?),
+            13 => Expression::TimeDef(self.str()?),
+            14 => Expression::Regex(LumeRegex {
+                regex: Regex::new(&self.str()?).ok()?,
+            }),
+            15 => Expression::Boolean(self.bool()?),
+            16 => Expression::None,
+            17 => Expression::List(Rc::new(self.exprs()?)),
+            18 => Expression::BSet(Rc::new(self.exprs()?.into_iter().collect::<BTreeSet<_>>())),
+            19 => Expression::HMap(Rc::new(
+                self.pairs()?.into_iter().collect::<HashMap<_, _>>(),
+            )),
+            20 => Expression::Map(Rc::new(
+                self.pairs()?.into_iter().collect::<BTreeMap<_, _>>(),
+            )),
+            21 => Expression::Index(self.rc()?, self.rc()?),
+            22 => Expression::Property(self.rc()?, self.rc()?),
+            23 => Expression::Del(self.str()?),
+            24 => Expression::Declare(self.str()?, self.rc()?),
+            25 => Expression::Assign(self.str()?, self.rc()?),
+            26 => Expression::SetParent(self.str()?, self.rc()?),
+            27 => Expression::Export(self.str()?, self.opt_rc()?),
+            28 => Expression::AliasDef(self.str()?, self.rc()?),
+            29 => Expression::For(self.str()?, self.opt_str()?, self.rc()?, self.rc()?),
+            30 => Expression::While(self.rc()?, self.rc()?),
+            31 => Expression::Loop(self.rc()?),
+            32 => {
+                let value = self.rc()?;
+                let arms = self.list(|d| Some((d.list(Self::pattern)?, d.expr()?)))?;
+                Expression::Match(value, Rc::new(arms))
+            }
+            33 => Expression::If(self.rc()?, self.rc()?, self.rc()?),
+            34 => Expression::Apply(self.rc()?, Rc::new(self.exprs()?)),
+            35 => Expression::Command(self.rc()?, Rc::new(self.exprs()?)),
+            36 => Expression::CommandRaw(self.rc()?, Rc::new(self.exprs()?)),
+            37 => Expression::Lambda(
+                self.strs()?,
+                self.rc()?,
+                self.opt(|d| Some(d.pairs()?.into_iter().collect()))?,
+            ),
+            38 => Expression::Function(
+                self.str()?,
+                self.list(|d| Some((d.destructure()?, d.opt(Self::expr)?, d.opt_str()?)))?,
+                self.opt_str()?,
+                self.rc()?,
+                self.list(|d| Some((d.str()?, d.opt(Self::exprs)?)))?,
+                self.opt_str()?,
+            ),
+            39 => Expression::StructDef(Rc::new(RecordType {
+                name: self.str()?,
+                fields: self.list(|d| Some((d.str()?, d.opt(Self::expr)?, d.opt_str()?)))?,
+                methods: self.pairs()?.into_iter().collect(),
+            })),
+            40 => Expression::Return(self.rc()?),
+            41 => Expression::Break(self.rc()?),
+            42 => Expression::Continue,
+            43 => Expression::Sequence(self.exprs()?),
+            44 => Expression::Block(Rc::new(self.exprs()?)),
+            45 => Expression::Quote(self.rc()?),
+            46 => {
+                let body = self.rc()?;
+                let ty = match self.u8()? {
+                    0 => CatchType::Ignore,
+                    1 => CatchType::PrintStd,
+                    2 => CatchType::PrintErr,
+                    3 => CatchType::PrintOver,
+                    4 => CatchType::Terminate,
+                    5 => CatchType::Deel,
+                    6 => CatchType::ToBoolean,
+                    _ => return None,
+                };
+                Expression::Catch(body, ty, self.opt_rc()?)
+            }
+            47 => Expression::Try(self.rc()?, self.opt_str()?, self.opt_rc()?, self.opt_rc()?),
+            48 => Expression::Defer(self.rc()?),
+            49 => Expression::Range(
+                self.u64()? as i64..self.u64()? as i64,
+                usize::try_from(self.u64()?).ok()?,
+            ),
+            50 => {
+                let secs = self.u64()? as i64;
+                let nanos = u32::try_from(self.u64()?).ok()?;
+                Expression::DateTime(DateTime::from_timestamp(secs, nanos)?.naive_utc())
+            }
+            51 => {
+                let size = self.u64()?;
+                let unit = match self.u8()? {
+                    0 => SizeUnit::B,
+                    1 => SizeUnit::K,
+                    2 => SizeUnit::M,
+                    3 => SizeUnit::G,
+                    4 => SizeUnit::T,
+                    5 => SizeUnit::P,
+                    6 => SizeUnit::None,
+                    _ => return None,
+                };
+                Expression::FileSize(FileSize::new(size, unit))
+            }
+            52 => Expression::Chain(
+                self.rc()?,
+                self.list(|d| {
+                    Some(ChainCall {
+                        method: d.str()?,
+                        args: d.exprs()?,
+                    })
+                })?,
+            ),
+            53 => Expression::PipeMethod(self.str()?, Rc::new(self.exprs()?)),
+            54 => Expression::DestructureAssign(self.list(Self::destructure)?, self.rc()?),
+            55 => Expression::Use(self.opt_str()?, self.str()?, self.opt(Self::strs)?),
+            56 => Expression::ModuleCall(self.strs()?, self.rc()?),
+            57 => Expression::Blank,
+            58 => {
+                let name = self.str()?;
+                Expression::LocalVar(slot_of(&name), name, self.bool()?)
+            }
+            59 => Expression::parse_int(&self.str()?)?,
+            60 => {
+                let name = self.str()?;
+                Expression::LocalSet(slot_of(&name), name, self.bool()?, self.rc()?)
+            }
+            _ => return None,
+        };
+        Some(expr)
+    }
+
+    fn pattern(&mut self) -> Option<MatchPattern> {
+        let pat = match self.u8()? {
+            0 => MatchPattern::Value(self.expr()?),
+            1 => MatchPattern::Bind(self.str()?),
+            2 => MatchPattern::Type(self.opt_str()?, self.str()?),
+            3 => MatchPattern::List(
+                self.list(Self::pattern)?,
+                self.opt(Self::pattern)?.map(Box::new),
+            ),
+            4 => MatchPattern::Map(self.list(|d| Some((d.str()?, d.pattern()?)))?),
+            5 => MatchPattern::Guard(Box::new(self.pattern()?), self.rc()?),
+            _ => return None,
+        };
+        Some(pat)
+    }
+
+    fn destructure(&mut self) -> Option<DestructurePattern> {
+        let pat = match self.u8()? {
+            0 => DestructurePattern::Identifier(self.str()?),
+            1 => DestructurePattern::Renamed((self.str()?, self.str()?)),
+            2 => DestructurePattern::Rest(self.str()?),
+            3 => DestructurePattern::Nested(self.opt_str()?, self.list(Self::destructure)?),
+            4 => DestructurePattern::Default(Box::new(self.destructure()?), self.rc()?),
+            _ => return None,
+        };
+        Some(pat)
+    }
+}
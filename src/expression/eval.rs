@@ -1,11 +1,18 @@
+use crate::expression::bignum::int_pow;
+use crate::expression::cmd_excutor::{
+    PipeStream, as_stdin_redirect, is_external_stage, is_stream_source, process_subst, to_expr,
+};
 use crate::expression::eval2::ifs_split;
 use crate::expression::eval3::prepare_args;
 
-use crate::expression::{BoxedIterator, LumeRegex, alias};
-use crate::libs::{get_builtin_via_expr, handle_color, handle_math, handle_style, time_parse};
+use crate::expression::slot::{Frame, find_slot, slot_name, slot_of};
+use crate::expression::{BoxedIterator, LumeRegex, MatchPattern, alias};
+use crate::libs::{
+    get_builtin_via_expr, handle_color, handle_math, handle_style, run_traps, time_parse,
+};
 use crate::utils::abs;
 use crate::utils::canon;
-use crate::{Environment, Expression, Int, RuntimeError, STRICT_ENABLED};
+use crate::{Environment, Expression, Int, RuntimeError, STRICT_ENABLED, childman};
 use crate::{MAX_RUNTIME_RECURSION, RuntimeErrorKind};
 use core::option::Option::None;
 use regex_lite::Regex;
@@ -14,13 +21,22 @@ use std::io::Write;
 use std::ops::Range;
 use std::rc::Rc;
 
+/// 函数调用的局部作用域：进入时的帧位置与被参数遮蔽的局部变量
+pub struct CallScope {
+    mark: usize,
+    shadowed: Vec<(usize, Expression)>,
+    was_local: bool,
+}
+
 // #[derive(Debug, Clone)]
 pub struct State(
-    u8,
-    Option<Expression>,                                     //pipe-data
-    Vec<String>,                                            //domains
-    HashMap<String, Expression>,                            //local-var
-    Option<(String, Option<String>, usize, BoxedIterator)>, //loop-iter
+    u16,
+    Option<Expression>,                                   //pipe-data
+    Vec<String>,                                          //domains
+    Frame,                                                //local-var
+    Option<(usize, Option<usize>, usize, BoxedIterator)>, //loop-iter，变量均为槽位
+    Option<PipeStream>,                                   //pipe-stream
+    Option<Rc<Expression>>,                               //tail-call 所在函数体
 );
 
 impl Default for State {
@@ -30,35 +46,35 @@ impl Default for State {
 }
 
 impl State {
-    pub const STRICT: u8 = 1;
-    pub const IN_LOCAL: u8 = 1 << 1; // 0b00000010
-    // pub const SKIP_BUILTIN_SEEK: u8 = 1 << 1; // 0b00000010
-    pub const IN_PIPE: u8 = 1 << 2; // 0b00000100
-    pub const PTY_MODE: u8 = 1 << 3; // 0b00001000
-    pub const IN_ASSIGN: u8 = 1 << 4; // 0b00010000
-    pub const IN_DECO: u8 = 1 << 5;
+    pub const STRICT: u16 = 1;
+    pub const IN_LOCAL: u16 = 1 << 1; // 0b00000010
+    // pub const SKIP_BUILTIN_SEEK: u16 = 1 << 1; // 0b00000010
+    pub const IN_PIPE: u16 = 1 << 2; // 0b00000100
+    pub const PTY_MODE: u16 = 1 << 3; // 0b00001000
+    pub const IN_ASSIGN: u16 = 1 << 4; // 0b00010000
+    pub const IN_DECO: u16 = 1 << 5;
 
     // 创建一个新的 State 实例
     pub fn new() -> Self {
         let strict = if is_strict() { 1 } else { 0 };
-        State(strict, None, Vec::new(), HashMap::new(), None)
+        State(strict, None, Vec::new(), Frame::default(), None, None, None)
     }
 
     // 设置标志
     #[inline]
-    pub fn set(&mut self, flag: u8) {
+    pub fn set(&mut self, flag: u16) {
         self.0 |= flag;
     }
 
     // 清除标志
     #[inline]
-    pub fn clear(&mut self, flag: u8) {
+    pub fn clear(&mut self, flag: u16) {
         self.0 &= !flag;
     }
 
     // 检查标志是否被设置
     #[inline]
-    pub fn contains(&self, flag: u8) -> bool {
+    pub fn contains(&self, flag: u16) -> bool {
         self.0 & flag != 0
     }
 
@@ -69,6 +85,11 @@ impl State {
 
     #[inline]
     pub fn pipe_out(&mut self) -> Option<Expression> {
+        // 未被外部命令消费的流，在此读尽
+        if let Some(stream) = self.5.take() {
+            self.1 = None;
+            return Some(to_expr(Some(stream.collect())));
+        }
         let p = self.1.clone();
         self.1 = None;
         p
@@ -76,7 +97,43 @@ impl State {
 }
 
 impl State {
-    pub const IN_DOMAINS: u8 = 1 << 6;
+    pub const STREAM_OUT: u16 = 1 << 8; // 下游为外部命令，stdout 以流交出
+
+    pub fn set_stream(&mut self, stream: PipeStream) {
+        self.5 = Some(stream);
+    }
+    pub fn take_stream(&mut self) -> Option<PipeStream> {
+        self.5.take()
+    }
+}
+
+impl State {
+    /// 标记下一个求值的表达式处于该函数体的尾部位置
+    #[inline]
+    pub fn set_tail(&mut self, body: Option<Rc<Expression>>) {
+        self.6 = body;
+    }
+    /// 取出尾部位置标记，只对紧接着求值的那一层有效
+    #[inline]
+    pub fn take_tail(&mut self) -> Option<Rc<Expression>> {
+        self.6.take()
+    }
+}
+
+impl State {
+    /// 语句级命令上下文，`cmd < file` 只在此处改写为输入重定向；同样只对紧接着求值的那一层有效
+    pub const CMD_STMT: u16 = 1 << 9;
+
+    #[inline]
+    pub fn take_cmd_stmt(&mut self) -> bool {
+        let is = self.contains(Self::CMD_STMT);
+        self.clear(Self::CMD_STMT);
+        is
+    }
+}
+
+impl State {
+    pub const IN_DOMAINS: u16 = 1 << 6;
 
     pub fn extend_lookup_domains(&mut self, domains: &[String]) {
         self.2.extend_from_slice(domains);
@@ -98,7 +155,7 @@ impl State {
 }
 
 impl State {
-    pub const IN_FOR_LOOP: u8 = 1 << 7; // 新增循环状态标志
+    pub const IN_FOR_LOOP: u16 = 1 << 7; // 新增循环状态标志
 
     // pub fn get_loop_context(
     //     &mut self,
@@ -114,51 +171,91 @@ impl State {
     // }
 
     #[inline]
-    pub fn set_local_var(&mut self, name: String, value: Expression) {
-        self.3.insert(name, value);
+    pub fn set_local_var(&mut self, name: &str, value: Expression) {
+        self.3.set(slot_of(name), value);
     }
     #[inline]
     pub fn has_local_var(&mut self, name: &str) -> bool {
-        self.3.contains_key(name)
+        self.get_local_var(name).is_some()
     }
     #[inline]
     pub fn get_local_var(&self, name: &str) -> Option<&Expression> {
-        self.3.get(name)
+        find_slot(name).and_then(|slot| self.3.get(slot))
+    }
+    #[inline]
+    pub fn get_slot(&self, slot: usize) -> Option<&Expression> {
+        self.3.get(slot)
+    }
+    #[inline]
+    pub fn set_slot(&mut self, slot: usize, value: Expression) {
+        self.3.set(slot, value);
+    }
+    pub fn remove_local_var(&mut self, name: &str) {
+        if let Some(slot) = find_slot(name) {
+            self.3.remove(slot);
+        }
     }
     pub fn clear_local_var(&mut self) {
         self.3.clear();
     }
-    pub fn set_local_vars(&mut self, map: HashMap<String, Expression>) {
-        self.3 = map;
+    pub fn set_local_vars(&mut self, frame: Frame) {
+        self.3 = frame;
     }
-    pub fn get_local_vars(&self) -> HashMap<String, Expression> {
+    pub fn get_local_vars(&self) -> Frame {
         self.3.clone()
     }
+    /// 记录当前局部变量，配合 drop_local_vars 丢弃其后新增的变量
+    #[inline]
+    pub fn local_mark(&self) -> usize {
+        self.3.mark()
+    }
+    #[inline]
+    pub fn drop_local_vars(&mut self, mark: usize) {
+        self.3.truncate(mark);
+    }
+    /// 进入函数调用：保存将被参数遮蔽的局部变量，其后绑定的参数写入局部帧
+    pub fn enter_call(&mut self, slots: &[usize]) -> CallScope {
+        let shadowed = slots
+            .iter()
+            .filter_map(|&slot| self.3.get(slot).map(|v| (slot, v.clone())))
+            .collect();
+        let scope = CallScope {
+            mark: self.3.mark(),
+            shadowed,
+            was_local: self.contains(Self::IN_LOCAL),
+        };
+        self.set(Self::IN_LOCAL);
+        scope
+    }
+    /// 离开函数调用：丢弃参数，恢复被遮蔽的局部变量
+    pub fn leave_call(&mut self, scope: CallScope) {
+        self.3.truncate(scope.mark);
+        for (slot, value) in scope.shadowed {
+            self.3.set(slot, value);
+        }
+        if !scope.was_local {
+            self.clear(Self::IN_LOCAL);
+        }
+    }
 
     // 添加循环状态字段
-    pub fn set_iter(
-        &mut self,
-        var_name: String,
-        index_name: Option<String>,
-        iterator: BoxedIterator,
-    ) {
-        self.4 = Some((var_name, index_name, 0, iterator));
+    pub fn set_iter(&mut self, var_name: &str, index_name: Option<&str>, iterator: BoxedIterator) {
+        self.4 = Some((slot_of(var_name), index_name.map(slot_of), 0, iterator));
     }
     #[inline]
     pub fn pop_iter(&mut self) -> Result<bool, RuntimeErrorKind> {
         match self.4.as_mut() {
             Some((v, ind, idx, iter)) => {
                 if let Some(value) = iter.next() {
-                    self.3.insert(v.to_string(), value);
+                    self.3.set(*v, value);
                     // if has index var
                     if let Some(index) = ind {
-                        self.3
-                            .insert(index.to_string(), Expression::Integer(*idx as Int));
+                        self.3.set(*index, Expression::Integer(*idx as Int));
                         *idx += 1;
                     };
                     Ok(true)
                 } else {
-                    Err(RuntimeErrorKind::IteratorExhausted(v.clone()))
+                    Err(RuntimeErrorKind::IteratorExhausted(slot_name(*v)))
                 }
             }
             None => Ok(false),
@@ -167,14 +264,12 @@ impl State {
     pub fn clear_iter(&mut self) {
         self.4 = None;
     }
-    pub fn take_iter(&mut self) -> Option<(String, Option<String>, usize, BoxedIterator)> {
+    pub fn take_iter(&mut self) -> Option<(usize, Option<usize>, usize, BoxedIterator)> {
         self.4.take()
     }
-    pub fn retain_local_vars<F>(&mut self, f: F)
-    where
-        F: FnMut(&String, &mut Expression) -> bool,
-    {
-        self.3.retain(f);
+    /// 恢复外层循环，保留其计数
+    pub fn restore_iter(&mut self, iter: Option<(usize, Option<usize>, usize, BoxedIterator)>) {
+        self.4 = iter;
     }
 }
 
@@ -185,7 +280,9 @@ impl Expression {
     /// 交互命令入口
     #[inline]
     pub fn eval_cmd(&self, env: &mut Environment) -> Result<Self, RuntimeError> {
-        self.eval_mut(&mut State::new(), env, 0)
+        let mut state = State::new();
+        state.set(State::CMD_STMT);
+        self.eval_mut(&mut state, env, 0)
         // let result =self.eval_mut(&mut State::new(), env, 0);
         // // dbg!(&result);
         // match result {
@@ -203,7 +300,9 @@ impl Expression {
     /// 脚本计算入口
     #[inline]
     pub fn eval(&self, env: &mut Environment) -> Result<Self, RuntimeError> {
-        self.eval_mut(&mut State::new(), env, 0)
+        let mut state = State::new();
+        state.set(State::CMD_STMT);
+        self.eval_mut(&mut state, env, 0)
     }
     /// builtin args eval in pipe.
     /// this call will capture the output of subcmd.
@@ -230,6 +329,9 @@ impl Expression {
         depth: usize,
     ) -> Result<Self, RuntimeError> {
         // dbg!("1.--->eval_mut:", &self, &self.type_name(), &state);
+        // 尾部位置随 if/match 分支、块末尾语句传递，子表达式不再处于尾部
+        let tail = state.take_tail();
+        let is_cmd_stmt = state.take_cmd_stmt();
 
         if MAX_RUNTIME_RECURSION.with(|v| depth > *v.borrow()) {
             return Err(RuntimeError::new(
@@ -238,6 +340,17 @@ impl Expression {
                 depth,
             ));
         }
+        // 安全点：执行已收到信号的陷阱，超时后中断求值
+        if childman::has_pending_signals() {
+            run_traps(env)?;
+        }
+        if let Some(limit) = childman::expired_watchdog() {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::Timeout(limit),
+                self.clone(),
+                depth,
+            ));
+        }
         let mut job = self;
         loop {
             // println!();
@@ -251,6 +364,8 @@ impl Expression {
                 Self::Float(f) => return Ok(Self::Float(*f)),
                 Self::Range(r, s) => return Ok(Self::Range(r.start..r.end, *s)),
                 Self::Regex(_)
+                | Self::Stream(_)
+                | Self::Record(..)
                 | Self::DateTime(_)
                 | Self::FileSize(_)
                 | Self::Bytes(_)
@@ -269,11 +384,43 @@ impl Expression {
                     let mut result = String::new();
                     for seg in segments.iter() {
                         let val = seg.eval_mut(state, env, depth + 1)?;
-                        result.push_str(&val.to_string());
+                        result.push_str(&val.display_string(state, env, depth)?);
                     }
                     return Ok(Expression::String(result));
                 }
                 Self::Variable(name) => return job.handle_variable(name, false, state, env, depth),
+                // 槽位变量：局部帧按下标读取
+                Self::LocalVar(slot, name, sym) => {
+                    if *sym && state.contains(State::STRICT) {
+                        return Ok(Self::Symbol(name.clone()));
+                    }
+                    if state.contains(State::IN_LOCAL)
+                        && let Some(local_val) = state.get_slot(*slot)
+                    {
+                        return Ok(local_val.clone());
+                    }
+                    return job.handle_env_variable(name, *sym, env, depth);
+                }
+                // 槽位 let/赋值：局部作用域内按下标写入，否则同 Declare/Assign
+                Self::LocalSet(slot, name, declare, expr) => {
+                    if !state.contains(State::IN_LOCAL) {
+                        let expr = match declare {
+                            true => Self::Declare(name.clone(), expr.clone()),
+                            false => Self::Assign(name.clone(), expr.clone()),
+                        };
+                        return expr.eval_mut(state, env, depth);
+                    }
+                    state.set(State::IN_ASSIGN | State::CMD_STMT);
+                    let value = expr.as_ref().eval_mut(state, env, depth + 1)?;
+                    state.clear(State::IN_ASSIGN);
+                    // 非局部变量的赋值按名称处理：外层变量或严格模式报错
+                    if !declare && state.get_slot(*slot).is_none() {
+                        handle_assign(name, value, job, state, env, depth)?;
+                        return Ok(Self::None);
+                    }
+                    state.set_slot(*slot, value);
+                    return Ok(Self::None);
+                }
                 // {
                 //     // dbg!("2.--->variable----", &name);
                 //     // var
@@ -292,10 +439,10 @@ impl Expression {
                     // dbg!("declare---->", &name, &expr.type_name());
                     // 块级作用域
                     if state.contains(State::IN_LOCAL) {
-                        state.set(State::IN_ASSIGN);
+                        state.set(State::IN_ASSIGN | State::CMD_STMT);
                         let value = expr.as_ref().eval_mut(state, env, depth + 1)?;
                         state.clear(State::IN_ASSIGN);
-                        state.set_local_var(name.to_string(), value);
+                        state.set_local_var(name, value);
                         return Ok(Self::None);
                     }
                     if state.contains(State::STRICT) && env.has(name) {
@@ -317,7 +464,7 @@ impl Expression {
                     //     }
                     //     env.define(name, value); // 新增 declare
                     // } else {
-                    state.set(State::IN_ASSIGN);
+                    state.set(State::IN_ASSIGN | State::CMD_STMT);
                     let value = expr.as_ref().eval_mut(state, env, depth + 1)?;
                     state.clear(State::IN_ASSIGN);
                     env.define(name, value);
@@ -327,21 +474,21 @@ impl Expression {
                 Self::SetParent(name, expr) => {
                     // 新增：先检查 local_vars 中是否有该变量
                     if state.contains(State::IN_LOCAL) && state.has_local_var(name) {
-                        state.set(State::IN_ASSIGN);
+                        state.set(State::IN_ASSIGN | State::CMD_STMT);
                         let value = expr.as_ref().eval_mut(state, env, depth + 1)?;
                         state.clear(State::IN_ASSIGN);
-                        state.set_local_var(name.to_string(), value);
+                        state.set_local_var(name, value);
                         return Ok(Self::None);
                     }
                     // 检查env
                     if env.has(name) {
-                        state.set(State::IN_ASSIGN);
+                        state.set(State::IN_ASSIGN | State::CMD_STMT);
                         let value = expr.as_ref().eval_mut(state, env, depth + 1)?;
                         state.clear(State::IN_ASSIGN);
                         env.define(name, value);
                     } else if env.is_defined(name) {
                         // 向上层环境查找并修改
-                        state.set(State::IN_ASSIGN);
+                        state.set(State::IN_ASSIGN | State::CMD_STMT);
                         let value = expr.as_ref().eval_mut(state, env, depth + 1)?;
                         state.clear(State::IN_ASSIGN);
 
@@ -361,7 +508,7 @@ impl Expression {
                                 depth,
                             ));
                         }
-                        state.set(State::IN_ASSIGN);
+                        state.set(State::IN_ASSIGN | State::CMD_STMT);
                         let value = expr.as_ref().eval_mut(state, env, depth + 1)?;
                         state.clear(State::IN_ASSIGN);
                         env.define(name, value);
@@ -372,7 +519,7 @@ impl Expression {
 
                 // Assign 优先修改子环境，未找到则修改父环境
                 Self::Assign(name, expr) => {
-                    state.set(State::IN_ASSIGN);
+                    state.set(State::IN_ASSIGN | State::CMD_STMT);
                     let value = expr.as_ref().eval_mut(state, env, depth + 1)?;
                     state.clear(State::IN_ASSIGN);
 
@@ -381,16 +528,18 @@ impl Expression {
 
                 Expression::DestructureAssign(pattern, value) => {
                     let evaluated_value = value.eval_mut(state, env, depth + 1)?;
-                    return if state.contains(State::IN_LOCAL) {
-                        self.destructure_assign_local(pattern, evaluated_value, state, depth + 1)
-                    } else {
-                        self.destructure_assign(pattern, evaluated_value, env, depth + 1)
-                    };
+                    return self.destructure_assign(
+                        pattern,
+                        evaluated_value,
+                        state,
+                        env,
+                        depth + 1,
+                    );
                 }
 
                 Self::Export(name, expr_opt) => {
                     let value = if let Some(expr) = expr_opt {
-                        state.set(State::IN_ASSIGN);
+                        state.set(State::IN_ASSIGN | State::CMD_STMT);
                         let value = expr.as_ref().eval_mut(state, env, depth + 1)?;
                         state.clear(State::IN_ASSIGN);
                         value
@@ -445,12 +594,16 @@ impl Expression {
 
                 // 一元运算
                 Self::UnaryOp(op, operand, _) => {
+                    if matches!(op.as_str(), "<" | ">") {
+                        return process_subst(job, op, operand, state, env, depth + 1);
+                    }
                     let operand_eval = operand.eval_mut(state, env, depth)?;
                     return match op.as_str() {
                         "!" => Ok(Expression::Boolean(!operand_eval.is_truthy())),
                         "-" => match operand_eval {
-                            Expression::Integer(i) => Ok(Expression::Integer(-i)),
-                            Expression::Float(i) => Ok(Expression::Float(-i)),
+                            v @ (Expression::Integer(_)
+                            | Expression::BigInt(_)
+                            | Expression::Float(_)) => Ok(-v),
                             _ => {
                                 return Err(RuntimeError::common(
                                     format!("Cannot apply Neg to {operand:?}:{operand_eval:?}")
@@ -516,6 +669,13 @@ impl Expression {
 
                 // 二元运算
                 Self::BinaryOp(operator, lhs, rhs) => {
+                    // `cmd < file`：语句级外部命令的输入重定向，表达式与参数中仍是比较
+                    if operator == "<"
+                        && is_cmd_stmt
+                        && let Some(cmd) = as_stdin_redirect(lhs, rhs, env)
+                    {
+                        return cmd.eval_mut(state, env, depth + 1);
+                    }
                     return match operator.as_str() {
                         "+=" => match lhs.as_ref() {
                             Expression::Symbol(base) => {
@@ -618,6 +778,12 @@ impl Expression {
                             // fmt.red : left is builtin, right never.
                             let l = lhs.as_ref().eval_mut(state, env, depth + 1)?;
                             let r = rhs.as_ref().eval_mut(state, env, depth + 1)?;
+                            // 结构体重载的运算符优先
+                            if let Some(result) =
+                                self.overload_binary(operator, &l, &r, state, env, depth)
+                            {
+                                return result;
+                            }
                             return match operator.as_str() {
                                 "+" => {
                                     (l + r).map_err(|e| RuntimeError::new(e, self.clone(), depth))
@@ -631,18 +797,14 @@ impl Expression {
                                 "/" => {
                                     (l / r).map_err(|e| RuntimeError::new(e, self.clone(), depth))
                                 } //no zero
-                                "%" => Ok(l % r),
+                                "%" => {
+                                    (l % r).map_err(|e| RuntimeError::new(e, self.clone(), depth))
+                                }
                                 "^" => match (l, r) {
-                                    (Expression::Float(base), Expression::Float(exponent)) => {
-                                        Ok(base.powf(exponent).into())
-                                    }
-                                    (Expression::Float(base), Expression::Integer(exponent)) => {
-                                        Ok(base.powf(exponent as f64).into())
-                                    }
-                                    (Expression::Integer(base), Expression::Float(exponent)) => {
-                                        Ok((base as f64).powf(exponent).into())
-                                    }
-                                    (Expression::Integer(base), Expression::Integer(exponent)) => {
+                                    (
+                                        base @ (Expression::Integer(_) | Expression::BigInt(_)),
+                                        Expression::Integer(exponent),
+                                    ) => {
                                         // 确保 exponent 是非负的
                                         if exponent < 0 {
                                             return Err(RuntimeError::common(
@@ -655,18 +817,23 @@ impl Expression {
                                             ));
                                         }
 
-                                        // 使用 checked_pow 进行幂运算
-                                        match base.checked_pow(exponent as u32) {
-                                            Some(n) => Ok(n.into()),
-                                            None => Err(RuntimeError::common(
-                                                format!(
-                                                    "overflow when raising int {base} to the power {exponent}"
+                                        // 溢出时提升为大整数，仅结果过大时报错
+                                        u32::try_from(exponent)
+                                            .ok()
+                                            .and_then(|e| int_pow(&base, e))
+                                            .ok_or_else(|| {
+                                                RuntimeError::common(
+                                                    format!(
+                                                        "overflow when raising int {base} to the power {exponent}"
+                                                    )
+                                                    .into(),
+                                                    self.clone(),
+                                                    depth,
                                                 )
-                                                .into(),
-                                                self.clone(),
-                                                depth,
-                                            )),
-                                        }
+                                            })
+                                    }
+                                    (a, b) if let (Some(x), Some(y)) = (a.as_f64(), b.as_f64()) => {
+                                        Ok(x.powf(y).into())
                                     }
                                     (a, b) => Err(RuntimeError::common(
                                         format!(
@@ -869,13 +1036,31 @@ impl Expression {
                         "|" | "|>" | "|^" => {
                             let is_in_pipe = state.contains(State::IN_PIPE);
                             state.set(State::IN_PIPE);
+                            // 外部命令之间以 OS 管道相连，各段同时运行
+                            let is_stream_out = state.contains(State::STREAM_OUT);
+                            if operator == "|" && is_stream_source(lhs) && is_external_stage(rhs) {
+                                state.set(State::STREAM_OUT);
+                            } else {
+                                state.clear(State::STREAM_OUT);
+                            }
+                            if is_cmd_stmt {
+                                state.set(State::CMD_STMT);
+                            }
                             // 逻辑：先做变量求值 → 若结果为 Symbol（变量未定义或 strict mode 回退）→ 转为 Command 重新执行。
-                            let left_output = match lhs.eval_mut(state, env, depth + 1) {
+                            let left_result = match lhs.eval_mut(state, env, depth + 1) {
                                 Ok(Expression::Symbol(name)) => Expression::Command(
                                     Rc::new(Expression::Symbol(name)),
                                     Rc::new(vec![]),
                                 )
-                                .eval_mut(state, env, depth + 1)?,
+                                .eval_mut(state, env, depth + 1),
+                                other => other,
+                            };
+                            if is_stream_out {
+                                state.set(State::STREAM_OUT);
+                            } else {
+                                state.clear(State::STREAM_OUT);
+                            }
+                            let left_output = match left_result {
                                 Ok(val) => val,
                                 Err(e) => {
                                     return match e.kind {
@@ -1259,6 +1444,17 @@ impl Expression {
 
                 // 执行应用
                 Self::Apply(func, args) => {
+                    // 尾部的自调用交由 eval_normal_function 循环执行
+                    if let Some(body) = &tail
+                        && let Some(args) =
+                            self.tail_call_args(func, body, args, state, env, depth + 1)?
+                    {
+                        return Err(RuntimeError::new(
+                            RuntimeErrorKind::TailCall(args),
+                            Expression::None,
+                            depth,
+                        ));
+                    }
                     break self.eval_apply(func.as_ref(), args, state, env, depth + 1);
                 }
                 Self::Command(cmd, args) => {
@@ -1280,37 +1476,35 @@ impl Expression {
                 Self::Match(dest, branches) => {
                     // 模式匹配求值
                     let value = dest.as_ref().eval_mut(state, env, depth + 1)?;
-                    let mut matched = false;
-                    for (pattern, expr) in branches.iter() {
-                        if pattern.iter().any(|pat| match pat {
-                            Expression::Blank => true,
-                            Expression::Symbol(s) | Expression::String(s) => {
-                                s == &value.to_string()
-                            }
-                            Expression::RangeOp(..) => {
-                                pat.eval_mut(state, env, depth).is_ok_and(|r| {
-                                    handle_contains(r, value.clone(), job, depth).is_ok_and(|x| x)
-                                })
-                            }
-                            Expression::RegexDef(s) => {
-                                Regex::new(s).is_ok_and(|r| r.is_match(value.to_string().as_str()))
+                    let mut matched = None;
+                    'arms: for (pattern, expr) in branches.iter() {
+                        for pat in pattern.iter() {
+                            let mut binds = vec![];
+                            if job.match_pattern(pat, &value, &mut binds, state, env, depth)? {
+                                matched = Some((expr, binds));
+                                break 'arms;
                             }
-
-                            o => o == &value,
-                        }) {
-                            job = expr;
-                            matched = true;
-                            break;
                         }
                     }
-                    if matched {
-                        continue;
+                    match matched {
+                        Some((expr, binds)) if binds.is_empty() => {
+                            job = expr;
+                            continue;
+                        }
+                        Some((expr, binds)) => {
+                            return with_binds(binds, state, |state| {
+                                state.set_tail(tail);
+                                expr.eval_mut(state, env, depth + 1)
+                            });
+                        }
+                        None => {
+                            return Err(RuntimeError::new(
+                                RuntimeErrorKind::NoMatchingBranch(dest.to_string()),
+                                self.clone(),
+                                depth,
+                            ));
+                        }
                     }
-                    return Err(RuntimeError::new(
-                        RuntimeErrorKind::NoMatchingBranch(dest.to_string()),
-                        self.clone(),
-                        depth,
-                    ));
                 }
                 Expression::Chain(base, calls) => {
                     return self.eval_chain(base, calls, state, env, depth + 1);
@@ -1326,7 +1520,10 @@ impl Expression {
                 }
                 Expression::Blank => return Ok(state.pipe_out().unwrap_or(job.clone())),
                 // 其他表达式处理...
-                _ => break job.eval_flows(state, env, depth + 1),
+                _ => {
+                    state.set_tail(tail);
+                    break job.eval_flows(state, env, depth + 1);
+                }
             };
             // depth += 1
         }
@@ -1334,6 +1531,83 @@ impl Expression {
 }
 
 impl Expression {
+    /// 匹配 match 分支的单个模式，结构模式中绑定的变量写入 binds
+    fn match_pattern(
+        &self,
+        pat: &MatchPattern,
+        value: &Expression,
+        binds: &mut Vec<(String, Expression)>,
+        state: &mut State,
+        env: &mut Environment,
+        depth: usize,
+    ) -> Result<bool, RuntimeError> {
+        Ok(match pat {
+            MatchPattern::Value(p) => match p {
+                Expression::Blank => true,
+                Expression::Symbol(s) | Expression::String(s) => s == &value.to_string(),
+                Expression::RangeOp(..) => p
+                    .eval_mut(state, env, depth)
+                    .is_ok_and(|r| handle_contains(r, value.clone(), self, depth).is_ok_and(|x| x)),
+                Expression::RegexDef(s) => {
+                    Regex::new(s).is_ok_and(|r| r.is_match(value.to_string().as_str()))
+                }
+                o => o == value,
+            },
+            MatchPattern::Bind(name) => {
+                binds.push((name.clone(), value.clone()));
+                true
+            }
+            MatchPattern::Type(bind, ty) => {
+                let is = value.is_type(ty).unwrap_or(false);
+                if is && let Some(name) = bind {
+                    binds.push((name.clone(), value.clone()));
+                }
+                is
+            }
+            MatchPattern::List(items, rest) => {
+                let Expression::List(list) = value else {
+                    return Ok(false);
+                };
+                if list.len() < items.len() || (rest.is_none() && list.len() != items.len()) {
+                    return Ok(false);
+                }
+                for (p, v) in items.iter().zip(list.iter()) {
+                    if !self.match_pattern(p, v, binds, state, env, depth)? {
+                        return Ok(false);
+                    }
+                }
+                match rest {
+                    Some(p) => {
+                        let tail = Expression::from(list[items.len()..].to_vec());
+                        self.match_pattern(p, &tail, binds, state, env, depth)?
+                    }
+                    None => true,
+                }
+            }
+            MatchPattern::Map(entries) => {
+                for (key, p) in entries {
+                    let v = match value {
+                        Expression::Map(m) => m.get(key),
+                        Expression::HMap(m) => m.get(key),
+                        _ => return Ok(false),
+                    };
+                    match v {
+                        Some(v) if self.match_pattern(p, v, binds, state, env, depth)? => {}
+                        _ => return Ok(false),
+                    }
+                }
+                true
+            }
+            MatchPattern::Guard(p, cond) => {
+                self.match_pattern(p, value, binds, state, env, depth)?
+                    && with_binds(binds.clone(), state, |state| {
+                        cond.eval_mut(state, env, depth + 1)
+                    })?
+                    .is_truthy()
+            }
+        })
+    }
+
     /// Property map.key, only for map property, key is not expanded.
     /// fallback to string for filename case.
     pub fn handle_property(
@@ -1373,6 +1647,23 @@ impl Expression {
                     depth,
                 ))
             }
+            (Expression::Record(def, m), Expression::Symbol(n) | Expression::String(n)) => {
+                match m.get(n).or_else(|| def.methods.get(n)) {
+                    Some(v) => Ok(v.clone()),
+                    None => Err(RuntimeError::new(
+                        RuntimeErrorKind::KeyNotFound(format!("{}.{n}", def.name)),
+                        self.clone(),
+                        depth,
+                    )),
+                }
+            }
+            (Expression::StructDef(def), Expression::Symbol(n) | Expression::String(n)) => {
+                def.methods.get(n).cloned().ok_or(RuntimeError::new(
+                    RuntimeErrorKind::KeyNotFound(format!("{}.{n}", def.name)),
+                    self.clone(),
+                    depth,
+                ))
+            }
             (m, n) => Err(RuntimeError::common(
                 format!(
                     "not valid property request: {}.{}",
@@ -1599,8 +1890,17 @@ impl Expression {
         {
             return Ok(local_val.clone());
         }
+        self.handle_env_variable(name, allow_sym, env, depth)
+    }
 
-        // 从环境读取
+    /// 局部变量未命中时从环境读取
+    fn handle_env_variable(
+        &self,
+        name: &str,
+        allow_sym: bool,
+        env: &Environment,
+        depth: usize,
+    ) -> Result<Expression, RuntimeError> {
         match env.get(name) {
             Some(expr) => Ok(expr),
             None => match allow_sym {
@@ -1738,6 +2038,11 @@ fn handle_assign(
     depth: usize,
 ) -> Result<Expression, RuntimeError> {
     if state.contains(State::IN_LOCAL) {
+        // 块内给外层已定义的变量赋值：修改外层变量
+        if !state.has_local_var(name) && env.is_defined(name) {
+            assign_defined(name, value, env);
+            return Ok(Expression::None);
+        }
         // if not exists, see if strict
         if state.contains(State::STRICT) && !state.has_local_var(name) {
             return Err(RuntimeError::new(
@@ -1747,7 +2052,7 @@ fn handle_assign(
             ));
         }
         // changes only exists
-        state.set_local_var(name.to_string(), value);
+        state.set_local_var(name, value);
         return Ok(Expression::None);
     } else if env.has(name) {
         env.define(name, value.clone());
@@ -1767,3 +2072,47 @@ fn handle_assign(
     // }
     Ok(value)
 }
+
+/// 修改当前或最近的父环境中已定义的变量
+fn assign_defined(name: &str, value: Expression, env: &mut Environment) {
+    if env.has(name) {
+        env.define(name, value);
+        return;
+    }
+    let mut current_env = env;
+    while let Some(parent) = current_env.get_parent_mut() {
+        if parent.has(name) {
+            parent.define(name, value);
+            break;
+        }
+        current_env = parent;
+    }
+}
+
+/// 以局部变量绑定 match 分支、catch 块中的变量，离开时恢复同名的原有局部变量
+pub fn with_binds<R>(
+    binds: Vec<(String, Expression)>,
+    state: &mut State,
+    f: impl FnOnce(&mut State) -> R,
+) -> R {
+    let was_local = state.contains(State::IN_LOCAL);
+    let saved: Vec<_> = binds
+        .iter()
+        .map(|(k, _)| (k.clone(), state.get_local_var(k).cloned()))
+        .collect();
+    state.set(State::IN_LOCAL);
+    for (k, v) in binds {
+        state.set_local_var(&k, v);
+    }
+    let r = f(state);
+    for (k, v) in saved.into_iter().rev() {
+        match v {
+            Some(v) => state.set_local_var(&k, v),
+            None => state.remove_local_var(&k),
+        }
+    }
+    if !was_local {
+        state.clear(State::IN_LOCAL);
+    }
+    r
+}
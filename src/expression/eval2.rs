@@ -1,9 +1,9 @@
-use super::catcher::catch_error;
-use super::eval::State;
+use super::catcher::{catch_error, error_map};
+use super::eval::{State, with_binds};
 use crate::{
-    Environment, Expression, RuntimeError, RuntimeErrorKind,
+    Environment, Expression, Int, RuntimeError, RuntimeErrorKind,
     expression::{BoxedIterator, CatchType, DestructurePattern},
-    modman::use_module,
+    modman::{bind_imports, use_module},
     runtime::{IFS_FOR, ifs_contains},
     utils::expand_home,
 };
@@ -23,6 +23,8 @@ impl Expression {
         env: &mut Environment,
         depth: usize,
     ) -> Result<Self, RuntimeError> {
+        // 只有块、序列的末尾语句与 return 的值继续处于尾部位置
+        let tail = state.take_tail();
         match self {
             Self::For(var, index_name, list_expr, body) => self.handle_for(
                 var.clone(),
@@ -95,10 +97,14 @@ impl Expression {
             Self::Lambda(params, body, _) => {
                 let free_vars = body.get_free_variables();
 
-                // 只捕获自由变量
+                // 只捕获自由变量，局部变量优先
                 let mut captured_env = HashMap::new();
                 for var in &free_vars {
-                    if let Some(value) = env.get(var) {
+                    let local = match state.contains(State::IN_LOCAL) {
+                        true => state.get_local_var(var).cloned(),
+                        false => None,
+                    };
+                    if let Some(value) = local.or_else(|| env.get(var)) {
                         captured_env.insert(var.to_string(), value);
                     }
                 }
@@ -109,33 +115,8 @@ impl Expression {
                 ))
             }
             // 处理函数定义
-            Self::Function(name, params, pc, body, decos) => {
+            Self::Function(name, params, pc, body, decos, ret) => {
                 // dbg!(&def_env);
-                // 验证默认值类型（新增）
-                for (p, default) in params {
-                    if let Some(expr) = default {
-                        match expr {
-                            Expression::String(_)
-                            | Expression::Boolean(_)
-                            | Expression::Integer(_)
-                            | Expression::Float(_)
-                            | Expression::RegexDef(_)
-                            | Expression::TimeDef(_)
-                            | Expression::DateTime(_) => {}
-                            _ => {
-                                return Err(RuntimeError::new(
-                                    RuntimeErrorKind::InvalidDefaultValue(
-                                        name.clone(),
-                                        p.to_string(),
-                                        expr.clone(),
-                                    ),
-                                    self.clone(),
-                                    depth,
-                                ));
-                            }
-                        }
-                    }
-                }
                 // let new_env = def_env.fork();
                 // // new_env.define(&param, Expression::None);
                 // // new_env.set_cwd(env.get_cwd());
@@ -153,6 +134,7 @@ impl Expression {
                     pc.clone(),
                     body.clone(),
                     decos.clone(),
+                    ret.clone(),
                 );
                 if state.contains(State::STRICT) && env.has(name) {
                     return Err(RuntimeError::new(
@@ -169,16 +151,24 @@ impl Expression {
                 //     Ok(Expression::None)
                 // }
             }
+            // 结构体定义：名字绑定到类型本身，用作构造器
+            Self::StructDef(def) => {
+                if state.contains(State::STRICT) && env.has(&def.name) {
+                    return Err(RuntimeError::new(
+                        RuntimeErrorKind::Redeclaration(def.name.clone()),
+                        self.clone(),
+                        depth,
+                    ));
+                }
+                env.define(&def.name, self.clone());
+                Ok(self.clone())
+            }
 
             Self::Sequence(exprs) => {
                 if exprs.is_empty() {
                     return Ok(Expression::None);
                 }
-                let mut last = Expression::None;
-                for expr in exprs {
-                    last = expr.eval_mut(state, env, depth + 1)?;
-                }
-                Ok(last)
+                eval_statements(exprs, state, env, depth, tail)
             }
 
             // 块表达式
@@ -188,33 +178,25 @@ impl Expression {
                     return Ok(Expression::None);
                 }
 
-                let mut last = Expression::None;
                 let is_last_local = state.contains(State::IN_LOCAL);
-                let last_local_vars = if is_last_local {
-                    Some(state.get_local_vars())
-                } else {
-                    None
-                };
+                let mark = state.local_mark();
                 state.set(State::IN_LOCAL);
-                for expr in exprs.as_ref() {
-                    last = expr.eval_mut(state, env, depth + 1)?;
-                }
+                let last = eval_statements(exprs, state, env, depth, tail);
 
-                // 退出时清理Block内新增的局部变量，保留原有局部变量
+                // 退出时（含 return、尾调用等提前退出）清理Block内新增的局部变量，保留原有局部变量
                 if is_last_local {
-                    // 原来是：state.set_local_vars(last_local_vars.unwrap()); // 完整恢复，丢弃所有修改
-                    // 改为：只删除 Block 内新增的 key，保留已有 key 的修改
-                    let existing_keys = last_local_vars.unwrap();
-                    state.retain_local_vars(|k, _| existing_keys.contains_key(k));
+                    // 只删除 Block 内新增的变量，保留已有变量的修改
+                    state.drop_local_vars(mark);
                 } else {
                     state.clear_local_var();
                     state.clear(State::IN_LOCAL);
                 }
-                Ok(last)
+                last
             }
 
             Self::Return(expr) => {
                 // 提前返回机制
+                state.set_tail(tail);
                 let v = expr.as_ref().eval_mut(state, env, depth + 1)?;
                 // Ok(Self::Return(Rc::new(v)))
                 Err(RuntimeError::new(
@@ -246,10 +228,36 @@ impl Expression {
                         CatchType::ToBoolean => Ok(Expression::Boolean(true)),
                         _ => Ok(result),
                     },
+                    Err(e) if is_interrupt(&e) => Err(e),
                     Err(e) => catch_error(e, typ, deeling, state, env, depth + 1),
                 }
             }
-            Expression::Use(alias, module_path) => {
+            Self::Try(body, var, handler, finally) => {
+                let result = match (body.as_ref().eval_mut(state, env, depth + 1), handler) {
+                    // return/break/continue 与中断不被 catch 捕获
+                    (Err(e), Some(handler)) if !e.is_control_flow() && !is_interrupt(&e) => {
+                        match var {
+                            Some(v) => {
+                                with_binds(vec![(v.clone(), error_map(&e))], state, |state| {
+                                    handler.as_ref().eval_mut(state, env, depth + 1)
+                                })
+                            }
+                            None => handler.as_ref().eval_mut(state, env, depth + 1),
+                        }
+                    }
+                    (result, _) => result,
+                };
+                match finally {
+                    Some(finally) => {
+                        finally.as_ref().eval_mut(state, env, depth + 1)?;
+                        result
+                    }
+                    None => result,
+                }
+            }
+            // 不在块中的 defer 立即执行
+            Self::Defer(expr) => expr.as_ref().eval_mut(state, env, depth + 1),
+            Expression::Use(alias, module_path, names) => {
                 // let module_info = load_module(module_path, env)?;
 
                 // // 使用别名或模块名作为键，存储为Map
@@ -257,7 +265,9 @@ impl Expression {
                 // let module_map = Expression::HMap(Rc::new(module_info.functions));
                 let (module_name, module_map) = use_module(alias, module_path, env)?;
                 // dbg!(&module_map);
-                env.define(&module_name, module_map);
+                for (name, value) in bind_imports(&module_name, module_map, names)? {
+                    env.define(&name, value);
+                }
                 Ok(Expression::None)
             }
             // Expression::Use(alias, module_path) => {
@@ -381,6 +391,24 @@ impl Expression {
                 let iterator = BoxedIterator::Vec(rows.into_iter());
                 execute_iteration(var, index_name, iterator, size, body, state, env, depth)
             }
+            Expression::Stream(stream) => {
+                // 流长度未知，逐个拉取直到耗尽
+                let iterator = BoxedIterator::Stream(stream.clone());
+                let r = execute_iteration(
+                    var,
+                    index_name,
+                    iterator,
+                    usize::MAX,
+                    body,
+                    state,
+                    env,
+                    depth,
+                )?;
+                match stream.take_error() {
+                    Some(e) => Err(e),
+                    None => Ok(r),
+                }
+            }
             _ => Err(RuntimeError::new(
                 RuntimeErrorKind::ForNonList(list_excuted),
                 self.clone(),
@@ -389,10 +417,12 @@ impl Expression {
         }
     }
 
+    /// 解构赋值；IN_LOCAL 时写入局部变量
     pub fn destructure_assign(
         &self,
-        patterns: &Vec<DestructurePattern>,
+        patterns: &[DestructurePattern],
         value: Expression,
+        state: &mut State,
         env: &mut Environment,
         depth: usize,
     ) -> Result<Expression, RuntimeError> {
@@ -400,99 +430,42 @@ impl Expression {
             // 数组解构
             Expression::List(values) => {
                 for (i, pattern) in patterns.iter().enumerate() {
-                    match pattern {
-                        DestructurePattern::Identifier(name) => {
-                            env.define(
-                                name.as_str(),
-                                values.get(i).map_or(Expression::None, |v| v.clone()),
-                            );
-                        }
-                        DestructurePattern::Rest(name) => {
-                            let rest_values: Vec<Expression> =
-                                values.iter().skip(i).cloned().collect();
-                            env.define(name.as_str(), Expression::List(Rc::new(rest_values)));
-                            break;
-                        } // ... 其他模式
-                        _ => {
-                            return Err(RuntimeError::common(
-                                "never use map_destructure on List".into(),
-                                self.clone(),
-                                depth,
-                            ));
-                        }
+                    if let DestructurePattern::Rest(name) = pattern {
+                        let rest_values: Vec<Expression> = values.iter().skip(i).cloned().collect();
+                        bind_var(name, Expression::from(rest_values), state, env);
+                        break;
                     }
+                    let missing = || RuntimeErrorKind::IndexOutOfBounds {
+                        index: i as Int,
+                        len: values.len(),
+                    };
+                    let found = values.get(i).cloned();
+                    self.destructure_item(pattern, found, missing, state, env, depth)?;
                 }
                 Ok(Expression::None)
             }
             Expression::BSet(values) => {
                 for (i, pattern) in patterns.iter().enumerate() {
-                    match pattern {
-                        DestructurePattern::Identifier(name) => {
-                            env.define(
-                                name.as_str(),
-                                values.iter().nth(i).map_or(Expression::None, |v| v.clone()),
-                            );
-                        }
-                        DestructurePattern::Rest(name) => {
-                            let rest_set: BTreeSet<_> = values.iter().skip(i).cloned().collect();
-                            env.define(name.as_str(), Expression::BSet(Rc::new(rest_set)));
-                            break;
-                        }
-                        _ => {
-                            return Err(RuntimeError::common(
-                                "never use map_destructure on Set".into(),
-                                self.clone(),
-                                depth,
-                            ));
-                        }
+                    if let DestructurePattern::Rest(name) = pattern {
+                        let rest_set: BTreeSet<_> = values.iter().skip(i).cloned().collect();
+                        bind_var(name, Expression::BSet(Rc::new(rest_set)), state, env);
+                        break;
                     }
+                    let missing = || RuntimeErrorKind::IndexOutOfBounds {
+                        index: i as Int,
+                        len: values.len(),
+                    };
+                    let found = values.iter().nth(i).cloned();
+                    self.destructure_item(pattern, found, missing, state, env, depth)?;
                 }
                 Ok(Expression::None)
             }
             // 对象解构
             Expression::Map(map) => {
-                for pattern in patterns {
-                    match pattern {
-                        DestructurePattern::Identifier(name) => {
-                            let value = map.get(name).cloned().unwrap_or(Expression::None);
-                            env.define(name.as_str(), value);
-                        }
-                        DestructurePattern::Renamed((key, name)) => {
-                            let value = map.get(key).cloned().unwrap_or(Expression::None);
-                            env.define(name.as_str(), value);
-                        }
-                        _ => {
-                            return Err(RuntimeError::common(
-                                "never use list_destructure on Map".into(),
-                                self.clone(),
-                                depth,
-                            ));
-                        }
-                    }
-                }
-                Ok(Expression::None)
+                self.destructure_map(patterns, |k| map.get(k).cloned(), state, env, depth)
             }
             Expression::HMap(map) => {
-                for pattern in patterns {
-                    match pattern {
-                        DestructurePattern::Identifier(name) => {
-                            let value = map.get(name).cloned().unwrap_or(Expression::None);
-                            env.define(name.as_str(), value);
-                        }
-                        DestructurePattern::Renamed((key, name)) => {
-                            let value = map.get(key).cloned().unwrap_or(Expression::None);
-                            env.define(name.as_str(), value);
-                        }
-                        _ => {
-                            return Err(RuntimeError::common(
-                                "never use list_destructure on HMap".into(),
-                                self.clone(),
-                                depth,
-                            ));
-                        }
-                    }
-                }
-                Ok(Expression::None)
+                self.destructure_map(patterns, |k| map.get(k).cloned(), state, env, depth)
             }
 
             _ => Err(RuntimeError::new(
@@ -506,124 +479,62 @@ impl Expression {
             )),
         }
     }
-    pub fn destructure_assign_local(
+
+    fn destructure_map(
         &self,
-        patterns: &Vec<DestructurePattern>,
-        value: Expression,
+        patterns: &[DestructurePattern],
+        get: impl Fn(&str) -> Option<Expression>,
         state: &mut State,
+        env: &mut Environment,
         depth: usize,
     ) -> Result<Expression, RuntimeError> {
-        match value {
-            // 数组解构
-            Expression::List(values) => {
-                for (i, pattern) in patterns.iter().enumerate() {
-                    match pattern {
-                        DestructurePattern::Identifier(name) => {
-                            state.set_local_var(
-                                name.to_string(),
-                                values.get(i).map_or(Expression::None, |v| v.clone()),
-                            );
-                        }
-                        DestructurePattern::Rest(name) => {
-                            let rest_values: Vec<Expression> =
-                                values.iter().skip(i).cloned().collect();
-                            state.set_local_var(
-                                name.to_string(),
-                                Expression::List(Rc::new(rest_values)),
-                            );
-                            break;
-                        } // ... 其他模式
-                        _ => {
-                            return Err(RuntimeError::common(
-                                "never use map_destructure on List".into(),
-                                self.clone(),
-                                depth,
-                            ));
-                        }
-                    }
-                }
-                Ok(Expression::None)
-            }
-            Expression::BSet(values) => {
-                for (i, pattern) in patterns.iter().enumerate() {
-                    match pattern {
-                        DestructurePattern::Identifier(name) => {
-                            state.set_local_var(
-                                name.to_string(),
-                                values.iter().nth(i).map_or(Expression::None, |v| v.clone()),
-                            );
-                        }
-                        DestructurePattern::Rest(name) => {
-                            let rest_set: BTreeSet<_> = values.iter().skip(i).cloned().collect();
-                            state.set_local_var(
-                                name.to_string(),
-                                Expression::BSet(Rc::new(rest_set)),
-                            );
-                            break;
-                        }
-                        _ => {
-                            return Err(RuntimeError::common(
-                                "never use map_destructure on Set".into(),
-                                self.clone(),
-                                depth,
-                            ));
-                        }
-                    }
-                }
-                Ok(Expression::None)
-            }
-            // 对象解构
-            Expression::Map(map) => {
-                for pattern in patterns {
-                    match pattern {
-                        DestructurePattern::Identifier(name) => {
-                            let value = map.get(name).cloned().unwrap_or(Expression::None);
-                            state.set_local_var(name.to_string(), value);
-                        }
-                        DestructurePattern::Renamed((key, name)) => {
-                            let value = map.get(key).cloned().unwrap_or(Expression::None);
-                            state.set_local_var(name.to_string(), value);
-                        }
-                        _ => {
-                            return Err(RuntimeError::common(
-                                "never use list_destructure on Map".into(),
-                                self.clone(),
-                                depth,
-                            ));
-                        }
-                    }
-                }
-                Ok(Expression::None)
-            }
-            Expression::HMap(map) => {
-                for pattern in patterns {
-                    match pattern {
-                        DestructurePattern::Identifier(name) => {
-                            let value = map.get(name).cloned().unwrap_or(Expression::None);
-                            state.set_local_var(name.to_string(), value);
-                        }
-                        DestructurePattern::Renamed((key, name)) => {
-                            let value = map.get(key).cloned().unwrap_or(Expression::None);
-                            state.set_local_var(name.to_string(), value);
-                        }
-                        _ => {
-                            return Err(RuntimeError::common(
-                                "never use list_destructure on HMap".into(),
-                                self.clone(),
-                                depth,
-                            ));
-                        }
-                    }
+        for pattern in patterns {
+            let (key, found) = match pattern.key() {
+                Some(key) => (key, get(key)),
+                None => {
+                    return Err(RuntimeError::common(
+                        "never use list_destructure on Map".into(),
+                        self.clone(),
+                        depth,
+                    ));
                 }
-                Ok(Expression::None)
-            }
+            };
+            let missing = || RuntimeErrorKind::KeyNotFound(key.to_string());
+            self.destructure_item(pattern, found, missing, state, env, depth)?;
+        }
+        Ok(Expression::None)
+    }
 
-            _ => Err(RuntimeError::new(
-                RuntimeErrorKind::TypeError {
-                    expected: "destructurable value".into(),
-                    sym: value.to_string(),
-                    found: value.type_name(),
-                },
+    /// 绑定单个解构元素；缺失时使用默认值，没有默认值则报错
+    fn destructure_item(
+        &self,
+        pattern: &DestructurePattern,
+        found: Option<Expression>,
+        missing: impl FnOnce() -> RuntimeErrorKind,
+        state: &mut State,
+        env: &mut Environment,
+        depth: usize,
+    ) -> Result<(), RuntimeError> {
+        let (pattern, value) = match (pattern, found) {
+            (DestructurePattern::Default(inner, _), Some(v)) if !matches!(v, Expression::None) => {
+                (inner.as_ref(), v)
+            }
+            (DestructurePattern::Default(inner, default), _) => {
+                (inner.as_ref(), default.eval_mut(state, env, depth + 1)?)
+            }
+            (pattern, Some(v)) => (pattern, v),
+            (_, None) => return Err(RuntimeError::new(missing(), self.clone(), depth)),
+        };
+        match pattern {
+            DestructurePattern::Identifier(name) | DestructurePattern::Renamed((_, name)) => {
+                bind_var(name, value, state, env);
+                Ok(())
+            }
+            DestructurePattern::Nested(_, patterns) => self
+                .destructure_assign(patterns, value, state, env, depth + 1)
+                .map(|_| ()),
+            _ => Err(RuntimeError::common(
+                format!("invalid destructure pattern `{pattern}`").into(),
                 self.clone(),
                 depth,
             )),
@@ -633,7 +544,9 @@ impl Expression {
     pub fn get_free_variables(&self) -> HashSet<String> {
         match self {
             // 变量和符号 - 符号是自由变量，变量需要环境解析
-            Self::Symbol(name) | Self::Variable(name) => HashSet::from([name.clone()]),
+            Self::Symbol(name) | Self::Variable(name) | Self::LocalVar(_, name, _) => {
+                HashSet::from([name.clone()])
+            }
 
             // Lambda - 从body收集自由变量，然后移除参数
             Self::Lambda(params, body, _) => {
@@ -750,11 +663,11 @@ impl Expression {
                 vars
             }
 
-            Self::Function(_, params, _, body, decorators) => {
+            Self::Function(_, params, _, body, decorators, _) => {
                 let mut vars = body.get_free_variables();
                 // 移除参数
-                for (param, _) in params {
-                    vars.remove(param);
+                for name in params.iter().flat_map(|(p, ..)| p.names()) {
+                    vars.remove(name);
                 }
                 // 收集装饰器中的自由变量
                 for (_, decorator_args) in decorators {
@@ -786,7 +699,7 @@ impl Expression {
 
             // 其他表达式
             Self::Declare(_, expr) => expr.get_free_variables(),
-            Self::Assign(_, expr) => expr.get_free_variables(),
+            Self::Assign(_, expr) | Self::LocalSet(_, _, _, expr) => expr.get_free_variables(),
             Self::DestructureAssign(_, expr) => expr.get_free_variables(),
             Self::Return(expr) => expr.get_free_variables(),
             Self::Break(expr) => expr.get_free_variables(),
@@ -802,6 +715,21 @@ impl Expression {
                 }
                 vars
             }
+            Self::Try(body, var, handler, finally) => {
+                let mut vars = body.get_free_variables();
+                if let Some(handler) = handler {
+                    let mut handler_vars = handler.get_free_variables();
+                    if let Some(v) = var {
+                        handler_vars.remove(v);
+                    }
+                    vars.extend(handler_vars);
+                }
+                if let Some(finally) = finally {
+                    vars.extend(finally.get_free_variables());
+                }
+                vars
+            }
+            Self::Defer(expr) => expr.get_free_variables(),
             _ => HashSet::new(), // Del是删除语句，没有自由变量
         }
     }
@@ -861,10 +789,10 @@ where
     let last_iter = state.take_iter();
     let is_last_in_loop = state.contains(State::IN_FOR_LOOP);
     state.set(State::IN_FOR_LOOP);
-    state.set_iter(var_name, index_name, iterator.into());
+    state.set_iter(&var_name, index_name.as_deref(), iterator.into());
 
     let r = if state.contains(State::IN_ASSIGN) || state.contains(State::IN_PIPE) {
-        let mut results = Vec::with_capacity(count);
+        let mut results = Vec::with_capacity(count.min(1024));
         for _ in 0..count {
             if state.pop_iter().is_err() {
                 break;
@@ -891,9 +819,7 @@ where
                 Err(e) => {
                     state.clear_iter();
                     if is_last_in_loop {
-                        if let Some((var_name, index_name, _idx, iterator)) = last_iter {
-                            state.set_iter(var_name, index_name, iterator);
-                        }
+                        state.restore_iter(last_iter);
                     } else {
                         state.clear(State::IN_FOR_LOOP);
                         // state.clear_local_var();
@@ -931,9 +857,7 @@ where
                 Err(e) => {
                     state.clear_iter();
                     if is_last_in_loop {
-                        if let Some((var_name, index_name, _idx, iterator)) = last_iter {
-                            state.set_iter(var_name, index_name, iterator);
-                        }
+                        state.restore_iter(last_iter);
                     } else {
                         state.clear(State::IN_FOR_LOOP);
                         // state.clear_local_var();
@@ -948,9 +872,7 @@ where
     // 清理循环状态
     state.clear_iter();
     if is_last_in_loop {
-        if let Some((var_name, index_name, _idx, iterator)) = last_iter {
-            state.set_iter(var_name, index_name, iterator);
-        }
+        state.restore_iter(last_iter);
     } else {
         state.clear(State::IN_FOR_LOOP);
         // state.clear_local_var(); //clear here instead of in every block. for efficency and index secure.
@@ -958,3 +880,59 @@ where
     }
     r
 }
+
+/// Ctrl-C、`?!` 的终止与计时中的超时：须中断到最外层，不被 catch 拦截。
+/// 超时离开 sys.timeout 后即为普通错误，可以捕获
+fn is_interrupt(e: &RuntimeError) -> bool {
+    match e.kind {
+        RuntimeErrorKind::Terminated => true,
+        RuntimeErrorKind::Timeout(_) => crate::childman::expired_watchdog().is_some(),
+        _ => false,
+    }
+}
+
+fn bind_var(name: &str, value: Expression, state: &mut State, env: &mut Environment) {
+    if state.contains(State::IN_LOCAL) {
+        state.set_local_var(name, value);
+    } else {
+        env.define(name, value);
+    }
+}
+
+/// 顺序求值语句；defer 的表达式在退出时（含出错、return、break）逆序执行
+fn eval_statements(
+    exprs: &[Expression],
+    state: &mut State,
+    env: &mut Environment,
+    depth: usize,
+    tail: Option<Rc<Expression>>,
+) -> Result<Expression, RuntimeError> {
+    // 有 defer 时末尾语句之后仍有代码执行，不算尾部位置
+    let mut tail = tail.filter(|_| !exprs.iter().any(|e| matches!(e, Expression::Defer(_))));
+    let mut deferred = vec![];
+    let mut result = Ok(Expression::None);
+    for (i, expr) in exprs.iter().enumerate() {
+        if let Expression::Defer(d) = expr {
+            deferred.push(d);
+            continue;
+        }
+        if i + 1 == exprs.len() {
+            state.set_tail(tail.take());
+        }
+        state.set(State::CMD_STMT);
+        result = expr.eval_mut(state, env, depth + 1);
+        if result.is_err() {
+            break;
+        }
+    }
+    for d in deferred.into_iter().rev() {
+        let r = d.as_ref().eval_mut(state, env, depth + 1);
+        // 保留最先发生的错误
+        if let Err(e) = r
+            && result.is_ok()
+        {
+            result = Err(e);
+        }
+    }
+    result
+}
@@ -1,8 +1,12 @@
 use super::eval::State;
 use crate::expression::cmd_excutor::handle_command;
-use crate::expression::{ChainCall, alias};
-use crate::libs::{exec_self_expand_lib, get_builtin_via_expr, is_lib};
+use crate::expression::slot::slot_of;
+use crate::expression::{ChainCall, DestructurePattern, alias};
+use crate::libs::{exec_self_expand_lib, get_belong_lib_name, get_builtin_via_expr, is_lib};
+use crate::modman::module_member;
+use crate::profiler::{self, Kind};
 use crate::{Environment, Expression, MAX_RUNTIME_RECURSION, RuntimeError, RuntimeErrorKind};
+use std::rc::Rc;
 
 // 需要延迟解析的特殊命令列表
 // const LAZY_EVAL_COMMANDS: &[&str] = &[
@@ -56,6 +60,25 @@ pub fn prepare_args(
     Ok(args_eval)
 }
 
+/// 求值函数实参，期间按赋值语境捕获命令输出
+fn eval_fn_args(
+    args: &[Expression],
+    state: &mut State,
+    env: &mut Environment,
+    depth: usize,
+) -> Result<Vec<Expression>, RuntimeError> {
+    let is_in_pipe = state.contains(State::IN_ASSIGN);
+    state.set(State::IN_ASSIGN);
+    let actual_args = args
+        .iter()
+        .map(|a| a.eval_mut(state, env, depth))
+        .collect::<Result<Vec<_>, _>>();
+    if !is_in_pipe {
+        state.clear(State::IN_ASSIGN);
+    }
+    actual_args
+}
+
 /// 执行
 impl Expression {
     // 函数应用
@@ -68,83 +91,190 @@ impl Expression {
         env: &mut Environment,
         depth: usize,
     ) -> Result<Expression, RuntimeError> {
-        match func {
-            Expression::Function(name, params, pc, body, _decos) => {
-                // 1. 先检查参数数量上限
-                if pc.is_none() && args.len() > params.len() {
+        let Expression::Function(name, params, pc, body, _decos, ret) = func else {
+            unreachable!()
+        };
+        // 1. 先检查参数数量上限
+        if pc.is_none() && args.len() > params.len() {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::TooManyArguments {
+                    name,
+                    max: params.len(),
+                    received: args.len(),
+                },
+                self.clone(),
+                depth,
+            ));
+        }
+
+        // 2. 求值实际参数
+        let mut actual_args = eval_fn_args(args, state, env, depth + 1)?;
+        // 参数与 collector 绑定在局部帧中，槽位只需计算一次
+        let param_slots: Vec<Option<usize>> = params
+            .iter()
+            .map(|(p, ..)| match p {
+                DestructurePattern::Identifier(n) => Some(slot_of(n)),
+                _ => None,
+            })
+            .collect();
+        let shadowed: Vec<usize> = params
+            .iter()
+            .flat_map(|(p, ..)| p.names())
+            .chain(pc.as_deref())
+            .map(slot_of)
+            .collect();
+        let collector_slot = pc.as_deref().map(slot_of);
+
+        // 尾部位置的自调用返回 TailCall，换上新参数循环执行，不再嵌套求值
+        let result = loop {
+            // 每轮循环计为一次调用，与未优化的递归计数一致
+            let _span = profiler::span(Kind::Function, || name.clone());
+            // 3. 创建新作用域
+            let new_env = match state.contains(State::IN_DECO) {
+                true => &mut *env,
+                _ => &mut env.fork(),
+            };
+
+            // 4. 参数写入局部帧，遮蔽调用方的同名局部变量，返回时恢复
+            let scope = state.enter_call(&shadowed);
+            if let Some(slot) = collector_slot {
+                // collector 获取剩余参数
+                let rest = actual_args.get(params.len()..).unwrap_or_default().to_vec();
+                state.set_slot(slot, Expression::from(rest));
+            }
+            // 5. 绑定正式参数并执行函数体
+            let result = self
+                .bind_arguments(
+                    &name,
+                    &params,
+                    &param_slots,
+                    &actual_args,
+                    state,
+                    new_env,
+                    depth,
+                )
+                .and_then(|_| {
+                    state.set_tail(Some(body.clone()));
+                    body.as_ref().eval_mut(state, new_env, depth + 1)
+                });
+            state.leave_call(scope);
+            match result {
+                Ok(v) => break v,
+                Err(RuntimeError {
+                    kind: RuntimeErrorKind::EarlyReturn(v),
+                    context: _,
+                    depth: _,
+                }) => break v,
+                Err(RuntimeError {
+                    kind: RuntimeErrorKind::TailCall(next),
+                    ..
+                }) => {
+                    if pc.is_none() && next.len() > params.len() {
+                        return Err(RuntimeError::new(
+                            RuntimeErrorKind::TooManyArguments {
+                                name,
+                                max: params.len(),
+                                received: next.len(),
+                            },
+                            self.clone(),
+                            depth,
+                        ));
+                    }
+                    actual_args = next;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+        match ret {
+            Some(ty) if !matches_type(&result, &ty) => Err(RuntimeError::new(
+                RuntimeErrorKind::ArgTypeError {
+                    name,
+                    target: "return value".into(),
+                    expected: ty,
+                    found: result.type_name(),
+                },
+                self.clone(),
+                depth,
+            )),
+            _ => Ok(result),
+        }
+    }
+
+    /// 按参数表把实参绑定到局部帧：缺少的参数求默认值（可引用前面的参数），解构参数就地解构
+    #[allow(clippy::too_many_arguments)]
+    fn bind_arguments(
+        &self,
+        name: &str,
+        params: &[(DestructurePattern, Option<Expression>, Option<String>)],
+        slots: &[Option<usize>],
+        args: &[Expression],
+        state: &mut State,
+        env: &mut Environment,
+        depth: usize,
+    ) -> Result<(), RuntimeError> {
+        for (i, (param, default, ty)) in params.iter().enumerate() {
+            let arg = match (args.get(i), default) {
+                (Some(arg), _) => arg.clone(),
+                (None, Some(def_expr)) => def_expr.eval_mut(state, env, depth + 1)?,
+                (None, None) => {
                     return Err(RuntimeError::new(
-                        RuntimeErrorKind::TooManyArguments {
-                            name,
-                            max: params.len(),
+                        RuntimeErrorKind::ArgumentMismatch {
+                            name: name.to_string(),
+                            expected: params.len(),
                             received: args.len(),
                         },
                         self.clone(),
                         depth,
                     ));
                 }
-
-                // 2. 求值实际参数
-                let is_in_pipe = state.contains(State::IN_ASSIGN);
-                state.set(State::IN_ASSIGN);
-                let mut actual_args = args
-                    .iter()
-                    .map(|a| a.eval_mut(state, env, depth + 1))
-                    .collect::<Result<Vec<_>, _>>()?;
-                if !is_in_pipe {
-                    state.clear(State::IN_ASSIGN);
-                }
-
-                // 3. 填充默认值（修正后）
-                for (i, (_, default)) in params.iter().enumerate() {
-                    if i >= actual_args.len() {
-                        if let Some(def_expr) = default {
-                            actual_args.push(def_expr.clone());
-                        } else {
-                            return Err(RuntimeError::new(
-                                RuntimeErrorKind::ArgumentMismatch {
-                                    name,
-                                    expected: params.len(),
-                                    received: actual_args.len(),
-                                },
-                                self.clone(),
-                                depth,
-                            ));
-                        }
-                    }
-                }
-
-                // 4. 创建新作用域
-                let new_env = match state.contains(State::IN_DECO) {
-                    true => env,
-                    _ => &mut env.fork(),
-                };
-
-                // 5. 正确处理 collector
-                if let Some(collector) = pc {
-                    // collector 获取剩余参数
-                    new_env.define(
-                        collector.as_str(),
-                        Expression::from(actual_args[params.len()..].to_vec()),
-                    );
-                }
-
-                // 6. 绑定正式参数（只绑定定义的参数）
-                for ((param, _), arg) in params.iter().zip(actual_args.iter().take(params.len())) {
-                    new_env.define(param, arg.clone());
+            };
+            // 类型标注检查
+            if let Some(ty) = ty
+                && !matches_type(&arg, ty)
+            {
+                return Err(RuntimeError::new(
+                    RuntimeErrorKind::ArgTypeError {
+                        name: name.to_string(),
+                        target: format!("argument `{param}`"),
+                        expected: ty.clone(),
+                        found: arg.type_name(),
+                    },
+                    self.clone(),
+                    depth,
+                ));
+            }
+            match (param, slots[i]) {
+                (_, Some(slot)) => state.set_slot(slot, arg),
+                (DestructurePattern::Nested(_, patterns), _) => {
+                    self.destructure_assign(patterns, arg, state, env, depth + 1)?;
                 }
+                _ => unreachable!(),
+            }
+        }
+        Ok(())
+    }
 
-                // 执行函数体
-                match body.as_ref().eval_mut(state, new_env, depth + 1) {
-                    Ok(v) => Ok(v),
-                    Err(RuntimeError {
-                        kind: RuntimeErrorKind::EarlyReturn(v),
-                        context: _,
-                        depth: _,
-                    }) => Ok(v),
-                    Err(e) => Err(e),
-                }
+    /// 尾部位置对当前函数（body 相同且无装饰器）的调用，返回求值后的参数
+    pub fn tail_call_args(
+        &self,
+        func: &Expression,
+        body: &Rc<Expression>,
+        args: &[Expression],
+        state: &mut State,
+        env: &mut Environment,
+        depth: usize,
+    ) -> Result<Option<Vec<Expression>>, RuntimeError> {
+        // 只处理按名调用，查找没有副作用
+        if !matches!(func, Expression::Symbol(_)) {
+            return Ok(None);
+        }
+        match func.eval_symbo_with_domain(state, env, depth)? {
+            Expression::Function(_, _, _, b, decos, _)
+                if decos.is_empty() && Rc::ptr_eq(&b, body) =>
+            {
+                Ok(Some(eval_fn_args(args, state, env, depth)?))
             }
-            _ => unreachable!(),
+            _ => Ok(None),
         }
     }
 
@@ -157,7 +287,7 @@ impl Expression {
         depth: usize,
     ) -> Result<Expression, RuntimeError> {
         match &func {
-            Expression::Function(name, _params, _pc, _body, decos) => {
+            Expression::Function(name, _params, _pc, _body, decos, _) => {
                 let mut decoders = Vec::with_capacity(decos.len());
                 for deco in decos.iter() {
                     let deco_fo = env.get(deco.0.as_str());
@@ -276,12 +406,6 @@ impl Expression {
                 // let pipe_out = state.pipe_out(); //必须先取得pipeout，否则可能被参数取走
                 // dbg!("2.--- applying lambda---", &params);
                 let mut current_env = env.fork();
-                // 先应用已捕获的环境
-                if let Some(captured) = captured_env {
-                    for (key, value) in captured.iter() {
-                        current_env.define(key, value.clone());
-                    }
-                }
                 // 批量参数绑定前先求值所有参数
                 let is_in_pipe = state.contains(State::IN_PIPE);
                 state.set(State::IN_PIPE);
@@ -298,9 +422,23 @@ impl Expression {
                 // };
 
                 match bind_arguments(&params, &evaluated_args, &mut current_env) {
-                    // 完全应用：求值函数体
+                    // 完全应用：捕获的变量与参数写入局部帧，遮蔽调用方的同名局部变量
                     None => {
+                        let captured = captured_env.unwrap_or_default();
+                        let slots = captured
+                            .keys()
+                            .chain(params.iter())
+                            .map(|k| slot_of(k))
+                            .collect::<Vec<_>>();
+                        let scope = state.enter_call(&slots);
+                        for (slot, value) in slots
+                            .iter()
+                            .zip(captured.values().chain(evaluated_args.iter()).cloned())
+                        {
+                            state.set_slot(*slot, value);
+                        }
                         let result = body.as_ref().eval_mut(state, &mut current_env, depth + 1);
+                        state.leave_call(scope);
                         match result {
                             Ok(v) => {
                                 // self.set_status_code(0, env);
@@ -321,24 +459,30 @@ impl Expression {
                         }
                     }
 
-                    // 部分应用：返回新的柯里化lambda
-                    Some(remain) => Ok(Expression::Lambda(
-                        remain,
-                        body,
-                        Some(current_env.get_bindings_map()),
-                    )),
+                    // 部分应用：返回新的柯里化lambda，捕获的变量与已绑定的参数一并保存
+                    Some(remain) => {
+                        let mut bindings = captured_env.unwrap_or_default();
+                        bindings.extend(current_env.get_bindings_map());
+                        Ok(Expression::Lambda(remain, body, Some(bindings)))
+                    }
                 }
             }
 
-            Expression::Function(.., ref decos) => match decos.is_empty() {
+            Expression::Function(_, _, _, _, ref decos, _) => match decos.is_empty() {
                 true => self.eval_normal_function(func_eval, args, state, env, depth),
                 false => self.eval_function_with_deco(func_eval, args, state, env, depth),
             },
+            // 结构体构造 Point(1, 2)
+            Expression::StructDef(def) => {
+                let args = prepare_args(args, None, env, state, depth + 1)?;
+                def.construct(args, self, depth)
+            }
             // 命令形式的内置函数调用如： fs.read! a
             // 不用!,则进入eval_cmd中
             // Expression::Index
             // 模块调用
             Expression::ModuleCall(modules, function) => {
+                self.check_module_visibility(&modules, &function, state, env, depth)?;
                 state.set(State::IN_DOMAINS);
                 state.extend_lookup_domains(&modules);
                 let result = self.eval_apply(&function, args, state, env, depth + 1);
@@ -359,6 +503,57 @@ impl Expression {
         }
     }
 
+    /// 模块调用的可见性：当前模块内可访问私有成员，
+    /// 跨模块只能访问 pub 成员
+    fn check_module_visibility(
+        &self,
+        modules: &[String],
+        function: &Expression,
+        state: &State,
+        env: &mut Environment,
+        depth: usize,
+    ) -> Result<(), RuntimeError> {
+        // 找到当前所在模块，找不到时交由后续查找报错
+        let mut current = None;
+        for domain in state.get_lookup_domains() {
+            current = match (current, domain) {
+                (None, d) => env.get(d),
+                (Some(Expression::HMap(m)), d) => module_member(&m, d, true).cloned(),
+                _ => return Ok(()),
+            };
+        }
+        let in_module = current.is_some();
+        for (i, domain) in modules.iter().enumerate() {
+            current = match current {
+                None => env.get(domain),
+                Some(Expression::HMap(m)) => match module_member(&m, domain, i == 0 && in_module) {
+                    Some(next) => Some(next.clone()),
+                    None if module_member(&m, domain, true).is_some() => {
+                        return Err(RuntimeError::new(
+                            RuntimeErrorKind::PrivateMember(domain.clone(), modules[i - 1].clone()),
+                            self.clone(),
+                            depth,
+                        ));
+                    }
+                    None => return Ok(()),
+                },
+                _ => return Ok(()),
+            };
+        }
+        if let (Some(Expression::HMap(m)), Expression::Symbol(name), Some(module)) =
+            (current, function, modules.last())
+            && module_member(&m, name, false).is_none()
+            && module_member(&m, name, true).is_some()
+        {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::PrivateMember(name.clone(), module.clone()),
+                self.clone(),
+                depth,
+            ));
+        }
+        Ok(())
+    }
+
     pub fn eval_symbo_with_domain(
         &self,
         // name: &String,
@@ -401,7 +596,8 @@ impl Expression {
                             }
                         };
                         for (index, domain) in domains.iter().skip(1).enumerate() {
-                            match parent.get(domain) {
+                            // 可见性已在模块调用处检查，这里可访问私有成员
+                            match module_member(parent, domain, true) {
                                 Some(Expression::HMap(m)) => {
                                     parent = m;
                                 }
@@ -431,7 +627,7 @@ impl Expression {
                             }
                         }
                         // after got parent
-                        if let Some(func) = parent.get(name) {
+                        if let Some(func) = module_member(parent, name, true) {
                             // state.push_lookup_domain(module);
                             // let result = self.eval_apply(func, args, state, env, depth + 1);
                             // state.pop_lookup_domain();
@@ -741,6 +937,11 @@ pub fn bind_arguments(
     }
 }
 
+/// 类型标注匹配；Any 匹配任意值，未知类型名按 type_name 比较
+pub fn matches_type(value: &Expression, ty: &str) -> bool {
+    ty == "Any" || value.is_type(ty).unwrap_or_else(|| value.type_name() == ty)
+}
+
 #[inline]
 pub fn handle_builtin(
     base: &Expression,
@@ -767,6 +968,11 @@ pub fn handle_builtin(
             // 'xx' should be injected
             val => prepare_args(args, Some(val.clone()), env, state, depth)?,
         };
+        let _span = profiler::span(Kind::Builtin, || match base {
+            Expression::Blank => method.to_string(),
+            Expression::Symbol(lib) => format!("{lib}.{method}"),
+            val => format!("{}.{method}", get_belong_lib_name(val).unwrap_or_default()),
+        });
         let result = bfn(p_args, env, ctx)?;
 
         return Ok(Some(result));
@@ -852,6 +1058,13 @@ impl Expression {
         // 依次执行每个链式调用
         for call in calls {
             let method = call.method.as_str();
+            // 结构体方法优先于内置库
+            if let Some(result) =
+                self.eval_record_method(&current_base, method, &call.args, state, env, depth)?
+            {
+                current_base = result;
+                continue;
+            }
             let lib_result =
                 handle_builtin(&current_base, method, &call.args, self, state, env, depth);
 
@@ -0,0 +1,235 @@
+// 格式说明符：[[fill]align][+][0][width][,][.precision][type]，时间可用 %Y-%m-%d
+
+use num_traits::{Signed, ToPrimitive};
+use std::fmt::{self, Write};
+use unicode_width::UnicodeWidthStr;
+
+use super::Expression;
+
+/// 解析后的格式说明符，如 `>10`、`.2`、`08x`、`,`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatSpec {
+    fill: char,
+    align: Option<char>,
+    sign: bool,
+    zero: bool,
+    width: usize,
+    thousands: bool,
+    precision: Option<usize>,
+    radix: Option<char>,
+    time: Option<String>, // strftime 模式，仅用于 DateTime
+}
+
+impl Default for FormatSpec {
+    fn default() -> Self {
+        Self {
+            fill: ' ',
+            align: None,
+            sign: false,
+            zero: false,
+            width: 0,
+            thousands: false,
+            precision: None,
+            radix: None,
+            time: None,
+        }
+    }
+}
+
+impl FormatSpec {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut fs = Self::default();
+        if spec.starts_with('%') {
+            fs.time = Some(spec.to_string());
+            return Ok(fs);
+        }
+        let chars = spec.chars().collect::<Vec<_>>();
+        let is_align = |c: Option<&char>| matches!(c, Some('<' | '>' | '^'));
+        let mut i = 0;
+        if is_align(chars.get(1)) {
+            fs.fill = chars[0];
+            fs.align = Some(chars[1]);
+            i = 2;
+        } else if is_align(chars.first()) {
+            fs.align = Some(chars[0]);
+            i = 1;
+        }
+        if chars.get(i) == Some(&'+') {
+            fs.sign = true;
+            i += 1;
+        }
+        if chars.get(i) == Some(&'0') {
+            fs.zero = true;
+            i += 1;
+        }
+        let digits = |i: &mut usize| {
+            let start = *i;
+            while chars.get(*i).is_some_and(char::is_ascii_digit) {
+                *i += 1;
+            }
+            chars[start..*i]
+                .iter()
+                .collect::<String>()
+                .parse::<usize>()
+                .ok()
+        };
+        fs.width = digits(&mut i).unwrap_or(0);
+        if chars.get(i) == Some(&',') {
+            fs.thousands = true;
+            i += 1;
+        }
+        if chars.get(i) == Some(&'.') {
+            i += 1;
+            fs.precision = Some(digits(&mut i).ok_or_else(|| {
+                format!("invalid format spec `{spec}`: missing precision after `.`")
+            })?);
+        }
+        if let Some(c @ ('x' | 'X' | 'o' | 'b' | 'e' | 'E')) = chars.get(i) {
+            fs.radix = Some(*c);
+            i += 1;
+        }
+        if i != chars.len() || spec.is_empty() {
+            return Err(format!("invalid format spec `{spec}`"));
+        }
+        Ok(fs)
+    }
+
+    /// 按宽度与对齐填充；数字默认右对齐，其余左对齐
+    fn pad(&self, sign: &str, body: &str, numeric: bool) -> String {
+        let len = sign.width() + body.width();
+        if len >= self.width {
+            return format!("{sign}{body}");
+        }
+        let gap = self.width - len;
+        if self.zero && self.align.is_none() && numeric {
+            return format!("{sign}{}{body}", "0".repeat(gap));
+        }
+        let fill = |n: usize| self.fill.to_string().repeat(n);
+        let align = self.align.unwrap_or(if numeric { '>' } else { '<' });
+        match align {
+            '>' => format!("{}{sign}{body}", fill(gap)),
+            '^' => format!("{}{sign}{body}{}", fill(gap / 2), fill(gap - gap / 2)),
+            _ => format!("{sign}{body}{}", fill(gap)),
+        }
+    }
+
+    fn sign(&self, negative: bool) -> &'static str {
+        match (negative, self.sign) {
+            (true, _) => "-",
+            (false, true) => "+",
+            _ => "",
+        }
+    }
+
+    fn fmt_int(&self, n: i128) -> Result<String, String> {
+        self.fmt_digits(n < 0, n.unsigned_abs(), n as f64)
+    }
+
+    /// 整数按绝对值输出，带小数或科学计数法时改走浮点
+    fn fmt_digits<T>(&self, negative: bool, abs: T, approx: f64) -> Result<String, String>
+    where
+        T: fmt::Display + fmt::LowerHex + fmt::UpperHex + fmt::Octal + fmt::Binary,
+    {
+        let body = match self.radix {
+            Some('x') => format!("{abs:x}"),
+            Some('X') => format!("{abs:X}"),
+            Some('o') => format!("{abs:o}"),
+            Some('b') => format!("{abs:b}"),
+            Some(_) => return self.fmt_float(approx),
+            None if self.precision.is_some() => return self.fmt_float(approx),
+            None if self.thousands => group_thousands(&abs.to_string()),
+            None => abs.to_string(),
+        };
+        Ok(self.pad(self.sign(negative), &body, true))
+    }
+
+    fn fmt_float(&self, f: f64) -> Result<String, String> {
+        let abs = f.abs();
+        let body = match (self.radix, self.precision) {
+            (Some(c @ ('x' | 'X' | 'o' | 'b')), _) => {
+                return Err(format!("format type `{c}` needs an integer, found {f}"));
+            }
+            (Some('e'), Some(p)) => format!("{abs:.p$e}"),
+            (Some('e'), None) => format!("{abs:e}"),
+            (Some(_), Some(p)) => format!("{abs:.p$E}"),
+            (Some(_), None) => format!("{abs:E}"),
+            (None, Some(p)) => format!("{abs:.p$}"),
+            (None, None) => abs.to_string(),
+        };
+        let body = match (self.thousands, self.radix) {
+            (true, None) => match body.split_once('.') {
+                Some((int, frac)) => format!("{}.{frac}", group_thousands(int)),
+                None => group_thousands(&body),
+            },
+            _ => body,
+        };
+        Ok(self.pad(self.sign(f.is_sign_negative() && f != 0.0), &body, true))
+    }
+
+    fn fmt_text(&self, s: &str) -> Result<String, String> {
+        if let Some(c) = self.radix {
+            return Err(format!("format type `{c}` needs a number, found `{s}`"));
+        }
+        let body = match self.precision {
+            Some(p) => s.chars().take(p).collect(),
+            None => s.to_string(),
+        };
+        Ok(self.pad("", &body, false))
+    }
+}
+
+/// 整数部分每三位插入逗号
+fn group_thousands(digits: &str) -> String {
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+impl Expression {
+    /// 按格式说明符输出
+    pub fn format_with(&self, spec: &FormatSpec) -> Result<String, String> {
+        if let Some(pattern) = &spec.time {
+            let Self::DateTime(t) = self else {
+                return Err(format!(
+                    "time pattern `{pattern}` needs a DateTime, found {}",
+                    self.type_name()
+                ));
+            };
+            let mut out = String::new();
+            write!(out, "{}", t.format(pattern))
+                .map_err(|_| format!("invalid time pattern `{pattern}`"))?;
+            return Ok(out);
+        }
+        match self {
+            Self::Integer(n) => spec.fmt_int(*n as i128),
+            Self::BigInt(n) => spec.fmt_digits(
+                n.is_negative(),
+                n.magnitude(),
+                n.to_f64().unwrap_or(f64::NAN),
+            ),
+            Self::Float(f) => spec.fmt_float(*f),
+            // 文件大小：进制或千分位按字节数，精度按可读单位
+            Self::FileSize(fs) => match spec.precision {
+                _ if spec.radix.is_some() || spec.thousands => spec.fmt_int(fs.to_bytes() as i128),
+                Some(p) => {
+                    let bytes = fs.to_bytes();
+                    let (unit, shift) = [("P", 50), ("T", 40), ("G", 30), ("M", 20), ("K", 10)]
+                        .into_iter()
+                        .find(|(_, s)| bytes >= 1 << s)
+                        .unwrap_or(("B", 0));
+                    let value = bytes as f64 / (1u64 << shift) as f64;
+                    Ok(spec.pad("", &format!("{value:.p$}{unit}"), true))
+                }
+                None => Ok(spec.pad("", &fs.to_human_readable(), true)),
+            },
+            Self::DateTime(_) => Ok(spec.pad("", &self.to_string(), false)),
+            Self::String(s) => spec.fmt_text(s),
+            other => spec.fmt_text(&other.to_string()),
+        }
+    }
+}
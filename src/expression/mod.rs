@@ -6,18 +6,25 @@ use std::ops::Range;
 use std::rc::Rc;
 pub mod alias;
 pub mod basic;
+pub mod bignum;
 pub mod catcher;
 pub mod cmd_excutor;
+pub mod codec;
 pub mod eval;
 pub mod eval2;
 pub mod eval3;
+pub mod fmt_spec;
 pub mod from;
 pub mod overop;
 pub mod pty;
+pub mod record;
+pub mod slot;
 pub mod table;
 pub mod terminal;
 
+use bignum::{BigOperands, big_operands};
 use chrono::NaiveDateTime;
+use record::RecordType;
 use regex_lite::Regex;
 #[derive(Clone, PartialEq)]
 pub enum Expression {
@@ -31,7 +38,10 @@ pub enum Expression {
     // 基础类型保持原样
     Symbol(String),
     Variable(String),
+    LocalVar(usize, String, bool), // 槽位解析后的变量：槽位、变量名、是否源自裸符号
+    LocalSet(usize, String, bool, Rc<Self>), // 槽位解析后的 let/赋值：槽位、变量名、是否为声明、值
     Integer(Int),
+    BigInt(Rc<num_bigint::BigInt>), // 超出 i64 的整数，运算结果能放进 i64 时降级
     Float(f64),
     Bytes(Vec<u8>), // 这个保持值类型，因为Rc<Vec>反而增加复杂度
     String(String),
@@ -39,6 +49,7 @@ pub enum Expression {
     RegexDef(String),
     TimeDef(String),
     Regex(LumeRegex),
+    Stream(LumeStream), // 惰性迭代流
     Boolean(bool),
     None,
 
@@ -62,7 +73,7 @@ pub enum Expression {
     For(String, Option<String>, Rc<Self>, Rc<Self>),
     While(Rc<Self>, Rc<Self>),
     Loop(Rc<Self>),
-    Match(Rc<Self>, Rc<Vec<(Vec<Self>, Self)>>),
+    Match(Rc<Self>, Rc<Vec<(Vec<MatchPattern>, Self)>>),
     If(Rc<Self>, Rc<Self>, Rc<Self>),
     Apply(Rc<Self>, Rc<Vec<Self>>),
     Command(Rc<Self>, Rc<Vec<Self>>),
@@ -70,11 +81,14 @@ pub enum Expression {
     Lambda(Vec<String>, Rc<Self>, Option<HashMap<String, Self>>),
     Function(
         String,
-        Vec<(String, Option<Self>)>,
+        Vec<(DestructurePattern, Option<Self>, Option<String>)>, // 参数名或解构模式、默认值、类型标注
         Option<String>,
         Rc<Self>,
         Vec<(String, Option<Vec<Expression>>)>,
+        Option<String>, // 返回值类型标注
     ),
+    StructDef(Rc<RecordType>), // 结构体定义，同时作为类型和构造器
+    Record(Rc<RecordType>, Rc<BTreeMap<String, Self>>), // 结构体实例
     Return(Rc<Self>),
     Break(Rc<Self>),
     Continue,
@@ -82,6 +96,8 @@ pub enum Expression {
     Block(Rc<Vec<Self>>),
     Quote(Rc<Self>),
     Catch(Rc<Self>, CatchType, Option<Rc<Self>>),
+    Try(Rc<Self>, Option<String>, Option<Rc<Self>>, Option<Rc<Self>>), // try {} catch e {} finally {}
+    Defer(Rc<Self>),                                                   // 所在块或函数退出时执行
     Range(Range<Int>, usize),
     DateTime(NaiveDateTime),
     FileSize(FileSize),
@@ -89,8 +105,8 @@ pub enum Expression {
     Chain(Rc<Expression>, Vec<ChainCall>), // 链式调用
     PipeMethod(String, Rc<Vec<Self>>),
     DestructureAssign(Vec<DestructurePattern>, Rc<Expression>), // 解构赋值
-    Use(Option<String>, String),
-    ModuleCall(Vec<String>, Rc<Self>), //模块调用
+    Use(Option<String>, String, Option<Vec<String>>), // use path as alias / use path::{a, b}
+    ModuleCall(Vec<String>, Rc<Self>),                //模块调用
     Blank,
 }
 
@@ -104,6 +120,58 @@ impl PartialEq for LumeRegex {
     }
 }
 
+/// 惰性流：按需拉取元素，克隆后共享同一迭代位置
+pub type StreamItem = Result<Expression, crate::RuntimeError>;
+
+#[derive(Clone)]
+pub struct LumeStream {
+    inner: Rc<std::cell::RefCell<Box<dyn Iterator<Item = StreamItem>>>>,
+    error: Rc<std::cell::RefCell<Option<crate::RuntimeError>>>,
+}
+
+impl LumeStream {
+    pub fn new(iter: impl Iterator<Item = StreamItem> + 'static) -> Self {
+        Self {
+            inner: Rc::new(std::cell::RefCell::new(Box::new(iter))),
+            error: Rc::new(std::cell::RefCell::new(None)),
+        }
+    }
+    /// 取出下一个元素
+    pub fn pull(&self) -> Option<StreamItem> {
+        self.inner.borrow_mut().next()
+    }
+    /// 迭代中出现的错误，供 for 循环结束后检查
+    pub fn take_error(&self) -> Option<crate::RuntimeError> {
+        self.error.borrow_mut().take()
+    }
+}
+
+impl Iterator for LumeStream {
+    type Item = Expression;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.pull()? {
+            Ok(v) => Some(v),
+            Err(e) => {
+                self.error.replace(Some(e));
+                None
+            }
+        }
+    }
+}
+
+impl PartialEq for LumeStream {
+    fn eq(&self, other: &LumeStream) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl std::fmt::Debug for LumeStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<stream>")
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ChainCall {
     pub method: String,
@@ -114,9 +182,74 @@ pub struct ChainCall {
 pub enum DestructurePattern {
     Identifier(String),
     Renamed((String, String)),
-    Rest(String), // ...rest 语法
-                  // Nested(Box<Expression>),          // 嵌套解构
-                  // Default(String, Box<Expression>), // 默认值
+    Rest(String),                                     // ...rest 语法
+    Nested(Option<String>, Vec<DestructurePattern>),  // 嵌套解构，对象中带键名
+    Default(Box<DestructurePattern>, Rc<Expression>), // 默认值
+}
+
+impl DestructurePattern {
+    /// 对象解构时对应的键名
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            Self::Identifier(k) | Self::Renamed((k, _)) | Self::Nested(Some(k), _) => Some(k),
+            Self::Default(p, _) => p.key(),
+            _ => None,
+        }
+    }
+
+    /// 模式绑定的变量名
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Self::Identifier(n) | Self::Renamed((_, n)) | Self::Rest(n) => vec![n],
+            Self::Nested(_, ps) => ps.iter().flat_map(|p| p.names()).collect(),
+            Self::Default(p, _) => p.names(),
+        }
+    }
+}
+
+/// match 分支的模式
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchPattern {
+    Value(Expression),            // 字面量、符号、正则、范围、时间
+    Bind(String),                 // 绑定变量，仅在分支内可见
+    Type(Option<String>, String), // 类型测试 `_: Int`，`x: Int` 匹配时绑定 x
+    List(Vec<MatchPattern>, Option<Box<MatchPattern>>), // [a, b, ...rest]
+    Map(Vec<(String, MatchPattern)>), // {status: 200, body}
+    Guard(Box<MatchPattern>, Rc<Expression>), // pat if cond
+}
+
+/// 模式与类型测试中可用的类型名
+pub const TYPE_NAMES: [&str; 21] = [
+    "Int", "Integer", "Float", "Number", "String", "Bool", "Boolean", "List", "Map", "HMap", "Set",
+    "Table", "Range", "Time", "DateTime", "FileSize", "Bytes", "Regex", "Stream", "Fn", "None",
+];
+
+impl Expression {
+    /// 类型测试；name 不是类型名时返回 None
+    pub fn is_type(&self, name: &str) -> Option<bool> {
+        let is = match name {
+            "Int" | "Integer" => matches!(self, Self::Integer(_) | Self::BigInt(_)),
+            "Float" => matches!(self, Self::Float(_)),
+            "Number" => matches!(self, Self::Integer(_) | Self::BigInt(_) | Self::Float(_)),
+            "String" => matches!(self, Self::String(_)),
+            "Bool" | "Boolean" => matches!(self, Self::Boolean(_)),
+            "List" => matches!(self, Self::List(_)),
+            "Map" => matches!(self, Self::Map(_) | Self::HMap(_)),
+            "HMap" => matches!(self, Self::HMap(_)),
+            "Set" => matches!(self, Self::BSet(_)),
+            "Table" => matches!(self, Self::Table(_)),
+            "Range" => matches!(self, Self::Range(..)),
+            "Time" | "DateTime" => matches!(self, Self::DateTime(_)),
+            "FileSize" => matches!(self, Self::FileSize(_)),
+            "Bytes" => matches!(self, Self::Bytes(_)),
+            "Regex" => matches!(self, Self::Regex(_)),
+            "Stream" => matches!(self, Self::Stream(_)),
+            "Fn" => matches!(self, Self::Function(..) | Self::Lambda(..)),
+            "None" => matches!(self, Self::None),
+            _ => return None,
+        };
+        Some(is)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -262,6 +395,10 @@ impl PartialOrd for Expression {
             (Self::Float(a), Self::Float(b)) => a.partial_cmp(b),
             (Self::Float(a), Self::Integer(b)) => a.partial_cmp(&(*b as f64)),
             (Self::Integer(a), Self::Float(b)) => (&(*a as f64)).partial_cmp(b),
+            (Self::BigInt(_), _) | (_, Self::BigInt(_)) => match big_operands(self, other)? {
+                BigOperands::Int(a, b) => a.partial_cmp(&b),
+                BigOperands::Float(a, b) => a.partial_cmp(&b),
+            },
 
             // ===== 字符串与数字互比 =====
             (Self::String(a), Self::Integer(b)) => a.parse::<i64>().ok()?.partial_cmp(b),
@@ -282,6 +419,11 @@ impl PartialOrd for Expression {
             (Self::TimeDef(a), Self::TimeDef(b)) => a.partial_cmp(b),
             (Self::DateTime(a), Self::DateTime(b)) => a.partial_cmp(b),
             (Self::FileSize(a), Self::FileSize(b)) => a.partial_cmp(b),
+            (Self::StructDef(a), Self::StructDef(b)) => a.name.partial_cmp(&b.name),
+            // 同一结构体的实例按字段比较
+            (Self::Record(a, x), Self::Record(b, y)) if a.name == b.name => {
+                Self::Map(x.clone()).partial_cmp(&Self::Map(y.clone()))
+            }
 
             // ===== 集合类型按长度比较 =====
             (Self::List(a), Self::List(b)) => match a.len().cmp(&b.len()) {
@@ -356,6 +498,7 @@ pub enum BoxedIterator {
     Vec(std::vec::IntoIter<Expression>),
     Map(std::collections::hash_map::IntoIter<String, Expression>),
     MapEntries(std::vec::IntoIter<Expression>),
+    Stream(LumeStream),
     // Other(Box<dyn Iterator<Item=Expression>>)
 }
 
@@ -370,6 +513,7 @@ impl Iterator for BoxedIterator {
                 .next()
                 .map(|(k, v)| Expression::from(vec![Expression::String(k), v])),
             Self::MapEntries(iter) => iter.next(),
+            Self::Stream(iter) => iter.next(),
         }
     }
 }
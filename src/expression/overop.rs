@@ -5,9 +5,12 @@ use std::{
     rc::Rc,
 };
 
-use crate::RuntimeErrorKind;
+use crate::{Environment, RuntimeError, RuntimeErrorKind};
 
 use super::Expression;
+use super::bignum::big_arith;
+use super::eval::State;
+use num_bigint::BigInt;
 
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Rem, Sub, SubAssign};
 
@@ -17,18 +20,19 @@ impl Add for Expression {
     fn add(self, other: Self) -> Result<Self, RuntimeErrorKind> {
         match (self, other) {
             // 数值运算
-            (Self::Integer(m), Self::Integer(n)) => m
+            (Self::Integer(m), Self::Integer(n)) => Ok(m
                 .checked_add(n)
-                .map(Self::Integer)
-                .ok_or_else(|| RuntimeErrorKind::Overflow(format!("{m} + {n}"))),
+                .map_or_else(|| Self::from(BigInt::from(m) + n), Self::Integer)),
             (Self::Integer(m), Self::Float(n)) => Ok(Self::Float(m as f64 + n)),
             (Self::Float(m), Self::Integer(n)) => Ok(Self::Float(m + n as f64)),
             (Self::Float(m), Self::Float(n)) => Ok(Self::Float(m + n)),
+            // 溢出后的大整数
+            (m, n) if let Some(v) = big_arith(&m, &n, |a, b| a + b, |a, b| a + b) => Ok(v),
 
             (Self::Integer(m), Self::String(n)) => {
                 // 尝试将字符串转换为整数
                 match n.parse::<i64>() {
-                    Ok(n) => Ok(Self::Integer(m + n)),
+                    Ok(n) => Self::Integer(m) + Self::Integer(n),
                     Err(_) => Err(RuntimeErrorKind::CommandFailed2(
                         "+".into(),
                         format!("Cannot convert string `{n}` to integer"),
@@ -81,7 +85,9 @@ impl Add for Expression {
 
             // 字符串拼接
             (Self::String(m), Self::String(n)) => Ok(Self::String(m + &n)),
-            (Self::String(m), Self::Integer(n)) => Ok(Self::String(m + &n.to_string())),
+            (Self::String(m), n @ (Self::Integer(_) | Self::BigInt(_))) => {
+                Ok(Self::String(m + &n.to_string()))
+            }
             (Self::String(m), Self::Float(n)) => Ok(Self::String(m + &n.to_string())),
 
             (Self::String(m), Self::List(b)) => {
@@ -198,18 +204,18 @@ impl Sub for Expression {
     fn sub(self, other: Self) -> Result<Self, RuntimeErrorKind> {
         match (self, other) {
             // 数值运算
-            (Self::Integer(m), Self::Integer(n)) => m
+            (Self::Integer(m), Self::Integer(n)) => Ok(m
                 .checked_sub(n)
-                .map(Self::Integer)
-                .ok_or_else(|| RuntimeErrorKind::Overflow(format!("{m} - {n}"))),
+                .map_or_else(|| Self::from(BigInt::from(m) - n), Self::Integer)),
             (Self::Integer(m), Self::Float(n)) => Ok(Self::Float(m as f64 - n)),
             (Self::Float(m), Self::Integer(n)) => Ok(Self::Float(m - n as f64)),
             (Self::Float(m), Self::Float(n)) => Ok(Self::Float(m - n)),
+            (m, n) if let Some(v) = big_arith(&m, &n, |a, b| a - b, |a, b| a - b) => Ok(v),
             // to-string
             (Self::Integer(m), Self::String(n)) => {
                 // 尝试将字符串转换为整数
                 match n.parse::<i64>() {
-                    Ok(n) => Ok(Self::Integer(m - n)),
+                    Ok(n) => Self::Integer(m) - Self::Integer(n),
                     Err(_) => Err(RuntimeErrorKind::CommandFailed2(
                         "-".into(),
                         format!("Cannot convert string `{n}` to integer"),
@@ -388,20 +394,18 @@ impl Mul for Expression {
     fn mul(self, other: Self) -> Result<Self, RuntimeErrorKind> {
         match (self, other) {
             // num
-            (Self::Integer(m), Self::Integer(n)) => match m.checked_mul(n) {
-                Some(result) => Ok(Self::Integer(result)),
-                None => Err(RuntimeErrorKind::Overflow(format!(
-                    "Integer overflow when multiplying {m} and {n}"
-                ))),
-            },
+            (Self::Integer(m), Self::Integer(n)) => Ok(m
+                .checked_mul(n)
+                .map_or_else(|| Self::from(BigInt::from(m) * n), Self::Integer)),
             (Self::Integer(m), Self::Float(n)) => Ok(Self::Float(m as f64 * n)),
             (Self::Float(m), Self::Integer(n)) => Ok(Self::Float(m * n as f64)),
             (Self::Float(m), Self::Float(n)) => Ok(Self::Float(m * n)),
+            (m, n) if let Some(v) = big_arith(&m, &n, |a, b| a * b, |a, b| a * b) => Ok(v),
             // to-string
             (Self::Integer(n), Self::String(m)) => {
                 // 尝试将字符串转换为整数
                 match m.parse::<i64>() {
-                    Ok(num) => Ok(Self::Integer(n * num)),
+                    Ok(num) => Self::Integer(n) * Self::Integer(num),
                     Err(_) => Err(RuntimeErrorKind::CommandFailed2(
                         "*".into(),
                         format!("Cannot convert string `{m}` to integer"),
@@ -566,10 +570,14 @@ impl Div for Expression {
             (l, Self::String(s)) if s == "0" || s == "0.0" => Err(RuntimeErrorKind::CustomError(
                 format!("can't divide {l} by zero").into(),
             )),
-            (Self::Integer(m), Self::Integer(n)) => Ok(Self::Integer(m / n)),
+            // i64::MIN / -1 溢出
+            (Self::Integer(m), Self::Integer(n)) => Ok(m
+                .checked_div(n)
+                .map_or_else(|| Self::from(BigInt::from(m) / n), Self::Integer)),
             (Self::Integer(m), Self::Float(n)) => Ok(Self::Float(m as f64 / n)),
             (Self::Float(m), Self::Integer(n)) => Ok(Self::Float(m / n as f64)),
             (Self::Float(m), Self::Float(n)) => Ok(Self::Float(m / n)),
+            (m, n) if let Some(v) = big_arith(&m, &n, |a, b| a / b, |a, b| a / b) => Ok(v),
 
             // to-string
             (Self::Integer(n), Self::String(m)) => {
@@ -641,7 +649,10 @@ impl Neg for Expression {
     type Output = Expression;
     fn neg(self) -> Self::Output {
         match self {
-            Self::Integer(n) => Self::Integer(-n),
+            Self::Integer(n) => n
+                .checked_neg()
+                .map_or_else(|| Self::from(-BigInt::from(n)), Self::Integer),
+            Self::BigInt(n) => Self::from(-n.as_ref()),
             Self::Float(n) => Self::Float(-n),
             Self::Boolean(b) => Self::Boolean(!b),
             _ => Self::None,
@@ -651,10 +662,13 @@ impl Neg for Expression {
 impl AddAssign for Expression {
     fn add_assign(&mut self, other: Self) {
         *self = match (&self, other) {
-            (Self::Integer(m), Self::Integer(n)) => Self::Integer(m.wrapping_add(n)),
+            (Self::Integer(m), Self::Integer(n)) => m
+                .checked_add(n)
+                .map_or_else(|| Self::from(BigInt::from(*m) + n), Self::Integer),
             (Self::Integer(m), Self::Float(n)) => Self::Float(*m as f64 + n),
             (Self::Float(m), Self::Integer(n)) => Self::Float(*m + n as f64),
             (Self::Float(m), Self::Float(n)) => Self::Float(*m + n),
+            (m, n) if let Some(v) = big_arith(m, &n, |a, b| a + b, |a, b| a + b) => v,
             _ => return,
         }
     }
@@ -662,10 +676,13 @@ impl AddAssign for Expression {
 impl SubAssign for Expression {
     fn sub_assign(&mut self, other: Self) {
         *self = match (&self, other) {
-            (Self::Integer(m), Self::Integer(n)) => Self::Integer(m.wrapping_sub(n)),
+            (Self::Integer(m), Self::Integer(n)) => m
+                .checked_sub(n)
+                .map_or_else(|| Self::from(BigInt::from(*m) - n), Self::Integer),
             (Self::Integer(m), Self::Float(n)) => Self::Float(*m as f64 - n),
             (Self::Float(m), Self::Integer(n)) => Self::Float(*m - n as f64),
             (Self::Float(m), Self::Float(n)) => Self::Float(*m - n),
+            (m, n) if let Some(v) = big_arith(m, &n, |a, b| a - b, |a, b| a - b) => v,
             _ => return,
         }
     }
@@ -673,10 +690,13 @@ impl SubAssign for Expression {
 impl MulAssign for Expression {
     fn mul_assign(&mut self, other: Self) {
         *self = match (&self, other) {
-            (Self::Integer(m), Self::Integer(n)) => Self::Integer(m.wrapping_mul(n)),
+            (Self::Integer(m), Self::Integer(n)) => m
+                .checked_mul(n)
+                .map_or_else(|| Self::from(BigInt::from(*m) * n), Self::Integer),
             (Self::Integer(m), Self::Float(n)) => Self::Float(*m as f64 * n),
             (Self::Float(m), Self::Integer(n)) => Self::Float(*m * n as f64),
             (Self::Float(m), Self::Float(n)) => Self::Float(*m * n),
+            (m, n) if let Some(v) = big_arith(m, &n, |a, b| a * b, |a, b| a * b) => v,
             _ => return,
         }
     }
@@ -686,24 +706,103 @@ impl DivAssign for Expression {
         *self = match (&self, other) {
             (_, Self::Integer(0)) => Self::None,
             (_, Self::Float(0.0)) => Self::None,
-            (Self::Integer(m), Self::Integer(n)) => Self::Integer(*m / n),
+            (Self::Integer(m), Self::Integer(n)) => m
+                .checked_div(n)
+                .map_or_else(|| Self::from(BigInt::from(*m) / n), Self::Integer),
             (Self::Integer(m), Self::Float(n)) => Self::Float(*m as f64 / n),
             (Self::Float(m), Self::Integer(n)) => Self::Float(*m / n as f64),
             (Self::Float(m), Self::Float(n)) => Self::Float(*m / n),
+            (m, n) if let Some(v) = big_arith(m, &n, |a, b| a / b, |a, b| a / b) => v,
             _ => return,
         }
     }
 }
 
 impl Rem for Expression {
-    type Output = Self;
-    fn rem(self, other: Self) -> Self {
+    type Output = Result<Self, RuntimeErrorKind>;
+    fn rem(self, other: Self) -> Result<Self, RuntimeErrorKind> {
         match (self, other) {
-            (Self::Integer(m), Self::Integer(n)) => Self::Integer(m % n),
-            (Self::Float(m), Self::Integer(n)) => Self::Float(m % n as f64),
-            (Self::Integer(m), Self::Float(n)) => Self::Float(m as f64 % n),
-            (Self::Float(m), Self::Float(n)) => Self::Float(m % n),
-            _ => Self::None,
+            (l, Self::Integer(0) | Self::Float(0.0)) => Err(RuntimeErrorKind::CustomError(
+                format!("can't divide {l} by zero").into(),
+            )),
+            (Self::Integer(m), Self::Integer(n)) => Ok(Self::Integer(m.wrapping_rem(n))),
+            (Self::Float(m), Self::Integer(n)) => Ok(Self::Float(m % n as f64)),
+            (Self::Integer(m), Self::Float(n)) => Ok(Self::Float(m as f64 % n)),
+            (Self::Float(m), Self::Float(n)) => Ok(Self::Float(m % n)),
+            (m, n) if let Some(v) = big_arith(&m, &n, |a, b| a % b, |a, b| a % b) => Ok(v),
+            _ => Ok(Self::None),
+        }
+    }
+}
+
+// 结构体运算符重载：分派到同名方法
+impl Expression {
+    /// 左操作数为结构体且定义了对应方法时返回结果，否则返回 None。
+    /// `==`/`!=` 也分派到右侧结构体的 eq；字符串与结构体 `+` 拼接时使用其 to_string
+    pub fn overload_binary(
+        &self,
+        op: &str,
+        l: &Expression,
+        r: &Expression,
+        state: &mut State,
+        env: &mut Environment,
+        depth: usize,
+    ) -> Option<Result<Expression, RuntimeError>> {
+        if !matches!(l, Self::Record(..)) && !matches!(r, Self::Record(..)) {
+            return None;
+        }
+        let (method, negate) = match op {
+            "+" => ("add", false),
+            "-" => ("sub", false),
+            "*" => ("mul", false),
+            "/" => ("div", false),
+            "==" => ("eq", false),
+            "!=" => ("eq", true),
+            _ => return None,
+        };
+        let found = match (l, r) {
+            (Self::Record(def, _), _) => {
+                def.methods.get(method).map(|f| (f, [l.clone(), r.clone()]))
+            }
+            // eq 对称，可由右侧结构体处理
+            (_, Self::Record(def, _)) if method == "eq" => {
+                def.methods.get(method).map(|f| (f, [r.clone(), l.clone()]))
+            }
+            _ => None,
+        };
+        let Some((func, args)) = found else {
+            return match (l, r) {
+                (Self::String(a), Self::Record(..)) if op == "+" => Some(
+                    r.display_string(state, env, depth)
+                        .map(|b| Self::String(format!("{a}{b}"))),
+                ),
+                (Self::Record(..), Self::String(b)) if op == "+" => Some(
+                    l.display_string(state, env, depth)
+                        .map(|a| Self::String(format!("{a}{b}"))),
+                ),
+                _ => None,
+            };
+        };
+        let result = self.eval_apply(func, &args, state, env, depth + 1);
+        match negate {
+            true => Some(result.map(|v| Self::Boolean(!v.is_truthy()))),
+            false => Some(result),
+        }
+    }
+
+    /// 输出用的字符串，结构体可重载 to_string
+    pub fn display_string(
+        &self,
+        state: &mut State,
+        env: &mut Environment,
+        depth: usize,
+    ) -> Result<String, RuntimeError> {
+        if let Self::Record(def, _) = self
+            && let Some(func) = def.methods.get("to_string")
+        {
+            let s = self.eval_apply(func, std::slice::from_ref(self), state, env, depth + 1)?;
+            return Ok(s.to_string());
         }
+        Ok(self.to_string())
     }
 }
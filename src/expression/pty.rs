@@ -1,3 +1,4 @@
+use super::cmd_excutor::env_overrides;
 use super::terminal::{TerminalOps, get_terminal_impl};
 use crate::utils::get_current_path;
 use crate::{Environment, RuntimeErrorKind, childman};
@@ -133,6 +134,9 @@ pub fn exec_in_pty(
         }
     }
 
+    let argv: Vec<String> = std::iter::once(cmdstr.clone())
+        .chain(args.iter().flatten().cloned())
+        .collect();
     let mut cmd = CommandBuilder::new(cmdstr);
 
     let current_dir = get_current_path(env);
@@ -145,7 +149,14 @@ pub fn exec_in_pty(
     for (k, v) in env.get_bindings_string() {
         cmd.env(k, v);
     }
+    for (k, v) in env_overrides() {
+        match v {
+            Some(v) => cmd.env(k, v),
+            None => cmd.env_remove(k),
+        }
+    }
 
+    let started = std::time::Instant::now();
     let mut child = pair
         .slave
         .spawn_command(cmd)
@@ -230,8 +241,11 @@ pub fn exec_in_pty(
         }
     });
 
-    child.wait()?;
+    let status = child.wait()?;
     childman::clear_child();
+    let mut info = childman::ExitInfo::new(argv, child.process_id().unwrap_or(0), started);
+    info.code = Some(status.exit_code() as i32);
+    childman::set_last_status(info);
     running.store(true, Ordering::SeqCst);
     let _ = input_thread.join();
     if is_shell || is_vi {
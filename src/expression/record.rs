@@ -0,0 +1,125 @@
+// 用户自定义结构体
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use super::eval::State;
+use super::eval3::{matches_type, prepare_args};
+use super::{DestructurePattern, Expression};
+use crate::{Environment, RuntimeError, RuntimeErrorKind};
+
+/// 结构体定义：字段与关联方法
+#[derive(Debug, PartialEq)]
+pub struct RecordType {
+    pub name: String,
+    pub fields: Vec<(String, Option<Expression>, Option<String>)>, // 字段名、默认值、类型标注
+    pub methods: BTreeMap<String, Expression>,
+}
+
+impl RecordType {
+    /// 按字段顺序构造实例，缺省字段取默认值
+    pub fn construct(
+        self: &Rc<Self>,
+        args: Vec<Expression>,
+        ctx: &Expression,
+        depth: usize,
+    ) -> Result<Expression, RuntimeError> {
+        if args.len() > self.fields.len() {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::TooManyArguments {
+                    name: self.name.clone(),
+                    max: self.fields.len(),
+                    received: args.len(),
+                },
+                ctx.clone(),
+                depth,
+            ));
+        }
+        let received = args.len();
+        let mut values = BTreeMap::new();
+        let mut args = args.into_iter();
+        for (field, default, ty) in self.fields.iter() {
+            let value = match args.next().or_else(|| default.clone()) {
+                Some(v) => v,
+                None => {
+                    return Err(RuntimeError::new(
+                        RuntimeErrorKind::ArgumentMismatch {
+                            name: self.name.clone(),
+                            expected: self.fields.len(),
+                            received,
+                        },
+                        ctx.clone(),
+                        depth,
+                    ));
+                }
+            };
+            if let Some(ty) = ty
+                && !matches_type(&value, ty)
+            {
+                return Err(RuntimeError::new(
+                    RuntimeErrorKind::ArgTypeError {
+                        name: self.name.clone(),
+                        target: format!("field `{field}`"),
+                        expected: ty.clone(),
+                        found: value.type_name(),
+                    },
+                    ctx.clone(),
+                    depth,
+                ));
+            }
+            values.insert(field.clone(), value);
+        }
+        Ok(Expression::Record(self.clone(), Rc::new(values)))
+    }
+
+    /// 成员列表，如 `x: Int = 0`、`fn dist(self, q)`
+    pub fn members(&self) -> Vec<String> {
+        let fields = self.fields.iter().map(|(name, default, ty)| {
+            let mut s = name.clone();
+            if let Some(ty) = ty {
+                s.push_str(&format!(": {ty}"));
+            }
+            if let Some(def) = default {
+                s.push_str(&format!(" = {def}"));
+            }
+            s
+        });
+        let methods = self
+            .methods
+            .iter()
+            .map(|(name, func)| format!("fn {}{}", name, func.fn_signature().unwrap_or_default()));
+        fields.chain(methods).collect()
+    }
+}
+
+/// 首个参数名为 self 的方法需要注入实例
+fn takes_self(func: &Expression) -> bool {
+    matches!(func, Expression::Function(_, params, ..) if params.first().is_some_and(|(p, ..)| matches!(p, DestructurePattern::Identifier(n) if n == "self")))
+}
+
+impl Expression {
+    /// 结构体方法调用：实例方法 p.dist(q)，关联函数 Point.new(1, 2)
+    /// 未找到方法时返回 None
+    pub fn eval_record_method(
+        &self,
+        base: &Expression,
+        method: &str,
+        args: &[Expression],
+        state: &mut State,
+        env: &mut Environment,
+        depth: usize,
+    ) -> Result<Option<Expression>, RuntimeError> {
+        let (def, instance) = match base {
+            Expression::Record(def, _) => (def, Some(base)),
+            Expression::StructDef(def) => (def, None),
+            _ => return Ok(None),
+        };
+        let Some(func) = def.methods.get(method) else {
+            return Ok(None);
+        };
+        let insert = instance.filter(|_| takes_self(func)).cloned();
+        let args = prepare_args(args, insert, env, state, depth)?;
+        self.eval_apply(func, &args, state, env, depth + 1)
+            .map(Some)
+    }
+}
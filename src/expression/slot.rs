@@ -0,0 +1,232 @@
+// 局部变量槽位：同名局部变量共用一个槽位，局部帧按下标存取，免去逐次字符串哈希
+// 解析后由 resolve_slots 把取值位置上的变量引用改写为 LocalVar、把 let/赋值改写为 LocalSet，
+// 求值时直接索引
+//
+// 槽位表按名称全局分配，并非按词法作用域的 (深度, 下标)：函数体沿用调用方的局部帧（动态作用域），
+// 参数在调用期间遮蔽同名槽位；顶层变量存于 Environment，闭包捕获按名称保存
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::{ChainCall, Expression, MatchPattern};
+use crate::DefaultHasher;
+
+#[derive(Default)]
+struct SlotTable {
+    index: HashMap<String, usize, DefaultHasher>,
+    names: Vec<String>,
+}
+
+thread_local! {
+    static SLOTS: RefCell<SlotTable> = RefCell::new(SlotTable::default());
+}
+
+/// 变量名对应的槽位，首次出现时分配
+pub fn slot_of(name: &str) -> usize {
+    SLOTS.with_borrow_mut(|t| match t.index.get(name) {
+        Some(&slot) => slot,
+        None => {
+            let slot = t.names.len();
+            t.names.push(name.to_string());
+            t.index.insert(name.to_string(), slot);
+            slot
+        }
+    })
+}
+
+/// 仅查询，不分配
+pub fn find_slot(name: &str) -> Option<usize> {
+    SLOTS.with_borrow(|t| t.index.get(name).copied())
+}
+
+pub fn slot_name(slot: usize) -> String {
+    SLOTS.with_borrow(|t| t.names.get(slot).cloned().unwrap_or_default())
+}
+
+/// 局部帧：按槽位存值，bound 按绑定顺序记录槽位，供块退出时撤销
+#[derive(Debug, Clone, Default)]
+pub struct Frame {
+    values: Vec<Option<Expression>>,
+    bound: Vec<usize>,
+}
+
+impl Frame {
+    #[inline]
+    pub fn get(&self, slot: usize) -> Option<&Expression> {
+        self.values.get(slot).and_then(Option::as_ref)
+    }
+
+    #[inline]
+    pub fn set(&mut self, slot: usize, value: Expression) {
+        if slot >= self.values.len() {
+            self.values.resize(slot + 1, None);
+        }
+        if self.values[slot].replace(value).is_none() {
+            self.bound.push(slot);
+        }
+    }
+
+    pub fn remove(&mut self, slot: usize) {
+        if let Some(v) = self.values.get_mut(slot) {
+            *v = None;
+        }
+    }
+
+    /// 当前绑定位置，配合 truncate 丢弃其后新增的变量
+    #[inline]
+    pub fn mark(&self) -> usize {
+        self.bound.len()
+    }
+
+    pub fn truncate(&mut self, mark: usize) {
+        for slot in self.bound.drain(mark..) {
+            self.values[slot] = None;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+}
+
+/// 可直接按槽位读取的运算符操作数；`<` `>` 左侧的裸符号可能是命令重定向，不改写
+fn rewrites_symbols(op: &str) -> bool {
+    matches!(
+        op,
+        "+" | "-" | "*" | "/" | "%" | "==" | "!=" | "===" | "!==" | ">=" | "<=" | "&&" | "||"
+    )
+}
+
+impl Expression {
+    /// 将取值位置上的变量引用改写为槽位引用
+    /// 只改写求值时按普通变量处理的位置，命令参数、函数位置等保持原样
+    pub fn resolve_slots(&self) -> Self {
+        let rc = |e: &Rc<Self>| Rc::new(e.resolve_slots());
+        let all = |v: &[Self]| v.iter().map(Self::resolve_slots).collect::<Vec<_>>();
+        match self {
+            Self::BinaryOp(op, l, r) => {
+                let operand = |e: &Rc<Self>, sym: bool| match e.as_ref() {
+                    Self::Variable(name) => {
+                        Rc::new(Self::LocalVar(slot_of(name), name.clone(), false))
+                    }
+                    Self::Symbol(name) if sym => {
+                        Rc::new(Self::LocalVar(slot_of(name), name.clone(), true))
+                    }
+                    _ => rc(e),
+                };
+                match op.as_str() {
+                    // 复合赋值左侧是变量名
+                    "+=" | "-=" | "*=" | "/=" => Self::BinaryOp(op.clone(), l.clone(), rc(r)),
+                    "<" | ">" => Self::BinaryOp(op.clone(), operand(l, false), operand(r, true)),
+                    op if rewrites_symbols(op) => {
+                        Self::BinaryOp(op.to_string(), operand(l, true), operand(r, true))
+                    }
+                    _ => Self::BinaryOp(op.clone(), rc(l), rc(r)),
+                }
+            }
+            Self::Declare(name, e) => Self::LocalSet(
+                slot_of(name),
+                name.clone(),
+                true,
+                Rc::new(e.resolve_value()),
+            ),
+            Self::Assign(name, e) => Self::LocalSet(
+                slot_of(name),
+                name.clone(),
+                false,
+                Rc::new(e.resolve_value()),
+            ),
+            Self::SetParent(name, e) => Self::SetParent(name.clone(), Rc::new(e.resolve_value())),
+            Self::If(c, t, e) => Self::If(Rc::new(c.resolve_value()), rc(t), rc(e)),
+            Self::While(c, body) => Self::While(Rc::new(c.resolve_value()), rc(body)),
+            Self::Return(e) => Self::Return(Rc::new(e.resolve_value())),
+            Self::Break(e) => Self::Break(Rc::new(e.resolve_value())),
+            Self::StringTemplate(parts) => {
+                Self::StringTemplate(parts.iter().map(Self::resolve_value).collect())
+            }
+
+            // 以下只向下遍历
+            Self::Group(e) => Self::Group(rc(e)),
+            Self::UnaryOp(op, e, prefix) => Self::UnaryOp(op.clone(), rc(e), *prefix),
+            Self::RangeOp(op, l, r, step) => {
+                Self::RangeOp(op.clone(), rc(l), rc(r), step.as_ref().map(rc))
+            }
+            Self::Pipe(op, l, r) => Self::Pipe(op.clone(), rc(l), rc(r)),
+            Self::List(v) => Self::List(Rc::new(all(v))),
+            Self::Index(a, b) => Self::Index(rc(a), rc(b)),
+            Self::For(var, index, iter, body) => {
+                Self::For(var.clone(), index.clone(), rc(iter), rc(body))
+            }
+            Self::Loop(body) => Self::Loop(rc(body)),
+            Self::Match(value, arms) => Self::Match(
+                rc(value),
+                Rc::new(
+                    arms.iter()
+                        .map(|(pats, body)| {
+                            let pats = pats.iter().map(MatchPattern::resolve_slots).collect();
+                            (pats, body.resolve_slots())
+                        })
+                        .collect(),
+                ),
+            ),
+            Self::Apply(f, args) => Self::Apply(f.clone(), Rc::new(all(args))),
+            Self::Command(f, args) => Self::Command(f.clone(), Rc::new(all(args))),
+            Self::Lambda(params, body, captured) => {
+                Self::Lambda(params.clone(), rc(body), captured.clone())
+            }
+            Self::Function(name, params, rest, body, decos, ret) => Self::Function(
+                name.clone(),
+                params.clone(),
+                rest.clone(),
+                rc(body),
+                decos.clone(),
+                ret.clone(),
+            ),
+            Self::Sequence(v) => Self::Sequence(all(v)),
+            Self::Block(v) => Self::Block(Rc::new(all(v))),
+            Self::Catch(body, ty, handler) => {
+                Self::Catch(rc(body), ty.clone(), handler.as_ref().map(rc))
+            }
+            Self::Try(body, var, catch, finally) => Self::Try(
+                rc(body),
+                var.clone(),
+                catch.as_ref().map(rc),
+                finally.as_ref().map(rc),
+            ),
+            Self::Defer(e) => Self::Defer(rc(e)),
+            Self::Chain(base, calls) => Self::Chain(
+                rc(base),
+                calls
+                    .iter()
+                    .map(|c| ChainCall {
+                        method: c.method.clone(),
+                        args: all(&c.args),
+                    })
+                    .collect(),
+            ),
+            Self::DestructureAssign(pats, e) => Self::DestructureAssign(pats.clone(), rc(e)),
+            other => other.clone(),
+        }
+    }
+
+    /// 整体处于取值位置的表达式
+    fn resolve_value(&self) -> Self {
+        match self {
+            Self::Variable(name) => Self::LocalVar(slot_of(name), name.clone(), false),
+            Self::Symbol(name) => Self::LocalVar(slot_of(name), name.clone(), true),
+            other => other.resolve_slots(),
+        }
+    }
+}
+
+impl MatchPattern {
+    fn resolve_slots(&self) -> Self {
+        match self {
+            Self::Guard(p, cond) => {
+                Self::Guard(Box::new(p.resolve_slots()), Rc::new(cond.resolve_value()))
+            }
+            other => other.clone(),
+        }
+    }
+}
@@ -55,8 +55,10 @@ pub mod editor;
 pub mod utils;
 // pub use utils::abs;
 // pub use utils::canon;
+pub mod ast_cache;
 pub mod completion;
 pub mod modman;
+pub mod profiler;
 // pub mod excutor;
 pub mod libs;
 
@@ -76,6 +78,9 @@ thread_local! {
     static PRINT_DIRECT: RefCell<bool> = const {RefCell::new(true)};
     static CFM_ENABLED: RefCell<bool> = const {RefCell::new(false)};
     static STRICT_ENABLED: RefCell<bool> = const {RefCell::new(false)};
+    static PIPEFAIL_ENABLED: RefCell<bool> = const {RefCell::new(false)};
+    // 测试中默认关闭，避免写入用户的缓存目录
+    static AST_CACHE_ENABLED: RefCell<bool> = const {RefCell::new(!cfg!(test))};
     static MAX_RUNTIME_RECURSION: RefCell<usize> = const {RefCell::new(800)};
     static MAX_SYNTAX_RECURSION: RefCell<usize> = const {RefCell::new(100)};
     static MAX_USEMODE_RECURSION: RefCell<usize> = const {RefCell::new(100)};
@@ -100,3 +105,19 @@ pub fn set_cfm_enabled(value: bool) {
 pub fn set_strict_enabled(value: bool) {
     STRICT_ENABLED.with(|v| *v.borrow_mut() = value);
 }
+
+pub fn with_pipefail_enabled<R>(f: impl FnOnce(bool) -> R) -> R {
+    PIPEFAIL_ENABLED.with(|v| f(*v.borrow()))
+}
+
+pub fn set_pipefail_enabled(value: bool) {
+    PIPEFAIL_ENABLED.with(|v| *v.borrow_mut() = value);
+}
+
+pub fn with_ast_cache_enabled<R>(f: impl FnOnce(bool) -> R) -> R {
+    AST_CACHE_ENABLED.with(|v| f(*v.borrow()))
+}
+
+pub fn set_ast_cache_enabled(value: bool) {
+    AST_CACHE_ENABLED.with(|v| *v.borrow_mut() = value);
+}
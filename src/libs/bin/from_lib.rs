@@ -5,7 +5,7 @@ use crate::{
     libs::{
         BuiltinInfo,
         bin::into_lib,
-        helper::{check_exact_args_len, get_string_ref},
+        helper::{check_exact_args_len, convert_list_map_to_table, get_string_ref},
         lazy_module::LazyModule,
     },
     parse, reg_info, reg_lazy,
@@ -13,12 +13,13 @@ use crate::{
 use regex_lite::Regex;
 use std::{collections::BTreeMap, sync::OnceLock};
 use tinyjson::JsonValue;
+use yaml_rust2::{Yaml, YamlLoader, yaml::Hash};
 static SELECT_RE: OnceLock<Regex> = OnceLock::new();
 
 pub fn regist_lazy() -> LazyModule {
     reg_lazy!({
         // 数据格式解析
-        toml, json, csv,
+        toml, json, csv, yaml,
         // 表达式解析
         script,
         // 解析第三方命令输出（into库）
@@ -35,6 +36,7 @@ pub fn regist_info() -> BTreeMap<&'static str, BuiltinInfo> {
         toml => "parse TOML into lumesh expression", "<toml_string>"
         json => "parse JSON into lumesh expression", "<json_string>"
         csv => "parse CSV into lumesh expression", "<csv_string>"
+        yaml => "parse YAML into lumesh expression, multi-document streams as a list", "<yaml_string>"
 
         // 表达式解析
         script => "parse script str to lumesh expression", "<script_string>"
@@ -230,6 +232,93 @@ fn try_convert_array_to_table(arr: &[JsonValue]) -> Option<(Vec<String>, Vec<Vec
     Some((headers, rows))
 }
 
+// YAML Parser Functions
+fn yaml(
+    args: Vec<Expression>,
+    _env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_exact_args_len("yaml", &args, 1, ctx)?;
+    let text_str = get_string_ref(&args[0], ctx)?;
+
+    // 锚点与别名在加载时已展开
+    let mut docs = YamlLoader::load_from_str(text_str).map_err(|e| {
+        RuntimeError::common(format!("Yaml parser error:\n{e}").into(), ctx.clone(), 0)
+    })?;
+    // 多文档流转为列表，忽略分隔符间的空文档（如结尾的 `---`）
+    if docs.len() > 1 {
+        docs.retain(|d| !matches!(d, Yaml::Null));
+    }
+    Ok(match docs.len() {
+        0 => Expression::None,
+        1 => yaml_to_expr(docs.pop().unwrap()),
+        _ => yaml_seq_to_expr(docs),
+    })
+}
+
+fn yaml_to_expr(val: Yaml) -> Expression {
+    match val {
+        Yaml::Null | Yaml::BadValue | Yaml::Alias(_) => Expression::None,
+        Yaml::Boolean(b) => Expression::Boolean(b),
+        Yaml::Integer(i) => Expression::Integer(i),
+        // 超出 i64 的整数也以 Real 保存
+        Yaml::Real(s) => match Expression::parse_int(&s) {
+            Some(i) => i,
+            None => Yaml::Real(s)
+                .as_f64()
+                .map_or(Expression::None, Expression::Float),
+        },
+        Yaml::String(s) => Expression::String(s),
+        Yaml::Array(a) => yaml_seq_to_expr(a),
+        Yaml::Hash(h) => Expression::from(yaml_map(h)),
+    }
+}
+
+// 元素全为映射的序列转为表格
+fn yaml_seq_to_expr(arr: Vec<Yaml>) -> Expression {
+    let items = arr.into_iter().map(yaml_to_expr).collect::<Vec<_>>();
+    if !items.is_empty() && items.iter().all(|v| matches!(v, Expression::Map(_))) {
+        return Expression::Table(convert_list_map_to_table(&items));
+    }
+    Expression::from(items)
+}
+
+fn yaml_map(hash: Hash) -> BTreeMap<String, Expression> {
+    let mut map = BTreeMap::new();
+    let mut merges = vec![];
+    for (k, v) in hash {
+        match k {
+            Yaml::String(k) if k == "<<" => merges.push(v),
+            k => {
+                map.insert(yaml_key(k), yaml_to_expr(v));
+            }
+        }
+    }
+    // 合并键 `<<: *base` / `<<: [*a, *b]`：显式写出的键优先，靠前的来源优先
+    for v in merges {
+        let sources = match v {
+            Yaml::Array(a) => a,
+            v => vec![v],
+        };
+        for source in sources {
+            if let Yaml::Hash(h) = source {
+                for (k, v) in yaml_map(h) {
+                    map.entry(k).or_insert(v);
+                }
+            }
+        }
+    }
+    map
+}
+
+fn yaml_key(key: Yaml) -> String {
+    match key {
+        Yaml::String(s) | Yaml::Real(s) => s,
+        Yaml::Null => "null".to_string(),
+        k => yaml_to_expr(k).to_string(),
+    }
+}
+
 // Expression Parser
 fn script(
     args: Vec<Expression>,
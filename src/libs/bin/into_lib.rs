@@ -1,6 +1,7 @@
 use std::collections::BTreeMap;
 
 use regex_lite::Regex;
+use yaml_rust2::{Yaml, YamlEmitter, yaml::Hash};
 
 use crate::{
     Environment, Expression, Int, RuntimeError, RuntimeErrorKind,
@@ -27,7 +28,7 @@ pub fn regist_lazy() -> LazyModule {
         time,
         table,
         // 数据格式序列化
-        toml, json, csv,
+        toml, json, csv, yaml,
         highlighted, striped,
     })
 }
@@ -45,6 +46,7 @@ pub fn regist_info() -> BTreeMap<&'static str, BuiltinInfo> {
         toml => "serialize lumesh expression to TOML", "<expr>"
         json => "serialize lumesh expression to JSON", "<expr>"
         csv => "serialize lumesh expression to CSV", "<expr>"
+        yaml => "serialize lumesh expression to YAML", "<expr>"
         highlighted => "highlight script str with ANSI", "<script_string>"
         striped => "remove all ANSI escape codes from string", "<string>"
     })
@@ -232,13 +234,13 @@ pub fn int(
 ) -> Result<Expression, RuntimeError> {
     check_exact_args_len("int", &args, 1, ctx)?;
     match &args[0] {
-        Expression::Integer(x) => Ok(Expression::Integer(*x)),
+        Expression::Integer(_) | Expression::BigInt(_) => Ok(args[0].clone()),
         Expression::Float(x) => Ok(Expression::Integer(*x as Int)),
         // [FIX] 新增 Boolean 处理
         Expression::Boolean(b) => Ok(Expression::Integer(if *b { 1 } else { 0 })),
         Expression::String(x) => {
-            if let Ok(n) = x.parse::<Int>() {
-                Ok(Expression::Integer(n))
+            if let Some(n) = Expression::parse_int(x) {
+                Ok(n)
             } else {
                 Err(RuntimeError::common(
                     format!("could not convert {x:?} to an integer").into(),
@@ -262,8 +264,9 @@ pub fn float(
 ) -> Result<Expression, RuntimeError> {
     check_exact_args_len("float", &args, 1, ctx)?;
     match &args[0] {
-        Expression::Integer(x) => Ok(Expression::Float(*x as f64)),
-        Expression::Float(x) => Ok(Expression::Float(*x)),
+        Expression::Integer(_) | Expression::BigInt(_) | Expression::Float(_) => {
+            Ok(Expression::Float(args[0].as_f64().unwrap_or_default()))
+        }
         // [FIX] 新增 Boolean 处理
         Expression::Boolean(b) => Ok(Expression::Float(if *b { 1.0 } else { 0.0 })),
         Expression::String(x) => {
@@ -412,7 +415,7 @@ fn expr_to_toml_string(expr: &Expression, table_prefix: Option<&str>) -> String
     match expr {
         Expression::None => "".to_string(),
         Expression::Boolean(b) => b.to_string(),
-        Expression::Integer(i) => i.to_string(),
+        Expression::Integer(_) | Expression::BigInt(_) => expr.to_string(),
         Expression::Float(f) => {
             if f.is_infinite() {
                 if f.is_sign_positive() {
@@ -583,7 +586,7 @@ fn expr_to_json_string(expr: &Expression) -> String {
     match expr {
         Expression::None => "null".to_string(),
         Expression::Boolean(b) => b.to_string(),
-        Expression::Integer(i) => i.to_string(),
+        Expression::Integer(_) | Expression::BigInt(_) => expr.to_string(),
         Expression::Float(f) => {
             if f.is_infinite() || f.is_nan() {
                 "null".to_string()
@@ -642,6 +645,66 @@ fn expr_to_json_string(expr: &Expression) -> String {
     }
 }
 
+pub fn yaml(
+    args: Vec<Expression>,
+    _env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_exact_args_len("yaml", &args, 1, ctx)?;
+    let mut out = String::new();
+    YamlEmitter::new(&mut out)
+        .dump(&expr_to_yaml(&args[0]))
+        .map_err(|e| {
+            RuntimeError::common(format!("YAML write failed: {e}").into(), ctx.clone(), 0)
+        })?;
+    // 单个文档，去掉开头的 `---`
+    let body = out.strip_prefix("---").unwrap_or(&out);
+    Ok(Expression::String(
+        body.trim_start_matches([' ', '\n']).to_string(),
+    ))
+}
+
+fn expr_to_yaml(expr: &Expression) -> Yaml {
+    let map_to_yaml = |pairs: &mut dyn Iterator<Item = (&String, &Expression)>| {
+        Yaml::Hash(
+            pairs
+                .map(|(k, v)| (Yaml::String(k.clone()), expr_to_yaml(v)))
+                .collect::<Hash>(),
+        )
+    };
+    match expr {
+        Expression::None => Yaml::Null,
+        Expression::Boolean(b) => Yaml::Boolean(*b),
+        Expression::Integer(i) => Yaml::Integer(*i),
+        // Real 原样输出，大整数按数字写出
+        Expression::BigInt(_) => Yaml::Real(expr.to_string()),
+        Expression::Float(f) => Yaml::Real(match f {
+            f if f.is_nan() => ".nan".to_string(),
+            f if f.is_infinite() && *f > 0.0 => ".inf".to_string(),
+            f if f.is_infinite() => "-.inf".to_string(),
+            // Debug 格式保留 `.0`，读回时仍为浮点
+            f => format!("{f:?}"),
+        }),
+        Expression::DateTime(dt) => Yaml::String(dt.format("%Y-%m-%dT%H:%M:%S%.fZ").to_string()),
+        Expression::String(s) => Yaml::String(s.clone()),
+        Expression::List(list) => Yaml::Array(list.iter().map(expr_to_yaml).collect()),
+        Expression::BSet(set) => Yaml::Array(set.iter().map(expr_to_yaml).collect()),
+        Expression::Map(map) => map_to_yaml(&mut map.iter()),
+        // HMap 无序，按键排序后输出
+        Expression::HMap(map) => {
+            map_to_yaml(&mut map.iter().collect::<BTreeMap<_, _>>().into_iter())
+        }
+        Expression::Table(table_data) => Yaml::Array(
+            table_data
+                .rows()
+                .iter()
+                .map(|row| map_to_yaml(&mut table_data.headers().iter().zip(row.iter())))
+                .collect(),
+        ),
+        other => Yaml::String(other.to_string()),
+    }
+}
+
 pub fn csv(
     mut args: Vec<Expression>,
     env: &mut Environment,
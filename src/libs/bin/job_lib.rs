@@ -0,0 +1,284 @@
+//! 作业控制相关的顶层函数：jobs/fg/bg/wait/kill/disown
+
+use std::collections::BTreeMap;
+
+use crate::{
+    Environment, Expression, Int, RuntimeError, RuntimeErrorKind,
+    childman::{self, Job},
+    expression::cmd_excutor::run_captured,
+    libs::helper::check_args_len,
+};
+
+/// 参数中的作业标识，缺省为空（当前作业）
+fn job_spec(args: &[Expression]) -> String {
+    args.first().map_or(String::new(), |a| a.to_string())
+}
+
+fn get_job(spec: &str, ctx: &Expression) -> Result<Job, RuntimeError> {
+    childman::find_job(spec).ok_or_else(|| {
+        let spec = if spec.is_empty() { "current" } else { spec };
+        RuntimeError::common(format!("no such job: {spec}").into(), ctx.clone(), 0)
+    })
+}
+
+fn job_io_error(e: std::io::Error, job: &Job, ctx: &Expression) -> RuntimeError {
+    RuntimeError::from_io_error(
+        e,
+        format!("job [{}] `{}`", job.id, job.cmd).into(),
+        ctx.clone(),
+        0,
+    )
+}
+
+pub fn jobs(
+    _args: Vec<Expression>,
+    _env: &mut Environment,
+    _ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    let list = childman::list_jobs()
+        .into_iter()
+        .map(|job| {
+            let mut m = BTreeMap::new();
+            m.insert("id".to_string(), Expression::Integer(job.id as Int));
+            m.insert("pid".to_string(), Expression::Integer(job.pid as Int));
+            m.insert("pgid".to_string(), Expression::Integer(job.pgid as Int));
+            m.insert("state".to_string(), Expression::String(job.state_str()));
+            m.insert("cmd".to_string(), Expression::String(job.cmd));
+            Expression::from(m)
+        })
+        .collect::<Vec<_>>();
+    Ok(Expression::from(list))
+}
+
+pub fn fg(
+    args: Vec<Expression>,
+    _env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_args_len("fg", &args, 0..=1, ctx)?;
+    let job = get_job(&job_spec(&args), ctx)?;
+    eprintln!("{}", job.cmd);
+    #[cfg(unix)]
+    {
+        let started = std::time::Instant::now();
+        match childman::continue_job(&job, true) {
+            Ok(Some(status)) => {
+                let info = childman::ExitInfo::new(vec![job.cmd.clone()], job.pid, started)
+                    .with_status(&status);
+                childman::set_last_status(info.clone());
+                match status.success() {
+                    true => Ok(Expression::None),
+                    false => Err(RuntimeError::new(
+                        RuntimeErrorKind::CommandExited {
+                            cmd: job.cmd,
+                            msg: status.to_string(),
+                            code: info.code,
+                            signal: info.signal,
+                        },
+                        ctx.clone(),
+                        0,
+                    )),
+                }
+            }
+            Ok(None) => Ok(Expression::None),
+            Err(e) => Err(job_io_error(e, &job, ctx)),
+        }
+    }
+    #[cfg(windows)]
+    Err(job_io_error(
+        std::io::ErrorKind::Unsupported.into(),
+        &job,
+        ctx,
+    ))
+}
+
+pub fn bg(
+    args: Vec<Expression>,
+    _env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_args_len("bg", &args, 0..=1, ctx)?;
+    let job = get_job(&job_spec(&args), ctx)?;
+    #[cfg(unix)]
+    {
+        childman::continue_job(&job, false).map_err(|e| job_io_error(e, &job, ctx))?;
+        eprintln!("[{}]+ {} &", job.id, job.cmd);
+        Ok(Expression::None)
+    }
+    #[cfg(windows)]
+    Err(job_io_error(
+        std::io::ErrorKind::Unsupported.into(),
+        &job,
+        ctx,
+    ))
+}
+
+/// 等待指定作业（默认全部）或子进程结束，返回最后一个的退出码
+pub fn wait(
+    args: Vec<Expression>,
+    _env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    #[cfg(unix)]
+    {
+        let targets = match args.is_empty() {
+            true => childman::list_jobs().into_iter().map(Ok).collect(),
+            false => args
+                .iter()
+                .map(|a| wait_target(a, ctx))
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+        let mut code = 0;
+        for target in targets {
+            let status = match target {
+                Ok(job) => childman::wait_job(&job),
+                // 不在作业表中的子进程
+                Err(pid) => childman::wait_pid(pid).map_err(|e| {
+                    RuntimeError::from_io_error(e, format!("wait {pid}").into(), ctx.clone(), 0)
+                })?,
+            };
+            code = match status {
+                Some(status) if libc::WIFSIGNALED(status) => 128 + libc::WTERMSIG(status),
+                Some(status) => libc::WEXITSTATUS(status),
+                // 被 Ctrl-C 打断
+                None => {
+                    return Err(RuntimeError::new(
+                        RuntimeErrorKind::Terminated,
+                        ctx.clone(),
+                        0,
+                    ));
+                }
+            };
+        }
+        match args.is_empty() {
+            true => Ok(Expression::None),
+            false => Ok(Expression::Integer(code as Int)),
+        }
+    }
+    #[cfg(windows)]
+    match args.first() {
+        Some(spec) => Err(job_io_error(
+            std::io::ErrorKind::Unsupported.into(),
+            &get_job(&spec.to_string(), ctx)?,
+            ctx,
+        )),
+        None => Ok(Expression::None),
+    }
+}
+
+/// wait 的参数：作业，或作业表外的子进程 pid
+#[cfg(unix)]
+fn wait_target(arg: &Expression, ctx: &Expression) -> Result<Result<Job, u32>, RuntimeError> {
+    let spec = arg.to_string();
+    match childman::find_job(&spec) {
+        Some(job) => Ok(Ok(job)),
+        None => match spec.parse::<u32>() {
+            Ok(pid) if !spec.starts_with('%') => Ok(Err(pid)),
+            _ => get_job(&spec, ctx).map(Ok),
+        },
+    }
+}
+
+/// 解析信号：`-9` `-KILL` `-SIGKILL`，或 `-s` 之后的名称
+#[cfg(unix)]
+fn parse_signal(name: &str) -> Option<i32> {
+    use std::str::FromStr;
+    if let Ok(n) = name.parse::<i32>() {
+        return Some(n);
+    }
+    let upper = name.to_uppercase();
+    let full = match upper.starts_with("SIG") {
+        true => upper,
+        false => format!("SIG{upper}"),
+    };
+    nix::sys::signal::Signal::from_str(&full)
+        .map(|s| s as i32)
+        .ok()
+}
+
+/// 拆分 kill 参数为信号与目标（作业或 pid）；其他写法返回 None
+#[cfg(unix)]
+fn parse_kill_args(argv: &[String]) -> Option<(i32, &[String])> {
+    let (sig, targets) = match argv.first()?.as_str() {
+        "-s" => (parse_signal(argv.get(1)?)?, &argv[2..]),
+        s if s.starts_with('-') => (parse_signal(&s[1..])?, &argv[1..]),
+        _ => (libc::SIGTERM, argv),
+    };
+    let valid = |t: &String| t.starts_with('%') || t.parse::<i32>().is_ok();
+    (!targets.is_empty() && targets.iter().all(valid)).then_some((sig, targets))
+}
+
+/// 向作业的进程组或指定进程发送信号，默认 SIGTERM。
+/// `kill -l` 等其他写法交给外部 kill 命令。
+pub fn kill(
+    args: Vec<Expression>,
+    env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_args_len("kill", &args, 1.., ctx)?;
+    let argv = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+    #[cfg(unix)]
+    if let Some((sig, targets)) = parse_kill_args(&argv) {
+        for target in targets {
+            if target.starts_with('%') {
+                let job = get_job(target, ctx)?;
+                childman::signal_job(&job, sig).map_err(|e| job_io_error(e, &job, ctx))?;
+                // 被停止的作业需继续运行才能处理信号
+                if sig == libc::SIGTERM || sig == libc::SIGHUP {
+                    let _ = childman::signal_job(&job, libc::SIGCONT);
+                }
+                continue;
+            }
+            let pid = target.parse::<i32>().unwrap_or_default();
+            if unsafe { libc::kill(pid, sig) } != 0 {
+                return Err(RuntimeError::from_io_error(
+                    std::io::Error::last_os_error(),
+                    format!("kill {pid}").into(),
+                    ctx.clone(),
+                    0,
+                ));
+            }
+        }
+        return Ok(Expression::None);
+    }
+    external_kill(argv, env, ctx)
+}
+
+/// 运行外部 kill 命令，标准输出作为返回值
+fn external_kill(
+    argv: Vec<String>,
+    env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    use std::io::Write;
+    let out = run_captured(ctx, &"kill".to_string(), argv.clone(), env, 0)?;
+    let _ = std::io::stderr().write_all(&out.stderr);
+    match out.info.code {
+        Some(0) if out.stdout.is_empty() => Ok(Expression::None),
+        Some(0) => Ok(Expression::String(
+            String::from_utf8_lossy(&out.stdout).into_owned(),
+        )),
+        code => Err(RuntimeError::new(
+            RuntimeErrorKind::CommandExited {
+                cmd: format!("kill {}", argv.join(" ")),
+                msg: String::from_utf8_lossy(&out.stderr).trim().to_string(),
+                code,
+                signal: out.info.signal,
+            },
+            ctx.clone(),
+            0,
+        )),
+    }
+}
+
+/// 将作业移出作业表，不再跟踪与报告
+pub fn disown(
+    args: Vec<Expression>,
+    _env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_args_len("disown", &args, 0..=1, ctx)?;
+    let job = get_job(&job_spec(&args), ctx)?;
+    childman::remove_job(job.id);
+    Ok(Expression::None)
+}
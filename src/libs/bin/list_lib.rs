@@ -2,8 +2,10 @@
 use std::rc::Rc;
 
 use crate::eval::State;
-use crate::expression::BoxedIterator;
+use crate::expression::catcher::error_map;
+use crate::expression::cmd_excutor::{prepare_par_cmd, run_parallel};
 use crate::expression::eval2::execute_iteration;
+use crate::expression::{BoxedIterator, DestructurePattern};
 use crate::libs::bin::{math_lib, top};
 use crate::libs::helper::{
     check_args_len, check_exact_args_len, check_fn_arg, get_integer_arg, get_integer_ref,
@@ -34,7 +36,7 @@ pub fn regist_lazy() -> LazyModule {
         //创建操作
         concat,from,
         //遍历操作
-        map,items,filter,filter_map,any,all,
+        map,par_map,items,filter,filter_map,any,all,
         //转换操作
         join,to_map,to_hmap,to_set,
         //结构操作
@@ -87,6 +89,7 @@ pub fn regist_info() -> BTreeMap<&'static str, BuiltinInfo> {
 
         // 遍历操作
         map => "apply function for each element", "<list> <fn>"
+        par_map => "map with external commands run in parallel, failed items become error maps", "<list> <fn> [jobs]"
         items => "iterate over index-value pairs", "<list>"
         filter => "filter elements by condition", "<list> <fn>"
         filter_map => "filter and map in one pass", "<list> <fn>"
@@ -849,17 +852,39 @@ fn map(
     let func = it.next().unwrap();
     let list = get_list_ref(&list_exp, ctx)?;
 
+    // 解构参数不能作为循环变量，逐项调用函数
+    if let Expression::Function(_, params, ..) = &func
+        && (1..=2).contains(&params.len())
+        && params
+            .iter()
+            .any(|(p, ..)| !matches!(p, DestructurePattern::Identifier(_)))
+    {
+        let mut state = State::new();
+        let mut result = Vec::with_capacity(list.len());
+        for (i, item) in list.iter().enumerate() {
+            let args = match params.len() {
+                2 => vec![Expression::Integer(i as Int), item.clone()],
+                _ => vec![item.clone()],
+            };
+            result.push(func.eval_apply(&func, &args, &mut state, env, 0)?);
+        }
+        return Ok(Expression::from(result));
+    }
+
     let (var_name, ind_name, body) = if check_fn_arg(&func, 2, ctx).is_ok() {
         match func {
-            Expression::Function(_, mut p, _, body, _) => {
-                (p.pop().unwrap().0, Some(p.pop().unwrap().0), body)
+            Expression::Function(_, mut p, _, body, ..) => {
+                let var = p.pop().unwrap().0.to_string();
+                (var, Some(p.pop().unwrap().0.to_string()), body)
             }
             Expression::Lambda(mut p, body, _) => (p.pop().unwrap(), Some(p.pop().unwrap()), body),
             _ => unreachable!(),
         }
     } else if check_fn_arg(&func, 1, ctx).is_ok() {
         match func {
-            Expression::Function(_, mut p, _, body, _) => (p.pop().unwrap().0, None, body),
+            Expression::Function(_, mut p, _, body, ..) => {
+                (p.pop().unwrap().0.to_string(), None, body)
+            }
             Expression::Lambda(mut p, body, _) => (p.pop().unwrap(), None, body),
             _ => unreachable!(),
         }
@@ -894,6 +919,71 @@ fn map(
     )
 }
 
+/// 并行 map：函数体为外部命令时，各项参数依次求值后以至多 jobs 个子进程并行运行；
+/// 其余函数体顺序求值。结果按输入顺序返回，失败的项以错误信息 map 代替
+fn par_map(
+    args: Vec<Expression>,
+    env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_args_len("par_map", &args, 2..=3, ctx)?;
+    let list = get_list_ref(&args[0], ctx)?;
+    let func = &args[1];
+    check_fn_arg(func, 1, ctx)?;
+    let jobs = match args.get(2) {
+        Some(n) => get_integer_ref(n, ctx)?.max(1) as usize,
+        None => std::thread::available_parallelism().map_or(4, |n| n.get()),
+    };
+    let (param, body) = match func {
+        Expression::Function(_, p, _, body, ..) => match &p[0].0 {
+            DestructurePattern::Identifier(n) => (Some(n), body),
+            _ => (None, body),
+        },
+        Expression::Lambda(p, body, _) => (Some(&p[0]), body),
+        _ => unreachable!(),
+    };
+    let body = match body.as_ref() {
+        Expression::Block(exprs) if exprs.len() == 1 => &exprs[0],
+        other => other,
+    };
+
+    let mut state = State::new();
+    state.set(State::IN_ASSIGN);
+    let mut slots = Vec::with_capacity(list.len());
+    let mut cmds = vec![];
+    for item in list.iter() {
+        let mut item_env = env.fork();
+        // 解构参数的函数不并行，按普通调用求值
+        let prepared = match param {
+            Some(param) => {
+                item_env.define(param, item.clone());
+                prepare_par_cmd(body, &mut state, &mut item_env, 0)
+            }
+            None => None,
+        };
+        match prepared {
+            Some(Ok(cmd)) => {
+                cmds.push(cmd);
+                slots.push(None);
+            }
+            Some(Err(e)) => slots.push(Some(Err(e))),
+            None => slots.push(Some(
+                func.apply(vec![item.clone()]).eval_mut(&mut state, env, 0),
+            )),
+        }
+    }
+    let mut outputs = run_parallel(cmds, jobs, 0).into_iter();
+    let results = slots
+        .into_iter()
+        .map(|slot| match slot.or_else(|| outputs.next()) {
+            Some(Ok(v)) => v,
+            Some(Err(e)) => error_map(&e),
+            None => Expression::None,
+        })
+        .collect::<Vec<_>>();
+    Ok(Expression::from(results))
+}
+
 fn items(
     args: Vec<Expression>,
     _env: &mut Environment,
@@ -924,7 +1014,7 @@ fn filter(
     let mut result = Vec::new();
     let fn_arg_count = match &target {
         Expression::Lambda(params, ..) => params.len(),
-        Expression::Function(_, params, _, _, _) => params.len(),
+        Expression::Function(_, params, ..) => params.len(),
         _ => {
             return Err(RuntimeError::common(
                 "expected a func/lambda as filter-function".into(),
@@ -1,8 +1,11 @@
+use crate::expression::bignum::{MAX_BIGINT_BITS, int_pow};
 use crate::libs::BuiltinInfo;
 use crate::libs::bin::into_lib::str as to_str;
-use crate::libs::helper::{check_args_len, check_exact_args_len, get_integer_ref};
+use crate::libs::helper::{check_args_len, check_exact_args_len, get_bigint_ref, get_integer_ref};
 use crate::libs::lazy_module::LazyModule;
 use crate::{Environment, Expression, Int, RuntimeError, RuntimeErrorKind, reg_info, reg_lazy};
+use num_bigint::BigInt;
+use num_traits::Signed;
 use std::collections::BTreeMap;
 
 pub fn handle_math(arg: &str, ctx: &Expression) -> Result<Expression, RuntimeError> {
@@ -134,14 +137,14 @@ fn eval_to_f64(
     ctx: &Expression,
 ) -> Result<Vec<f64>, RuntimeError> {
     args.iter()
-        .map(|arg| match arg {
-            Expression::Integer(i) => Ok(*i as f64),
-            Expression::Float(f) => Ok(*f),
-            e => Err(RuntimeError::common(
-                format!("invalid {func_name} argument {e}").into(),
-                ctx.clone(),
-                0,
-            )),
+        .map(|arg| {
+            arg.as_f64().ok_or_else(|| {
+                RuntimeError::common(
+                    format!("invalid {func_name} argument {arg}").into(),
+                    ctx.clone(),
+                    0,
+                )
+            })
         })
         .collect()
 }
@@ -149,14 +152,13 @@ fn eval_to_f64(
 // Helper function to collect arguments (used by max/min)
 
 pub fn get_float_arg(expr: &Expression, ctx: &Expression) -> Result<f64, RuntimeError> {
-    match expr {
-        Expression::Integer(i) => Ok(*i as f64),
-        Expression::Float(i) => Ok(*i),
-        e => Err(RuntimeError::new(
+    match expr.as_f64() {
+        Some(f) => Ok(f),
+        None => Err(RuntimeError::new(
             RuntimeErrorKind::TypeError {
                 expected: "Integer/Float".into(),
-                found: e.type_name(),
-                sym: e.to_string(),
+                found: expr.type_name(),
+                sym: expr.to_string(),
             },
             ctx.clone(),
             0,
@@ -286,10 +288,10 @@ fn bit_and(
 ) -> Result<Expression, RuntimeError> {
     check_exact_args_len("bit_and", &args, 2, ctx)?;
 
-    let a = get_integer_ref(&args[0], ctx)?;
-    let b = get_integer_ref(&args[1], ctx)?;
-
-    Ok(Expression::Integer(a & b))
+    match (&args[0], &args[1]) {
+        (Expression::Integer(a), Expression::Integer(b)) => Ok(Expression::Integer(a & b)),
+        (a, b) => Ok((get_bigint_ref(a, ctx)? & get_bigint_ref(b, ctx)?).into()),
+    }
 }
 
 fn bit_or(
@@ -299,10 +301,10 @@ fn bit_or(
 ) -> Result<Expression, RuntimeError> {
     check_exact_args_len("bit_or", &args, 2, ctx)?;
 
-    let a = get_integer_ref(&args[0], ctx)?;
-    let b = get_integer_ref(&args[1], ctx)?;
-
-    Ok(Expression::Integer(a | b))
+    match (&args[0], &args[1]) {
+        (Expression::Integer(a), Expression::Integer(b)) => Ok(Expression::Integer(a | b)),
+        (a, b) => Ok((get_bigint_ref(a, ctx)? | get_bigint_ref(b, ctx)?).into()),
+    }
 }
 
 fn bit_xor(
@@ -312,10 +314,10 @@ fn bit_xor(
 ) -> Result<Expression, RuntimeError> {
     check_exact_args_len("bit_xor", &args, 2, ctx)?;
 
-    let a = get_integer_ref(&args[0], ctx)?;
-    let b = get_integer_ref(&args[1], ctx)?;
-
-    Ok(Expression::Integer(a ^ b))
+    match (&args[0], &args[1]) {
+        (Expression::Integer(a), Expression::Integer(b)) => Ok(Expression::Integer(a ^ b)),
+        (a, b) => Ok((get_bigint_ref(a, ctx)? ^ get_bigint_ref(b, ctx)?).into()),
+    }
 }
 
 fn bit_not(
@@ -325,9 +327,10 @@ fn bit_not(
 ) -> Result<Expression, RuntimeError> {
     check_exact_args_len("bit_not", &args, 1, ctx)?;
 
-    let a = get_integer_ref(&args[0], ctx)?;
-
-    Ok(Expression::Integer(!a))
+    match &args[0] {
+        Expression::Integer(a) => Ok(Expression::Integer(!a)),
+        a => Ok((!get_bigint_ref(a, ctx)?).into()),
+    }
 }
 
 fn bit_shl(
@@ -337,16 +340,17 @@ fn bit_shl(
 ) -> Result<Expression, RuntimeError> {
     check_exact_args_len("bit_shl", &args, 2, ctx)?;
 
-    let a = get_integer_ref(&args[0], ctx)?;
+    let a = get_bigint_ref(&args[0], ctx)?;
     let b = get_integer_ref(&args[1], ctx)?;
-    if !(0..=63).contains(&b) {
+    if !(0..=MAX_BIGINT_BITS as i64).contains(&b) {
         return Err(RuntimeError::common(
-            format!("shift amount {} out of range (0-63)", a).into(),
+            format!("shift amount {b} out of range (0-{MAX_BIGINT_BITS})").into(),
             ctx.clone(),
             0,
         ));
     }
-    Ok(Expression::Integer(a << b))
+    // 左移溢出 i64 时提升为大整数
+    Ok((a << b as usize).into())
 }
 
 fn bit_shr(
@@ -356,16 +360,17 @@ fn bit_shr(
 ) -> Result<Expression, RuntimeError> {
     check_exact_args_len("bit_shr", &args, 2, ctx)?;
 
-    let a = get_integer_ref(&args[0], ctx)?;
+    let a = get_bigint_ref(&args[0], ctx)?;
     let b = get_integer_ref(&args[1], ctx)?;
-    if !(0..=63).contains(&b) {
+    if !(0..=MAX_BIGINT_BITS as i64).contains(&b) {
         return Err(RuntimeError::common(
-            format!("shift amount {} out of range (0-63)", a).into(),
+            format!("shift amount {b} out of range (0-{MAX_BIGINT_BITS})").into(),
             ctx.clone(),
             0,
         ));
     }
-    Ok(Expression::Integer(a >> b))
+    // 左移溢出 i64 时提升为大整数
+    Ok((a >> b as usize).into())
 }
 // Comparison Functions
 fn gt(
@@ -441,7 +446,10 @@ fn abs(
 ) -> Result<Expression, RuntimeError> {
     check_exact_args_len("abs", &args, 1, ctx)?;
     match &args[0] {
-        Expression::Integer(i) => Ok(i.abs().into()),
+        Expression::Integer(i) => Ok(i
+            .checked_abs()
+            .map_or_else(|| (-BigInt::from(*i)).into(), Expression::Integer)),
+        Expression::BigInt(n) => Ok(n.abs().into()),
         Expression::Float(f) => Ok(f.abs().into()),
         e => Err(RuntimeError::common(
             format!("invalid abs argument {e:?}").into(),
@@ -775,6 +783,23 @@ fn pow(
     ctx: &Expression,
 ) -> Result<Expression, RuntimeError> {
     check_exact_args_len("pow", &args, 2, ctx)?;
+    // 整数的非负整数次幂保持精确，溢出时提升为大整数
+    if let Expression::Integer(exponent) = args[1]
+        && let Ok(exponent) = u32::try_from(exponent)
+        && matches!(args[0], Expression::Integer(_) | Expression::BigInt(_))
+    {
+        return int_pow(&args[0], exponent).ok_or_else(|| {
+            RuntimeError::common(
+                format!(
+                    "overflow when raising int {} to the power {exponent}",
+                    args[0]
+                )
+                .into(),
+                ctx.clone(),
+                0,
+            )
+        });
+    }
     let nums = eval_to_f64(args, env, "pow", ctx)?;
     Ok(nums[0].powf(nums[1]).into())
 }
@@ -9,6 +9,7 @@ pub mod fs_lib;
 mod fs_ls;
 pub mod hmap_lib;
 pub mod into_lib;
+pub mod job_lib;
 pub mod list_lib;
 pub mod log_lib;
 pub mod map_lib;
@@ -16,9 +17,11 @@ pub mod math_lib;
 pub mod rand_lib;
 pub mod regex_lib;
 pub mod se_lib;
+pub mod stream_lib;
 pub mod string_lib;
 pub mod sys_lib;
 pub mod table_lib;
 pub mod time_lib;
 pub mod top;
+pub mod trap_lib;
 pub mod ui_lib;
@@ -8,6 +8,7 @@ use regex_lite::Regex;
 use crate::{
     Environment, Expression, RuntimeError, RuntimeErrorKind,
     eval::State,
+    expression::fmt_spec::FormatSpec,
     libs::{
         BuiltinInfo, SelfExpandFunc,
         helper::{
@@ -43,7 +44,7 @@ pub fn regist_info() -> BTreeMap<&'static str, BuiltinInfo> {
       typeof => "get type of data value", "<value>"
 
       // Data manipulation
-      format => "print formatted string with vars", "<template> <args>..."
+      format => "format string with vars and specs like {n:>8} {x:.2} {}", "<template> <args>..."
       where => "filter rows by condition", "<table> <condition> "
 
       // Execution control
@@ -77,14 +78,14 @@ fn r#where(
     state.set(State::IN_LOCAL);
 
     let predicate = |nr: usize, row: &[Expression]| -> bool {
-        state.set_local_var("NR".to_string(), Expression::Integer(nr as i64));
+        state.set_local_var("NR", Expression::Integer(nr as i64));
         for (nf, cell) in row.iter().enumerate() {
             let name = data
                 .headers()
                 .get(nf)
                 .map_or("unkown".to_string(), |x| x.to_string());
-            state.set_local_var(name, cell.clone());
-            state.set_local_var("NF".to_string(), Expression::Integer(nf as i64));
+            state.set_local_var(&name, cell.clone());
+            state.set_local_var("NF", Expression::Integer(nf as i64));
         }
         match args[1].eval_mut(state, env, 0) {
             Ok(x) => x.is_truthy(),
@@ -228,18 +229,32 @@ fn format(
     check_args_len("format", args, 1.., ctx)?;
     let template_expr = args[0].eval_mut(state, env, 0)?;
     let template = get_string_arg(template_expr, ctx)?;
-    let re = FORMAT_RE.get_or_init(|| Regex::new(r#"\{(\w+)\}"#).unwrap());
-    let mut result = template.clone();
-    for (full, [var]) in re.captures_iter(&template).map(|m| m.extract()) {
-        let value = ctx.handle_variable(var, false, state, env, 0)?;
-        result = result.replace(full, &value.to_string());
-    }
-
-    // position arg
-    let placeholders = result.matches("{}").count();
-    for arg in args.iter().skip(1).take(placeholders) {
-        result = result.replacen("{}", &arg.eval_mut(state, env, 0)?.to_string(), 1);
+    let re = FORMAT_RE.get_or_init(|| Regex::new(r#"\{(\w*)(?::([^{}]+))?\}"#).unwrap());
+    let mut result = String::with_capacity(template.len());
+    let mut last = 0;
+    let mut positional = args.iter().skip(1);
+    for caps in re.captures_iter(&template) {
+        let full = caps.get(0).unwrap();
+        let value = match caps.get(1).map(|m| m.as_str()) {
+            // named var
+            Some(var) if !var.is_empty() => ctx.handle_variable(var, false, state, env, 0)?,
+            // position arg, 多余的占位符原样保留
+            _ => match positional.next() {
+                Some(arg) => arg.eval_mut(state, env, 0)?,
+                None => continue,
+            },
+        };
+        let text = match caps.get(2) {
+            Some(spec) => FormatSpec::parse(spec.as_str())
+                .and_then(|spec| value.format_with(&spec))
+                .map_err(|msg| RuntimeError::common(msg.into(), ctx.clone(), 0))?,
+            None => value.to_string(),
+        };
+        result.push_str(&template[last..full.start()]);
+        result.push_str(&text);
+        last = full.end();
     }
+    result.push_str(&template[last..]);
 
     // println!("{}", result);
     Ok(Expression::String(result))
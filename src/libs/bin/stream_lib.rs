@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader};
+
+use crate::expression::cmd_excutor::stdout_lines;
+use crate::expression::{LumeStream, StreamItem};
+use crate::libs::helper::{
+    check_args_len, check_exact_args_len, check_fn_arg, get_integer_ref, get_string_arg,
+    get_string_ref,
+};
+use crate::libs::lazy_module::LazyModule;
+use crate::utils::canon;
+use crate::{
+    Environment, Expression, Int, RuntimeError, RuntimeErrorKind, eval::State, libs::BuiltinInfo,
+    reg_info, reg_lazy,
+};
+
+pub fn regist_lazy() -> LazyModule {
+    reg_lazy!({
+        //创建
+        from, lines, cmd,
+        //惰性变换
+        map, filter, take, skip, chunk,
+        //消费
+        collect, count,
+    })
+}
+pub fn regist_info() -> BTreeMap<&'static str, BuiltinInfo> {
+    reg_info!({
+        from => "create a stream from a list, set or range", "<list|set|range>"
+        lines => "stream the lines of a file", "<path>"
+        cmd => "stream the stdout lines of a command", "<cmd> [args...]"
+
+        map => "lazily apply a function to each item", "<stream> <fn>"
+        filter => "lazily keep items matching a condition", "<stream> <fn>"
+        take => "lazily take the first n items", "<stream> <n>"
+        skip => "lazily skip the first n items", "<stream> <n>"
+        chunk => "lazily group items into lists of n", "<stream> <n>"
+
+        collect => "pull all items into a list", "<stream>"
+        count => "pull all items and count them", "<stream>"
+    })
+}
+
+fn get_stream(expr: &Expression, ctx: &Expression) -> Result<LumeStream, RuntimeError> {
+    match expr {
+        Expression::Stream(s) => Ok(s.clone()),
+        e => Err(RuntimeError::new(
+            RuntimeErrorKind::TypeError {
+                expected: "Stream".into(),
+                found: e.type_name(),
+                sym: e.to_string(),
+            },
+            ctx.clone(),
+            0,
+        )),
+    }
+}
+
+fn get_count(expr: &Expression, ctx: &Expression) -> Result<usize, RuntimeError> {
+    let n = get_integer_ref(expr, ctx)?;
+    usize::try_from(n).map_err(|_| {
+        RuntimeError::common(
+            format!("expected a non-negative count, found {n}").into(),
+            ctx.clone(),
+            0,
+        )
+    })
+}
+
+// 创建
+fn from(
+    args: Vec<Expression>,
+    _env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_exact_args_len("from", &args, 1, ctx)?;
+    let stream = match args.into_iter().next().unwrap() {
+        Expression::Range(r, step) => {
+            LumeStream::new(r.step_by(step).map(|i| Ok(Expression::Integer(i))))
+        }
+        Expression::List(list) => {
+            let items = list.as_ref().clone();
+            LumeStream::new(items.into_iter().map(Ok))
+        }
+        Expression::BSet(set) => {
+            let items = set.iter().cloned().collect::<Vec<_>>();
+            LumeStream::new(items.into_iter().map(Ok))
+        }
+        Expression::Stream(s) => s,
+        e => {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::TypeError {
+                    expected: "List/Set/Range".into(),
+                    found: e.type_name(),
+                    sym: e.to_string(),
+                },
+                ctx.clone(),
+                0,
+            ));
+        }
+    };
+    Ok(Expression::Stream(stream))
+}
+
+fn lines(
+    args: Vec<Expression>,
+    env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_exact_args_len("lines", &args, 1, ctx)?;
+    let p = get_string_ref(&args[0], ctx)?;
+    let path = canon(p, env)?;
+    let file = std::fs::File::open(&path)
+        .map_err(|e| RuntimeError::from_io_error(e, "open file".into(), args[0].clone(), 0))?;
+    let target = args[0].clone();
+    let stream = LumeStream::new(BufReader::new(file).lines().map(move |line| {
+        line.map(Expression::String)
+            .map_err(|e| RuntimeError::from_io_error(e, "read line".into(), target.clone(), 0))
+    }));
+    Ok(Expression::Stream(stream))
+}
+
+fn cmd(
+    args: Vec<Expression>,
+    env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_args_len("cmd", &args, 1.., ctx)?;
+    let mut it = args.into_iter();
+    let cmdstr = get_string_arg(it.next().unwrap(), ctx)?;
+    let cmd_args = it.map(|a| a.to_string()).collect();
+    Ok(Expression::Stream(stdout_lines(
+        &cmdstr, cmd_args, env, ctx,
+    )?))
+}
+
+// 惰性变换
+fn map(
+    args: Vec<Expression>,
+    env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_exact_args_len("map", &args, 2, ctx)?;
+    let stream = get_stream(&args[0], ctx)?;
+    let func = args[1].clone();
+    check_fn_arg(&func, 1, ctx)?;
+    let mut env = env.fork();
+    let mapped = std::iter::from_fn(move || {
+        let item = stream.pull()?;
+        Some(item.and_then(|v| func.eval_apply(&func, &[v], &mut State::new(), &mut env, 0)))
+    });
+    Ok(Expression::Stream(LumeStream::new(mapped)))
+}
+
+fn filter(
+    args: Vec<Expression>,
+    env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_exact_args_len("filter", &args, 2, ctx)?;
+    let stream = get_stream(&args[0], ctx)?;
+    let func = args[1].clone();
+    check_fn_arg(&func, 1, ctx)?;
+    let mut env = env.fork();
+    let filtered = std::iter::from_fn(move || {
+        loop {
+            let v = match stream.pull()? {
+                Ok(v) => v,
+                Err(e) => return Some(Err(e)),
+            };
+            match func.eval_apply(
+                &func,
+                std::slice::from_ref(&v),
+                &mut State::new(),
+                &mut env,
+                0,
+            ) {
+                Ok(r) if r.is_truthy() => return Some(Ok(v)),
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    });
+    Ok(Expression::Stream(LumeStream::new(filtered)))
+}
+
+fn take(
+    args: Vec<Expression>,
+    _env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_exact_args_len("take", &args, 2, ctx)?;
+    let stream = get_stream(&args[0], ctx)?;
+    let n = get_count(&args[1], ctx)?;
+    // 取够后不再拉取上游
+    let mut left = n;
+    let taken = std::iter::from_fn(move || {
+        if left == 0 {
+            return None;
+        }
+        left -= 1;
+        stream.pull()
+    });
+    Ok(Expression::Stream(LumeStream::new(taken)))
+}
+
+fn skip(
+    args: Vec<Expression>,
+    _env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_exact_args_len("skip", &args, 2, ctx)?;
+    let stream = get_stream(&args[0], ctx)?;
+    let mut n = get_count(&args[1], ctx)?;
+    let skipped = std::iter::from_fn(move || {
+        while n > 0 {
+            n -= 1;
+            if let Err(e) = stream.pull()? {
+                return Some(Err(e));
+            }
+        }
+        stream.pull()
+    });
+    Ok(Expression::Stream(LumeStream::new(skipped)))
+}
+
+fn chunk(
+    args: Vec<Expression>,
+    _env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_exact_args_len("chunk", &args, 2, ctx)?;
+    let stream = get_stream(&args[0], ctx)?;
+    let n = get_count(&args[1], ctx)?;
+    if n == 0 {
+        return Err(RuntimeError::common(
+            "chunk size must be greater than 0".into(),
+            ctx.clone(),
+            0,
+        ));
+    }
+    let chunked = std::iter::from_fn(move || -> Option<StreamItem> {
+        let mut items = Vec::with_capacity(n);
+        while items.len() < n {
+            match stream.pull() {
+                Some(Ok(v)) => items.push(v),
+                Some(Err(e)) => return Some(Err(e)),
+                None => break,
+            }
+        }
+        (!items.is_empty()).then(|| Ok(Expression::from(items)))
+    });
+    Ok(Expression::Stream(LumeStream::new(chunked)))
+}
+
+// 消费
+fn collect(
+    args: Vec<Expression>,
+    _env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_exact_args_len("collect", &args, 1, ctx)?;
+    let stream = get_stream(&args[0], ctx)?;
+    let mut items = Vec::new();
+    while let Some(item) = stream.pull() {
+        items.push(item?);
+    }
+    Ok(Expression::from(items))
+}
+
+fn count(
+    args: Vec<Expression>,
+    _env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_exact_args_len("count", &args, 1, ctx)?;
+    let stream = get_stream(&args[0], ctx)?;
+    let mut n: Int = 0;
+    while let Some(item) = stream.pull() {
+        item?;
+        n += 1;
+    }
+    Ok(Expression::Integer(n))
+}
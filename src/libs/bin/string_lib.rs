@@ -2,6 +2,7 @@ use regex_lite::Regex;
 
 use crate::{
     Environment, Expression, Int, RuntimeError, RuntimeErrorKind,
+    expression::fmt_spec::FormatSpec,
     libs::{
         BuiltinInfo,
         bin::colors::{COLOR_MAP, true_color_by_hex},
@@ -39,7 +40,7 @@ pub fn regist_lazy() -> LazyModule {
         caesar,
         strip,
         // 格式化
-        pad_start, pad_end, center, wrap,
+        pad_start, pad_end, center, wrap, fmt,
         // 样式
         href, bold, dim, italic, underline, blink, invert, strike,
         // 标准颜色
@@ -110,6 +111,7 @@ pub fn regist_info() -> BTreeMap<&'static str, BuiltinInfo> {
        pad_end => "pad string to specified length at end", "<string> <length> [pad_char]"
        center => "center string by padding both ends", "<string> <length> [pad_char]"
        wrap => "wrap text to fit in specific number of columns", "<string> <width>"
+       fmt => "format a value by spec like >10, .2, 08x, , or %Y-%m-%d", "<value> <spec>"
 
        // 样式
        href => "create terminal hyperlink", "<url> <text>"
@@ -694,6 +696,19 @@ fn wrap(
     Ok(textwrap::fill(text, columns as usize).into())
 }
 
+fn fmt(
+    args: Vec<Expression>,
+    _env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_exact_args_len("fmt", &args, 2, ctx)?;
+    let spec = get_string_ref(&args[1], ctx)?;
+    FormatSpec::parse(spec)
+        .and_then(|spec| args[0].format_with(&spec))
+        .map(Expression::String)
+        .map_err(|msg| RuntimeError::common(msg.into(), ctx.clone(), 0))
+}
+
 // Style Functions
 fn href(
     args: Vec<Expression>,
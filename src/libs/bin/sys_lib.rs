@@ -2,12 +2,16 @@ use std::rc::Rc;
 
 use common_macros::hash_map;
 
+use crate::expression::cmd_excutor::{EnvOverride, run_captured};
 use crate::libs::BuiltinInfo;
-use crate::libs::helper::{check_exact_args_len, get_integer_ref};
+use crate::libs::helper::{
+    check_args_len, check_exact_args_len, check_fn_arg, get_integer_ref, get_map_ref,
+};
 use crate::{
     CFM_ENABLED, Environment, Expression, Int, LmError, MAX_RUNTIME_RECURSION,
-    MAX_SYNTAX_RECURSION, MAX_USEMODE_RECURSION, PRINT_DIRECT, RuntimeError, STRICT_ENABLED,
-    set_cfm_enabled, set_print_direct, set_strict_enabled,
+    MAX_SYNTAX_RECURSION, MAX_USEMODE_RECURSION, PRINT_DIRECT, RuntimeError, RuntimeErrorKind,
+    STRICT_ENABLED, childman, set_cfm_enabled, set_pipefail_enabled, set_print_direct,
+    set_strict_enabled, with_pipefail_enabled,
 };
 use std::collections::BTreeMap;
 
@@ -20,13 +24,16 @@ pub fn regist_lazy() -> LazyModule {
         quote, ecodes_rt, ecodes_lm,
 
         info,modes,
+        last_status, run, timeout,
         // throw,
         max_syntax,
         max_runtime,
         max_usemode,
         set_cfm,
         set_pdm,
-        set_strict
+        set_strict,
+        set_pipefail,
+        pipestatus
     })
 }
 pub fn regist_info() -> BTreeMap<&'static str, BuiltinInfo> {
@@ -44,6 +51,9 @@ pub fn regist_info() -> BTreeMap<&'static str, BuiltinInfo> {
 
         info => "get os info", ""
         modes => "get lume modes", ""
+        last_status => "get status of the last external command: code, signal, duration(ms), pid, argv", ""
+        run => "run a command, capture stdout, stderr, code, signal, duration(ms) without failing", "<cmd> [args...]"
+        timeout => "run a no-arg func, interrupt it and kill its process groups when time is up", "<secs> <func> [grace_secs]"
 
         max_syntax => "get/set max syntax recursion","[int]"
         max_runtime=> "get/set max runtime recursion","[int]"
@@ -51,6 +61,8 @@ pub fn regist_info() -> BTreeMap<&'static str, BuiltinInfo> {
         set_cfm=> "enable/disable CFM","<boolean>"
         set_pdm=> "enable/disable print direct mode","<boolean>"
         set_strict=> "enable/disable strict mode","<boolean>"
+        set_pipefail=> "enable/disable pipefail: a pipeline fails if any stage fails","<boolean>"
+        pipestatus=> "get exit codes of every stage of the last pipeline",""
 
     })
 }
@@ -107,10 +119,91 @@ fn modes(
     Ok(Expression::from(hash_map! {
         String::from("cfm") => CFM_ENABLED.with_borrow(|c|c==&true),
         String::from("strict") => STRICT_ENABLED.with_borrow(|c|c==&true),
+        String::from("pipefail") => with_pipefail_enabled(|b| b),
         String::from("pdm") => PRINT_DIRECT.with_borrow(|c|c==&true),
     }))
 }
 
+fn last_status(
+    _args: Vec<Expression>,
+    _env: &mut Environment,
+    _ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    let st = childman::last_status();
+    Ok(Expression::from(hash_map! {
+        String::from("code") => st.code.map_or(Expression::None, |c| Expression::Integer(c as Int)),
+        String::from("signal") => st.signal.map_or(Expression::None, |s| Expression::Integer(s as Int)),
+        String::from("duration") => Expression::Integer(st.duration.as_millis() as Int),
+        String::from("pid") => Expression::Integer(st.pid as Int),
+        String::from("argv") => Expression::from(st.argv.into_iter().map(Expression::String).collect::<Vec<_>>()),
+    }))
+}
+
+/// 运行外部命令，返回 {stdout, stderr, code, signal, duration}；列表参数逐项展开
+fn run(
+    args: Vec<Expression>,
+    env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_args_len("run", &args, 1.., ctx)?;
+    let mut argv = args.iter().flat_map(|a| match a {
+        Expression::List(ls) => ls.iter().map(|i| i.to_string()).collect(),
+        other => vec![other.to_string()],
+    });
+    let cmd = argv.next().unwrap_or_default();
+    let out = run_captured(ctx, &cmd, argv.collect(), env, 0)?;
+    Ok(Expression::from(hash_map! {
+        String::from("stdout") => Expression::String(String::from_utf8_lossy(&out.stdout).into_owned()),
+        String::from("stderr") => Expression::String(String::from_utf8_lossy(&out.stderr).into_owned()),
+        String::from("code") => out.info.code.map_or(Expression::None, |c| Expression::Integer(c as Int)),
+        String::from("signal") => out.info.signal.map_or(Expression::None, |s| Expression::Integer(s as Int)),
+        String::from("duration") => Expression::Integer(out.info.duration.as_millis() as Int),
+    }))
+}
+
+/// 秒数参数，允许整数或小数
+fn get_secs(expr: &Expression, ctx: &Expression) -> Result<std::time::Duration, RuntimeError> {
+    let secs = match expr {
+        Expression::Integer(i) => *i as f64,
+        Expression::Float(f) => *f,
+        _ => -1.0,
+    };
+    std::time::Duration::try_from_secs_f64(secs).map_err(|_| {
+        RuntimeError::common(
+            format!("timeout: invalid seconds `{expr}`").into(),
+            ctx.clone(),
+            0,
+        )
+    })
+}
+
+/// sys.timeout：限时执行无参函数，到期后中断脚本求值，向其间启动的进程组发送 SIGTERM，
+/// 宽限期（默认 3 秒）后 SIGKILL，并返回 Timeout 错误。
+/// 不占用顶层名字，`timeout 1 sleep 3` 仍调用系统命令。
+fn timeout(
+    args: Vec<Expression>,
+    env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_args_len("timeout", &args, 2..=3, ctx)?;
+    let limit = get_secs(&args[0], ctx)?;
+    let grace = match args.get(2) {
+        Some(g) => get_secs(g, ctx)?,
+        None => std::time::Duration::from_secs(3),
+    };
+    check_fn_arg(&args[1], 0, ctx)?;
+    let watchdog = childman::Watchdog::start(limit, grace);
+    let result = args[1].apply(vec![]).eval_cmd(env);
+    match watchdog.expired() {
+        true => Err(RuntimeError::new(
+            RuntimeErrorKind::Timeout(limit),
+            ctx.clone(),
+            0,
+        )),
+        false => result,
+    }
+}
+
 fn quote(
     mut args: Vec<Expression>,
     _env: &mut Environment,
@@ -133,6 +226,25 @@ fn env(
     Ok(Expression::from(env.get_root().clone()))
 }
 
+/// with_env {K: v, X: none} func：仅对 func 中启动的外部命令设置或移除环境变量
+pub fn with_env(
+    args: Vec<Expression>,
+    env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_exact_args_len("with_env", &args, 2, ctx)?;
+    let vars = get_map_ref(&args[0], ctx)?
+        .iter()
+        .map(|(k, v)| match v {
+            Expression::None => (k.clone(), None),
+            v => (k.clone(), Some(v.to_string())),
+        })
+        .collect();
+    check_fn_arg(&args[1], 0, ctx)?;
+    let _scope = EnvOverride::push(vars);
+    args[1].apply(vec![]).eval_cmd(env)
+}
+
 fn vars(
     _args: Vec<Expression>,
     env: &mut Environment,
@@ -236,6 +348,33 @@ fn set_strict(
     }
     Ok(Expression::None)
 }
+fn set_pipefail(
+    args: Vec<Expression>,
+    env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_exact_args_len("set_pipefail", &args, 1, ctx)?;
+    let b = args[0].is_truthy();
+    env.define_in_root("IS_PIPEFAIL", Expression::Boolean(b));
+    set_pipefail_enabled(b);
+    if b {
+        println!("\x1b[38;5;141m[PIPEFAIL Mode: ON]\x1b[0m");
+    } else {
+        println!("\x1b[38;5;209m[PIPEFAIL Mode: OFF]\x1b[0m");
+    }
+    Ok(Expression::None)
+}
+fn pipestatus(
+    _args: Vec<Expression>,
+    _env: &mut Environment,
+    _ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    let codes = childman::pipe_status()
+        .into_iter()
+        .map(|c| Expression::Integer(c as Int))
+        .collect::<Vec<_>>();
+    Ok(Expression::from(codes))
+}
 fn set_cfm(
     args: Vec<Expression>,
     env: &mut Environment,
@@ -6,17 +6,21 @@ use std::{
 
 use crate::{
     Environment, Expression, Int, RuntimeError, RuntimeErrorKind, VERSION,
+    eval::State,
     expression::table::TableData,
     libs::{
         BuiltinFunc, BuiltinInfo, LIBS_INFO,
         bin::{
             boolean_lib::not,
+            job_lib::{bg, disown, fg, jobs, kill, wait},
+            sys_lib::with_env,
             table_lib::{select, sortby},
+            trap_lib::run_exit_trap,
         },
         helper::{check_args_len, check_exact_args_len, get_string_ref},
         pretty_printer,
     },
-    parse_and_eval, reg_all, reg_info,
+    parse_and_eval, profiler, reg_all, reg_info,
     utils::{abs_script, canon, get_current_path_string},
 };
 
@@ -29,6 +33,7 @@ pub fn regist_all() -> HashMap<&'static str, Rc<BuiltinFunc>> {
         eval, exec, eval_str, exec_str, include, import,
         help,
         throw,
+        jobs, fg, bg, wait, kill, disown, with_env,
     })
 }
 
@@ -77,6 +82,15 @@ pub fn regist_info() -> BTreeMap<&'static str, BuiltinInfo> {
         include => "evaluate file in current env", "<path>"
         import => "evaluate file in new env", "<path>"
 
+        // Job control
+        jobs => "list background and stopped jobs", ""
+        fg => "continue a job in foreground", "[%job]"
+        bg => "continue a stopped job in background", "[%job]"
+        wait => "wait for jobs to finish, return exit code", "[%job|pid]..."
+        kill => "send signal to jobs or processes", "[-SIG|-s SIG] <%job|pid>..."
+        disown => "remove a job from job table", "[%job]"
+        with_env => "run a no-arg func with env vars set (none unsets) for the commands it starts", "<map> <func>"
+
         // env
         // set_root => "define a variable in root environment", "<var> <val>"
         // unset_root => "undefine a variable in root environment", "<var>"
@@ -95,6 +109,17 @@ fn help(
     use std::fmt::Write as FmtWrite;
     let mut s = String::new();
 
+    // 自定义函数：显示带类型标注的签名
+    if let Some(f @ Expression::Function(name, ..)) = args.first() {
+        writeln!(
+            s,
+            "fn \x1b[92m\x1b[1m{name}\x1b[m\x1b[0m\x1b[2m{}\x1b[m\x1b[0m",
+            f.fn_signature().unwrap_or_default()
+        )
+        .unwrap();
+        return Ok(Expression::String(s));
+    }
+
     match args.is_empty() {
         false => match args[0].to_string().as_str() {
             "doc" => {
@@ -298,7 +323,7 @@ fn include(
 }
 fn exit(
     args: Vec<Expression>,
-    _env: &mut Environment,
+    env: &mut Environment,
     ctx: &Expression,
 ) -> Result<Expression, RuntimeError> {
     let code = if args.is_empty() {
@@ -315,6 +340,8 @@ fn exit(
             }
         }
     };
+    run_exit_trap(env);
+    profiler::report();
     std::process::exit(code);
 }
 fn cd(
@@ -417,13 +444,13 @@ fn tap(
 
 fn print(
     args: Vec<Expression>,
-    _env: &mut Environment,
+    env: &mut Environment,
     _ctx: &Expression,
 ) -> Result<Expression, RuntimeError> {
     // let is_tty = std::io::stdout().is_terminal();
     let mut stdout = std::io::stdout().lock();
     for x in args.iter() {
-        let s = format!("{x} ");
+        let s = format!("{} ", x.display_string(&mut State::new(), env, 0)?);
         // if is_tty {
         //     let _ = write!(&mut stdout, "{}", s);
         //     // let _ = write!(&mut stdout, "{}", s.replace('\n', "\r\n"));
@@ -441,12 +468,12 @@ fn print(
 }
 fn println(
     args: Vec<Expression>,
-    _env: &mut Environment,
+    env: &mut Environment,
     _ctx: &Expression,
 ) -> Result<Expression, RuntimeError> {
     let mut stdout = std::io::stdout().lock();
     for x in args.iter() {
-        let s = format!("{x}");
+        let s = x.display_string(&mut State::new(), env, 0)?;
         let _ = writeln!(&mut stdout, "{s}");
     }
     // let _ = stdout.flush();
@@ -0,0 +1,160 @@
+//! 信号陷阱：trap 及求值安全点、脚本结束时执行陷阱
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+};
+
+use crate::{
+    Environment, Expression, RuntimeError, childman,
+    libs::{
+        BuiltinFunc, BuiltinInfo,
+        helper::{check_args_len, check_fn_arg},
+    },
+    profiler, reg_all, reg_info,
+};
+
+pub fn regist_all() -> HashMap<&'static str, std::rc::Rc<BuiltinFunc>> {
+    reg_all!({ trap })
+}
+
+pub fn regist_info() -> BTreeMap<&'static str, BuiltinInfo> {
+    reg_info!({
+        trap => "run a func when INT/TERM/HUP/USR1 arrives or on EXIT; no func removes it", "[signal] [func]"
+    })
+}
+
+/// 可设置陷阱的信号；EXIT 为脚本结束时的伪信号
+#[cfg(unix)]
+const TRAP_SIGNALS: [(&str, i32); 4] = [
+    ("INT", libc::SIGINT),
+    ("TERM", libc::SIGTERM),
+    ("HUP", libc::SIGHUP),
+    ("USR1", libc::SIGUSR1),
+];
+
+thread_local! {
+    /// 信号名 -> 处理函数
+    static TRAPS: RefCell<BTreeMap<String, Expression>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+fn trap_name(arg: &Expression, ctx: &Expression) -> Result<String, RuntimeError> {
+    let name = arg.to_string().to_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name).to_string();
+    #[cfg(unix)]
+    let known = name == "EXIT" || TRAP_SIGNALS.iter().any(|(n, _)| *n == name);
+    #[cfg(windows)]
+    let known = name == "EXIT";
+    match known {
+        true => Ok(name),
+        false => Err(RuntimeError::common(
+            format!("trap: unsupported signal `{arg}`, expect INT/TERM/HUP/USR1/EXIT").into(),
+            ctx.clone(),
+            0,
+        )),
+    }
+}
+
+/// 需由 shell 捕获的信号：有对应陷阱，或设置了 EXIT 陷阱时的终止类信号
+#[cfg(unix)]
+fn caught_signals() -> Vec<(&'static str, i32)> {
+    TRAPS.with_borrow(|traps| {
+        let on_exit = traps.contains_key("EXIT");
+        TRAP_SIGNALS
+            .into_iter()
+            .filter(|(name, sig)| {
+                let fatal = match *sig {
+                    // 交互模式下 Ctrl-C 不退出 shell
+                    libc::SIGINT => !childman::job_control_enabled(),
+                    libc::SIGUSR1 => false,
+                    _ => true,
+                };
+                traps.contains_key(*name) || (on_exit && fatal)
+            })
+            .collect()
+    })
+}
+
+/// 为信号设置处理函数（可接收信号名作参数）；不给处理函数时移除陷阱。
+/// 无参数时返回已设置的陷阱
+pub fn trap(
+    args: Vec<Expression>,
+    _env: &mut Environment,
+    ctx: &Expression,
+) -> Result<Expression, RuntimeError> {
+    check_args_len("trap", &args, 0..=2, ctx)?;
+    let Some(sig) = args.first() else {
+        let traps = TRAPS.with_borrow(|t| t.clone());
+        return Ok(Expression::from(traps));
+    };
+    let name = trap_name(sig, ctx)?;
+    match args.get(1) {
+        Some(Expression::None) | None => {
+            TRAPS.with_borrow_mut(|t| t.remove(&name));
+        }
+        Some(handler) => {
+            if check_fn_arg(handler, 0, ctx).is_err() {
+                check_fn_arg(handler, 1, ctx)?;
+            }
+            TRAPS.with_borrow_mut(|t| t.insert(name, handler.clone()));
+        }
+    }
+    #[cfg(unix)]
+    {
+        let caught = caught_signals();
+        for (name, sig) in TRAP_SIGNALS {
+            childman::catch_signal(sig, caught.iter().any(|(n, _)| *n == name));
+        }
+    }
+    Ok(Expression::None)
+}
+
+fn call_trap(handler: &Expression, name: &str, env: &mut Environment) -> Result<(), RuntimeError> {
+    let args = match check_fn_arg(handler, 1, handler) {
+        Ok(_) => vec![Expression::String(name.to_string())],
+        Err(_) => vec![],
+    };
+    handler.apply(args).eval_cmd(env).map(|_| ())
+}
+
+/// 求值安全点：执行已收到信号的陷阱。
+/// 仅因 EXIT 陷阱而捕获的终止信号，执行 EXIT 陷阱后以 128+信号 退出
+pub fn run_traps(env: &mut Environment) -> Result<(), RuntimeError> {
+    #[cfg(unix)]
+    {
+        let caught = caught_signals();
+        let mask = caught.iter().fold(0, |m, (_, sig)| m | (1 << sig));
+        let pending = childman::take_pending_signals(mask);
+        for (name, sig) in caught {
+            if pending & (1 << sig) == 0 {
+                continue;
+            }
+            match TRAPS.with_borrow(|t| t.get(name).cloned()) {
+                Some(handler) => call_trap(&handler, name, env)?,
+                None => {
+                    run_exit_trap(env);
+                    profiler::report();
+                    std::process::exit(128 + sig);
+                }
+            }
+        }
+    }
+    #[cfg(windows)]
+    let _ = env;
+    Ok(())
+}
+
+/// 执行并移除 EXIT 陷阱：脚本结束、调用 exit 或被终止时。
+/// 先处理尚未到达安全点的信号（如中断导致脚本出错结束）
+pub fn run_exit_trap(env: &mut Environment) {
+    if childman::has_pending_signals()
+        && let Err(e) = run_traps(env)
+    {
+        eprintln!("trap failed:\n{e}");
+    }
+    if let Some(handler) = TRAPS.with_borrow_mut(|t| t.remove("EXIT"))
+        && let Err(e) = call_trap(&handler, "EXIT", env)
+    {
+        eprintln!("trap EXIT failed:\n{e}");
+    }
+}
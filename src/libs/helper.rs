@@ -6,6 +6,7 @@ use std::{
 };
 
 use crate::{Expression, RuntimeError, RuntimeErrorKind, expression::table::TableData};
+use num_bigint::BigInt;
 
 // use std::rc::Rc;
 
@@ -222,6 +223,20 @@ pub fn get_integer_ref(expr: &Expression, ctx: &Expression) -> Result<i64, Runti
         )),
     }
 }
+/// 整数参数（含大整数）
+pub fn get_bigint_ref(expr: &Expression, ctx: &Expression) -> Result<BigInt, RuntimeError> {
+    expr.to_bigint().ok_or_else(|| {
+        RuntimeError::new(
+            RuntimeErrorKind::TypeError {
+                expected: "Integer".into(),
+                sym: expr.to_string(),
+                found: expr.type_name(),
+            },
+            ctx.clone(),
+            0,
+        )
+    })
+}
 
 pub fn get_map_ref<'a>(
     expr: &'a Expression,
@@ -302,7 +317,7 @@ pub fn check_fn_arg(
 ) -> Result<(), RuntimeError> {
     let fn_arg_count = match fn_arg {
         Expression::Lambda(params, ..) => params.len(),
-        Expression::Function(_, params, _, _, _) => params.len(),
+        Expression::Function(_, params, ..) => params.len(),
         _ => {
             return Err(RuntimeError::common(
                 "expect a func/lambda as param".into(),
@@ -3,11 +3,13 @@ mod helper;
 mod lazy_module;
 mod pprint;
 use crate::RuntimeErrorKind;
+use crate::profiler::{self, Kind};
 use crate::{Environment, Expression, RuntimeError, eval::State, libs::lazy_module::LazyModule};
 pub use bin::colors::{handle_color, handle_style};
 pub use bin::math_lib::handle_math;
 pub use bin::time_lib::parse as time_parse;
 pub use bin::top::regist_info;
+pub use bin::trap_lib::{run_exit_trap, run_traps};
 pub use pprint::pretty_printer;
 use std::{
     borrow::Cow,
@@ -44,7 +46,9 @@ thread_local! {
     });
 
     static TOP_LIB: RefCell<HashMap<&'static str, Rc<BuiltinFunc>>> = RefCell::new({
-       bin::top::regist_all()
+       let mut top = bin::top::regist_all();
+       top.extend(bin::trap_lib::regist_all());
+       top
     });
     static BOOL_LIB: RefCell<HashMap<&'static str, Rc<BuiltinFunc>>> = RefCell::new({
         bin::boolean_lib::regist_all()
@@ -64,6 +68,7 @@ thread_local! {
     static TABLE_LIB: LazyModule = bin::table_lib::regist_lazy();
     static TIME_LIB: LazyModule = bin::time_lib::regist_lazy();
     static REGEX_LIB: LazyModule = bin::regex_lib::regist_lazy();
+    static STREAM_LIB: LazyModule = bin::stream_lib::regist_lazy();
     static MATH_LIB: LazyModule = bin::math_lib::regist_lazy();
     static RAND_LIB: LazyModule = bin::rand_lib::regist_lazy();
     static LOG_LIB: LazyModule = bin::log_lib::regist_lazy();
@@ -83,6 +88,7 @@ fn regist_all_info() -> BTreeMap<&'static str, BTreeMap<&'static str, BuiltinInf
     let mut top_info = bin::top::regist_info();
     let se_info = bin::se_lib::regist_info();
     top_info.extend(se_info);
+    top_info.extend(bin::trap_lib::regist_info());
     libs_info.insert("", top_info); //regist to top
     libs_info.insert("boolean", bin::boolean_lib::regist_info());
     libs_info.insert("string", bin::string_lib::regist_info());
@@ -93,6 +99,7 @@ fn regist_all_info() -> BTreeMap<&'static str, BTreeMap<&'static str, BuiltinInf
     libs_info.insert("table", bin::table_lib::regist_info());
     libs_info.insert("time", bin::time_lib::regist_info());
     libs_info.insert("regex", bin::regex_lib::regist_info());
+    libs_info.insert("stream", bin::stream_lib::regist_info());
     libs_info.insert("math", bin::math_lib::regist_info());
     libs_info.insert("rand", bin::rand_lib::regist_info());
     libs_info.insert("fs", bin::fs_lib::regist_info());
@@ -165,6 +172,10 @@ pub fn get_builtin_optimized(
             .with(|m| m.get_function(fn_name))
             .ok_or(no_lib_err("regex", fn_name, ctx))
             .map(Some),
+        "stream" => STREAM_LIB
+            .with(|m| m.get_function(fn_name))
+            .ok_or(no_lib_err("stream", fn_name, ctx))
+            .map(Some),
         "math" => MATH_LIB
             .with(|m| m.get_function(fn_name))
             .ok_or(no_lib_err("math", fn_name, ctx))
@@ -276,7 +287,7 @@ pub fn get_lib_completions(prefix: &str) -> Option<Vec<&str>> {
 }
 
 /// 类型名称
-fn get_belong_lib_name(exp: &Expression) -> Option<Cow<'static, str>> {
+pub fn get_belong_lib_name(exp: &Expression) -> Option<Cow<'static, str>> {
     match exp {
         Expression::List(_) | Expression::Range(..) => Some("list".into()),
         Expression::BSet(_) => Some("set".into()),
@@ -290,6 +301,7 @@ fn get_belong_lib_name(exp: &Expression) -> Option<Cow<'static, str>> {
         Expression::DateTime(_) => Some("time".into()),
         Expression::Boolean(_) => Some("boolean".into()),
         Expression::Regex(_) => Some("regex".into()),
+        Expression::Stream(_) => Some("stream".into()),
         Expression::FileSize(_) => Some("filesize".into()),
         _ => None,
     }
@@ -319,6 +331,7 @@ pub fn exec_self_expand_lib(
 ) -> Result<Option<Expression>, RuntimeError> {
     SE_LIB.with_borrow(|s| {
         if let Some(selib) = s.get(fn_name) {
+            let _span = profiler::span(Kind::Builtin, || fn_name.to_string());
             let result = selib(args, env, state, ctx)?;
             return Ok(Some(result));
         }
@@ -1,25 +1,34 @@
 use crate::MAX_USEMODE_RECURSION;
+use crate::RuntimeErrorKind;
 use crate::SyntaxErrorKind;
+use crate::ast_cache;
 use crate::utils::canon;
 use crate::utils::expand_home;
 use crate::{Environment, Expression, ModuleInfo, RuntimeError, SyntaxError, use_script};
 use std::borrow::Cow;
+use std::collections::HashMap;
 // use std::collections::HashSet;
 use std::fs::read_to_string;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// 模块私有成员（未 pub 的函数、未再导出的导入）存放的键
+pub const MODULE_PRIVATE: &str = "__private__";
 
 pub fn use_module<'a>(
     alias: &'a Option<String>,
     module_path: &'a str,
     env: &mut Environment,
 ) -> Result<(Cow<'a, str>, Expression), RuntimeError> {
-    // 获取基础路径
+    // 获取基础路径：脚本所在目录
     let base = match env.get("SCRIPT") {
-        Some(Expression::String(s)) => s,
-        _ => String::from("."),
+        Some(Expression::String(s)) => match Path::new(&s).parent() {
+            Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+            _ => PathBuf::from("."),
+        },
+        _ => PathBuf::from("."),
     };
-    let cwd = Path::new(&base);
-    use_module_wrap(alias, module_path, cwd, env, 0)
+    use_module_wrap(alias, module_path, &base, env, 0)
 }
 pub fn use_module_wrap<'a>(
     alias: &'a Option<String>,
@@ -29,29 +38,100 @@ pub fn use_module_wrap<'a>(
     use_depth: usize,
 ) -> Result<(Cow<'a, str>, Expression), RuntimeError> {
     let (module_info, parent_file) = load_module(module_path, base, env)?;
-    let mut map = module_info.functions;
+    let exports = module_info.exports;
+    // 未使用 pub 的模块导出全部函数
+    let is_pub = |name: &str| exports.as_ref().is_none_or(|e| e.contains(name));
+    let (mut map, mut private): (HashMap<_, _>, HashMap<_, _>) = module_info
+        .functions
+        .into_iter()
+        .partition(|(name, _)| is_pub(name));
 
     if !module_info.use_statements.is_empty() {
         let cwd = parent_file.parent().unwrap_or(base);
         // 递归use语句
         // let mut already = HashSet::new();
-        for (ua, up) in module_info.use_statements.iter() {
+        for u in module_info.use_statements.iter() {
             // 避免循环调用
             if MAX_USEMODE_RECURSION.with_borrow(|v| &use_depth < v) {
                 // 允许重复调用,但给出提示
                 // if !already.insert(ua){
                 //     eprintln!()
                 // }
-                let (na, np) = use_module_wrap(ua, up, cwd, env, use_depth + 1)?;
-                map.insert(na.into(), np);
+                let (na, np) = use_module_wrap(&u.alias, &u.path, cwd, env, use_depth + 1)?;
+                // 传递导入默认私有，pub use 再导出；pub use mod::{a} 只导出 a
+                for (name, value) in bind_imports(&na, np, &u.names)? {
+                    let export = u.public && (u.names.is_none() || name != na);
+                    match export {
+                        true => map.insert(name, value),
+                        false => private.insert(name, value),
+                    };
+                }
             }
         }
     }
+    if !private.is_empty() {
+        map.insert(MODULE_PRIVATE.into(), Expression::from(private));
+    }
 
     // 使用别名或模块名作为键，存储为Map
     let module_name = get_module_name_from_path(alias, module_path)?;
     Ok((module_name, Expression::from(map)))
 }
+
+/// 导入后要绑定的名字：整个模块，或 use mod::{a, b} 选中的成员
+/// 选中的成员绑定为模块调用，以便其函数体仍在模块内查找
+pub fn bind_imports(
+    module_name: &str,
+    module: Expression,
+    names: &Option<Vec<String>>,
+) -> Result<Vec<(String, Expression)>, RuntimeError> {
+    let mut binds = Vec::new();
+    if let (Some(names), Expression::HMap(members)) = (names, &module) {
+        for name in names {
+            if module_member(members, name, false).is_none() {
+                let kind = match module_member(members, name, true) {
+                    Some(_) => RuntimeErrorKind::PrivateMember(name.clone(), module_name.into()),
+                    None => RuntimeErrorKind::SymbolNotDefinedInModule(
+                        name.clone(),
+                        module_name.into(),
+                        module_name.into(),
+                    ),
+                };
+                return Err(RuntimeError::new(
+                    kind,
+                    Expression::Use(None, module_name.into(), Some(names.clone())),
+                    0,
+                ));
+            }
+            binds.push((
+                name.clone(),
+                Expression::ModuleCall(
+                    vec![module_name.into()],
+                    Rc::new(Expression::Symbol(name.clone())),
+                ),
+            ));
+        }
+    }
+    binds.push((module_name.into(), module));
+    Ok(binds)
+}
+
+/// 查找模块成员；private 为真时也查找私有成员
+pub fn module_member<'a>(
+    module: &'a HashMap<String, Expression>,
+    name: &str,
+    private: bool,
+) -> Option<&'a Expression> {
+    if name == MODULE_PRIVATE {
+        return None;
+    }
+    module
+        .get(name)
+        .or_else(|| match module.get(MODULE_PRIVATE) {
+            Some(Expression::HMap(p)) if private => p.get(name),
+            _ => None,
+        })
+}
 fn load_module(
     file_path: &str,
     cwd: &Path,
@@ -108,6 +188,10 @@ fn read_module_file(
     mod_file: &PathBuf,
     _env: &mut Environment,
 ) -> Result<ModuleInfo, RuntimeError> {
+    // 命中解析缓存时跳过读取与解析
+    if let Some(module) = ast_cache::load_module(mod_file) {
+        return Ok(module);
+    }
     // 读取并解析模块文件
     let module_content = match read_to_string(mod_file) {
         Ok(content) => content,
@@ -123,7 +207,14 @@ fn read_module_file(
 
     // 解析模块内容
     match use_script(&module_content) {
-        Ok(result) => Ok(result),
+        Ok(mut result) => {
+            // 为局部变量分配槽位
+            for func in result.functions.values_mut() {
+                *func = func.resolve_slots();
+            }
+            ast_cache::store_module(mod_file, &result);
+            Ok(result)
+        }
         Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
             let err = SyntaxError {
                 source: format!("{module_content}   ").into(),
@@ -169,7 +260,7 @@ fn get_module_name_from_path<'a>(
                 }
                 None => Err(RuntimeError::common(
                     "get filename failed".into(),
-                    Expression::Use(alias.clone(), module_path.to_string()),
+                    Expression::Use(alias.clone(), module_path.to_string(), None),
                     0,
                 )),
             }
@@ -2,13 +2,17 @@ use core::option::Option::None;
 use detached_str::Str;
 use std::{
     borrow::Cow,
-    collections::{BTreeMap, BTreeSet, HashMap},
+    cell::Cell,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     rc::Rc,
 };
 
 use crate::{
     Diagnostic, Expression, Int, MAX_SYNTAX_RECURSION, SyntaxErrorKind, Token, TokenKind,
-    expression::{CatchType, ChainCall, DestructurePattern, FileSize},
+    expression::{
+        CatchType, ChainCall, DestructurePattern, FileSize, MatchPattern, TYPE_NAMES,
+        fmt_spec::FormatSpec, record::RecordType,
+    },
     tokens::{Input, Tokens},
     with_cfm_enabled,
 };
@@ -116,6 +120,8 @@ impl PrattParser {
         // dbg!("===----prepare to prefix---===>", input, min_prec);
         let (new_input, mut lhs) = Self::parse_prefix(input, min_prec, depth)?;
         input = new_input;
+        // 调用参数顶层的 `<(` `>(` 是比较运算
+        let call_arg = min_prec == PREC_FUNC_ARG && in_call_arg(depth);
         // dbg!("=======prefix=======>", input, &lhs, min_prec);
         // opt(alt((kind(TokenKind::LineBreak), eof_slice)));
         // 2. 循环处理中缀和后缀
@@ -270,6 +276,20 @@ impl PrattParser {
                     (input, lhs) = Self::build_postfix_ast(lhs, operator.to_string(), input,depth)?;
                     // dbg!(&input, &lhs);
                 }
+                // <( >( 只在命令参数位置是进程替换；跟在值之后或位于函数参数中时为比较运算
+                TokenKind::OperatorPrefix
+                    if matches!(operator, "<" | ">")
+                        && min_prec <= PREC_COMPARISON
+                        && (call_arg
+                            || !matches!(lhs, Expression::Symbol(_) | Expression::String(_))) =>
+                {
+                    let op_info = Self::get_operator_info(operator).unwrap();
+                    input = input.skip_n(1);
+                    let (new_input, rhs) =
+                        parse_expr_or_failure(input, op_info.precedence + 1, depth + 1)?;
+                    input = new_input;
+                    lhs = Self::build_bin_ast(input, op_info, lhs, rhs)?;
+                }
                 TokenKind::Symbol
                 | TokenKind::StringLiteral
                 | TokenKind::StringRaw
@@ -396,6 +416,13 @@ impl PrattParser {
                         // let (input, expr) = Self::parse_prefix(input, prec)?;
                         Ok((input, Expression::UnaryOp(op.into(), Rc::new(expr), true)))
                     }
+                    "<" | ">" => {
+                        // 进程替换 <(cmd) >(cmd)
+                        let input = input.skip_n(1);
+                        let (input, group) =
+                            cut(|i| Self::parse_prefix(i, PREC_GROUP, depth + 1))(input)?;
+                        Ok((input, Expression::UnaryOp(op.into(), Rc::new(group), true)))
+                    }
                     ".." | "..=" => {
                         // ..b   ..b:2
                         let input = input.skip_n(1);
@@ -927,6 +954,8 @@ fn parse_control_flow(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, Synt
         "break" => parse_break(input),
         "continue" => parse_continue(input),
         "return" => parse_return(input),
+        "try" => parse_try_flow(input),
+        "defer" => parse_defer(input),
         _ => Err(nom::Err::Error(SyntaxErrorKind::NoExpression)),
     }
 }
@@ -1072,7 +1101,7 @@ fn parse_args(
             separated_list0(
                 terminated(text(","), opt(kind(TokenKind::LineBreak))),
                 |inp| {
-                    PrattParser::parse_expr_with_precedence(inp, PREC_FUNC_ARG, depth + 1)
+                    parse_call_arg(inp, depth + 1)
                     // PrattParser::parse_expr_with_precedence(inp, PREC_CMD_ARG, 0)
                 },
             ),
@@ -1083,33 +1112,76 @@ fn parse_args(
 }
 
 // 参数解析函数
+#[allow(clippy::type_complexity)]
 fn parse_param(
     input: Tokens<'_>,
-) -> IResult<Tokens<'_>, (String, Option<Expression>), SyntaxErrorKind> {
-    alt((
-        // 带默认值的参数解析分支
-        map(
-            separated_pair(
-                parse_symbol_string,
-                text("="),
-                // 限制只能解析基本类型表达式
-                cut(parse_literal),
-            ),
-            |(name, expr)| (name, Some(expr)), // 将结果包装为Some
-        ),
-        // map(preceded(text("*"), parse_symbol_string), |s| {
-        //     (s, None, true)
-        // }),
-        // 普通参数解析分支
-        map(parse_symbol_string, |s| (s, None)), // , 1+2 also match first symbol, so failed in ) parser.
-    ))(input)
+) -> IResult<Tokens<'_>, (String, Option<Expression>, Option<String>), SyntaxErrorKind> {
+    let (input, name) = parse_symbol_string(input)?; // , 1+2 also match first symbol, so failed in ) parser.
+    // 可选类型标注 name: Type
+    let (input, ty) = opt(preceded(text(":"), cut(parse_type_annotation)))(input)?;
+    // 结构体字段的默认值在定义时保存，只接受字面量
+    let (input, default) = opt(preceded(text("="), |inp| {
+        cut(parse_literal)(inp).map_err(|_| {
+            SyntaxErrorKind::failure(
+                inp.get_str_slice(),
+                "literal default value",
+                None,
+                Some("field defaults must be literals like 0, 'a' or true"),
+            )
+        })
+    }))(input)?;
+    Ok((input, (name, default, ty)))
+}
+
+// 默认值表达式，调用时求值，可引用前面的参数
+fn parse_default_value(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, SyntaxErrorKind> {
+    preceded(text("="), cut(|inp| parse_call_arg(inp, 0)))(input)
+}
+
+thread_local! {
+    /// 正在解析的调用参数所在的递归深度
+    static CALL_ARG_DEPTH: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// 调用参数与默认值：其顶层的 `<(` `>(` 是比较运算而非进程替换
+fn parse_call_arg(
+    input: Tokens<'_>,
+    depth: usize,
+) -> IResult<Tokens<'_>, Expression, SyntaxErrorKind> {
+    let saved = CALL_ARG_DEPTH.replace(Some(depth));
+    let result = PrattParser::parse_expr_with_precedence(input, PREC_FUNC_ARG, depth);
+    CALL_ARG_DEPTH.set(saved);
+    result
+}
+
+fn in_call_arg(depth: usize) -> bool {
+    CALL_ARG_DEPTH.get() == Some(depth)
+}
+
+// 类型标注：类型名
+fn parse_type_annotation(input: Tokens<'_>) -> IResult<Tokens<'_>, String, SyntaxErrorKind> {
+    parse_symbol_string(input).map_err(|_| {
+        SyntaxErrorKind::failure(
+            input.get_str_slice(),
+            "type name",
+            None,
+            Some("annotate like x: Int"),
+        )
+    })
 }
 
 // 函数参数列表解析
 #[allow(clippy::type_complexity)]
 fn parse_param_list(
     input: Tokens<'_>,
-) -> IResult<Tokens<'_>, (Vec<(String, Option<Expression>)>, Option<String>), SyntaxErrorKind> {
+) -> IResult<
+    Tokens<'_>,
+    (
+        Vec<(DestructurePattern, Option<Expression>, Option<String>)>,
+        Option<String>,
+    ),
+    SyntaxErrorKind,
+> {
     let (input, _) = cut(text("("))(input).map_err(|_| {
         SyntaxErrorKind::failure(
             input.get_str_slice(),
@@ -1119,9 +1191,23 @@ fn parse_param_list(
         )
     })?;
     let (input, _) = opt(kind(TokenKind::LineBreak))(input)?; //允许可选回车
+    // 参数为变量名或解构模式，解构在调用时绑定
     let (input, params) = separated_list0(
         terminated(text(","), opt(kind(TokenKind::LineBreak))),
-        parse_param,
+        alt((
+            map(
+                pair(parse_nested_destructure, opt(parse_default_value)),
+                |(ps, d)| (DestructurePattern::Nested(None, ps), d, None),
+            ),
+            map(
+                tuple((
+                    parse_symbol_string, // , 1+2 also match first symbol, so failed in ) parser.
+                    opt(preceded(text(":"), cut(parse_type_annotation))),
+                    opt(parse_default_value),
+                )),
+                |(n, t, d)| (DestructurePattern::Identifier(n), d, t),
+            ),
+        )),
     )(input)?;
     // let mut params = vec![];
     // let mut param_collector: Option<String> = None;
@@ -1210,6 +1296,8 @@ fn parse_fn_declare(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, Syntax
         )
     })?;
     let (input, (params, param_collector)) = cut(parse_param_list)(input)?; // 使用新参数列表
+    // 可选返回值类型 -> Type
+    let (input, ret) = opt(preceded(text("->"), cut(parse_type_annotation)))(input)?;
     let (input, _) = opt(kind(TokenKind::LineBreak))(input)?; //允许可选回车
 
     // 无函数体应报错
@@ -1246,7 +1334,52 @@ fn parse_fn_declare(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, Syntax
     };
     Ok((
         input,
-        Expression::Function(name, params, param_collector, last_body, decos),
+        Expression::Function(name, params, param_collector, last_body, decos, ret),
+    ))
+}
+// 结构体定义 struct Point { x, y: Int = 0, fn dist(self, q) {...} }
+fn parse_struct_def(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, SyntaxErrorKind> {
+    enum Member {
+        Field((String, Option<Expression>, Option<String>)),
+        Method(Expression),
+    }
+    // struct 不是保留字：只有 `struct Name {` 才是定义，其余仍作普通符号
+    let (input, _) = text("struct")(input)?;
+    let (input, name) = parse_symbol_string(input)?;
+    let (input, _) = terminated(text("{"), many0(kind(TokenKind::LineBreak)))(input)?;
+    // 字段与方法以逗号或换行分隔
+    let (input, members) = separated_list0(
+        alt((
+            map(pair(text(","), many0(kind(TokenKind::LineBreak))), |_| ()),
+            map(many1(kind(TokenKind::LineBreak)), |_| ()),
+        )),
+        alt((
+            map(parse_fn_declare, Member::Method),
+            map(parse_param, Member::Field),
+        )),
+    )(input)?;
+    let (input, _) = many0(kind(TokenKind::LineBreak))(input)?;
+    let (input, _) = cut(text_close("}"))(input)?;
+
+    let mut fields = vec![];
+    let mut methods = BTreeMap::new();
+    for member in members {
+        match member {
+            Member::Field(field) => fields.push(field),
+            Member::Method(func) => {
+                if let Expression::Function(fname, ..) = &func {
+                    methods.insert(fname.clone(), func);
+                }
+            }
+        }
+    }
+    Ok((
+        input,
+        Expression::StructDef(Rc::new(RecordType {
+            name,
+            fields,
+            methods,
+        })),
     ))
 }
 // return statement
@@ -1266,6 +1399,37 @@ fn parse_break(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, SyntaxError
         Expression::Break(Rc::new(expr.unwrap_or(Expression::None))),
     ))
 }
+// try { } catch e { } finally { }
+fn parse_try_flow(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, SyntaxErrorKind> {
+    let (input, _) = text("try")(input)?;
+    let (input, body) = cut(parse_block)(input)?;
+    let (input, catch) = opt(preceded(
+        text("catch"),
+        cut(pair(opt(parse_symbol_string), parse_block)),
+    ))(input)?;
+    let (input, finally) = opt(preceded(text("finally"), cut(parse_block)))(input)?;
+    if catch.is_none() && finally.is_none() {
+        return Err(SyntaxErrorKind::failure(
+            input.get_str_slice(),
+            "catch or finally",
+            input.first().map(|t| t.text(input).to_string()),
+            Some("add `catch e { ... }` or `finally { ... }` after the try block"),
+        ));
+    }
+    let (var, handler) = match catch {
+        Some((var, handler)) => (var, Some(Rc::new(handler))),
+        None => (None, None),
+    };
+    Ok((
+        input,
+        Expression::Try(Rc::new(body), var, handler, finally.map(Rc::new)),
+    ))
+}
+fn parse_defer(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, SyntaxErrorKind> {
+    let (input, _) = text("defer")(input)?;
+    let (input, expr) = cut(parse_expr)(input)?;
+    Ok((input, Expression::Defer(Rc::new(expr))))
+}
 fn parse_continue(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, SyntaxErrorKind> {
     let (input, _) = text("continue")(input)?;
     Ok((input, Expression::Continue))
@@ -1557,12 +1721,63 @@ fn find_matching_brace(s: &str, start: usize) -> Option<usize> {
 fn parse_brace_segment(template: &str, start: usize, prefix: &str) -> Option<(Expression, usize)> {
     let end = find_matching_brace(template, start)?;
     let inner = &template[start..end];
+    // {expr:spec} 交给 string.fmt 格式化
+    if let Some((code, spec)) = split_format_spec(inner)
+        && let Ok(expr) = parse_script(code)
+    {
+        let call = ChainCall {
+            method: "fmt".into(),
+            args: vec![expr, Expression::String(spec.into())],
+        };
+        let fmt = Expression::Chain(Rc::new(Expression::Symbol("string".into())), vec![call]);
+        return Some((fmt, end + 1));
+    }
     let expr = match parse_script(inner) {
         Ok(expr) => expr,
         Err(_) => Expression::String(format!("{prefix}{{{inner}}}")),
     };
     Some((expr, end + 1)) // end + 1 跳过 '}'
 }
+/// 在顶层的 ':' 处拆出格式说明符，如 `size:>10`、`pct:.2`
+/// 冒号前不能是空白，以免误拆 `a ? b : c`；`::` 不拆；
+/// 尚未配对的三元 '?' 之后的 ':' 属于三元表达式，如 `x ?a:5`
+fn split_format_spec(inner: &str) -> Option<(&str, &str)> {
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut prev = ' ';
+    // 未配对的三元 '?' 个数
+    let mut ternary = 0usize;
+    let mut skip = false;
+    for (i, c) in inner.char_indices() {
+        if skip {
+            // `?:` `??` 等运算符的第二个字符
+            skip = false;
+            prev = c;
+            continue;
+        }
+        match (quote, c) {
+            (Some(q), c) if c == q && prev != '\\' => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '(' | '[' | '{') => depth += 1,
+            (None, ')' | ']' | '}') => depth = depth.saturating_sub(1),
+            (None, '?') if depth == 0 => match inner[i + 1..].chars().next() {
+                Some('+' | '.' | '?' | '>' | '!' | ':' | '~') => skip = true,
+                _ => ternary += 1,
+            },
+            (None, ':') if depth == 0 && ternary > 0 => ternary -= 1,
+            (None, ':') if depth == 0 && !prev.is_whitespace() && prev != ':' => {
+                let (code, spec) = (&inner[..i], &inner[i + 1..]);
+                if !code.is_empty() && !spec.starts_with(':') && FormatSpec::parse(spec).is_ok() {
+                    return Some((code, spec));
+                }
+            }
+            _ => {}
+        }
+        prev = c;
+    }
+    None
+}
 /// 将模板字符串内容分解为表达式片段列表
 /// 支持三种插值语法：
 ///   $var      — 简单变量查找（直接 env.get，不走 parse）
@@ -1942,11 +2157,15 @@ fn parse_bset(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, SyntaxErrorK
 #[inline]
 fn parse_integer(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, SyntaxErrorKind> {
     let (input, num) = kind(TokenKind::IntegerLiteral)(input)?;
-    let num = num
-        .to_str(input.str)
-        .parse::<Int>()
-        .map_err(|e| SyntaxErrorKind::failure(num, "Integer", Some(format!("error: {e}")), None))?;
-    Ok((input, Expression::Integer(num)))
+    let text = num.to_str(input.str);
+    // 超出 i64 的字面量解析为大整数
+    let expr = match text.parse::<Int>() {
+        Ok(n) => Expression::Integer(n),
+        Err(e) => Expression::parse_int(text).ok_or_else(|| {
+            SyntaxErrorKind::failure(num, "Integer", Some(format!("error: {e}")), None)
+        })?,
+    };
+    Ok((input, expr))
 }
 
 fn parse_float(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, SyntaxErrorKind> {
@@ -2178,7 +2397,12 @@ fn parse_statement(mut input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, Syn
         .ok_or_else(|| nom::Err::Error(SyntaxErrorKind::NoExpression))?;
     match keyword {
         "fn" => parse_fn_declare(input), // 函数声明（仅语句级）这里的作用是允许函数嵌套
+        "struct" => alt((parse_struct_def, parse_single_expr))(input), // 结构体定义
         "use" => parse_use_statement(input), //允许语句中间按需use
+        "pub" => alt((
+            preceded(text("pub"), alt((parse_fn_declare, parse_use_statement))),
+            parse_single_expr,
+        ))(input), // pub 仅在模块中有意义
         // 1.声明语句
         "let" => parse_lets(input),
         "set" => parse_set(input),
@@ -2380,14 +2604,21 @@ fn parse_loop_flow(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, SyntaxE
 fn parse_for_flow(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, SyntaxErrorKind> {
     let (input, _) = text("for")(input)?;
     let (input, pat_ind) = opt(terminated(parse_symbol_string, text(",")))(input)?;
-    let (input, pat_var) = cut(parse_symbol_string)(input).map_err(|_| {
-        SyntaxErrorKind::failure(
-            input.get_str_slice(),
-            "loop variable",
-            input.first().map(|t| t.text(input).to_string()),
-            Some("add a variable name"),
-        )
-    })?;
+    let (input, pattern) = opt(parse_nested_destructure)(input)?;
+    let (input, pat_var) = match pattern {
+        Some(ref ps) => (
+            input,
+            DestructurePattern::Nested(None, ps.clone()).to_string(),
+        ),
+        None => cut(parse_symbol_string)(input).map_err(|_| {
+            SyntaxErrorKind::failure(
+                input.get_str_slice(),
+                "loop variable",
+                input.first().map(|t| t.text(input).to_string()),
+                Some("add a variable name"),
+            )
+        })?,
+    };
     let (input, _) = cut(text("in"))(input)?;
     let (input, iterable) = cut(parse_expr)(input).map_err(|_| {
         SyntaxErrorKind::failure(
@@ -2398,6 +2629,10 @@ fn parse_for_flow(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, SyntaxEr
         )
     })?;
     let (input, body) = cut(parse_block)(input)?;
+    let body = match pattern {
+        Some(pattern) => prepend_destructure(body, vec![(pattern, pat_var.clone())]),
+        None => body,
+    };
 
     Ok((
         input,
@@ -2508,12 +2743,19 @@ fn parse_array_destructure(
         cut(separated_list1(
             text(","),
             alt((
-                map(preceded(text("*"), cut(parse_symbol_string)), |s| {
-                    DestructurePattern::Rest(s)
-                }),
-                map(cut(parse_symbol_string), |s| {
-                    DestructurePattern::Identifier(s)
-                }),
+                map(
+                    preceded(alt((text("*"), text("..."))), cut(parse_symbol_string)),
+                    DestructurePattern::Rest,
+                ),
+                // [[a, b], {c}] 嵌套语法
+                map(
+                    pair(parse_nested_destructure, parse_destructure_default),
+                    |(ps, d)| with_destructure_default(DestructurePattern::Nested(None, ps), d),
+                ),
+                map(
+                    pair(cut(parse_symbol_string), parse_destructure_default),
+                    |(s, d)| with_destructure_default(DestructurePattern::Identifier(s), d),
+                ),
             )),
         )),
         cut(text_close("]")),
@@ -2528,21 +2770,89 @@ fn parse_map_destructure(
         cut(separated_list1(
             text(","),
             alt((
+                // {key: {a, b}} 嵌套语法
+                map(
+                    tuple((
+                        parse_symbol_string,
+                        text(":"),
+                        parse_nested_destructure,
+                        parse_destructure_default,
+                    )),
+                    |(k, _, ps, d)| {
+                        with_destructure_default(DestructurePattern::Nested(Some(k), ps), d)
+                    },
+                ),
                 // {key: newName} 语法
                 map(
-                    separated_pair(parse_symbol_string, text(":"), cut(parse_symbol_string)),
-                    |(i, n)| DestructurePattern::Renamed((i, n)),
+                    tuple((
+                        parse_symbol_string,
+                        text(":"),
+                        cut(parse_symbol_string),
+                        parse_destructure_default,
+                    )),
+                    |(k, _, n, d)| with_destructure_default(DestructurePattern::Renamed((k, n)), d),
                 ),
                 // {key} 简写语法
-                map(cut(parse_symbol_string), |s| {
-                    DestructurePattern::Identifier(s)
-                }),
+                map(
+                    pair(cut(parse_symbol_string), parse_destructure_default),
+                    |(s, d)| with_destructure_default(DestructurePattern::Identifier(s), d),
+                ),
             )),
         )),
         cut(text_close("}")),
     )(input)
 }
 
+fn parse_nested_destructure(
+    input: Tokens<'_>,
+) -> IResult<Tokens<'_>, Vec<DestructurePattern>, SyntaxErrorKind> {
+    alt((parse_array_destructure, parse_map_destructure))(input)
+}
+
+/// 解构默认值，与函数参数一样在值缺失时求值
+fn parse_destructure_default(
+    input: Tokens<'_>,
+) -> IResult<Tokens<'_>, Option<Expression>, SyntaxErrorKind> {
+    opt(parse_default_value)(input)
+}
+
+fn with_destructure_default(
+    pattern: DestructurePattern,
+    default: Option<Expression>,
+) -> DestructurePattern {
+    match default {
+        Some(d) => DestructurePattern::Default(Box::new(pattern), Rc::new(d)),
+        None => pattern,
+    }
+}
+
+/// 将解构模式展开为对循环变量的解构赋值，插入到块的开头。
+/// 循环变量以模式文本命名，不会与用户变量重名
+fn prepend_destructure(
+    body: Expression,
+    bindings: Vec<(Vec<DestructurePattern>, String)>,
+) -> Expression {
+    if bindings.is_empty() {
+        return body;
+    }
+    let mut stmts: Vec<Expression> = bindings
+        .into_iter()
+        .map(|(pattern, tmp)| {
+            Expression::DestructureAssign(pattern, Rc::new(Expression::Variable(tmp)))
+        })
+        .collect();
+    match body {
+        Expression::Block(b) => {
+            stmts.extend(b.iter().cloned());
+            Expression::Block(Rc::new(stmts))
+        }
+        other => {
+            stmts.push(other);
+            Expression::Block(Rc::new(stmts))
+        }
+    }
+}
+
 // 别名解析逻辑
 fn parse_alias(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, SyntaxErrorKind> {
     let (input, _) = text("alias")(input)?;
@@ -2692,31 +3002,140 @@ fn parse_custom_postfix_operator(
     })(input)
 }
 // 模式匹配解析（简化示例）
-fn parse_pattern(input: Tokens<'_>) -> IResult<Tokens<'_>, Vec<Expression>, SyntaxErrorKind> {
+fn parse_pattern(input: Tokens<'_>) -> IResult<Tokens<'_>, Vec<MatchPattern>, SyntaxErrorKind> {
     let (input, pat) = separated_list1(
         text(","),
         alt((
-            parse_num_range,
-            parse_string,
-            parse_string_raw,
-            parse_value_symbol,
-            parse_symbol,
-            parse_regex,
-            parse_time,
-            parse_integer,
-            parse_float,
+            parse_list_pattern,
+            parse_map_pattern,
+            parse_type_pattern,
+            map(
+                alt((
+                    parse_num_range,
+                    parse_string,
+                    parse_string_raw,
+                    parse_value_symbol,
+                    parse_symbol,
+                    parse_regex,
+                    parse_time,
+                    parse_integer,
+                    parse_float,
+                )),
+                MatchPattern::Value,
+            ),
         )),
     )(input)
-    .map_err(|_| {
-        SyntaxErrorKind::expected(
+    .map_err(|e| match e {
+        nom::Err::Failure(inner) => nom::Err::Failure(inner),
+        _ => SyntaxErrorKind::expected(
             input.get_str_slice(),
             "match pattern",
             input.first().map(|t| t.text(input).to_string()),
-            Some("add a string/symbol/regex/number/range/time"),
-        )
+            Some("add a string/symbol/regex/number/range/time/type or [..]/{..} shape"),
+        ),
     })?;
+    // 守卫作用于整个分支；带守卫的单独符号绑定变量
+    let (input, guard) = opt(preceded(text("if"), cut(parse_expr)))(input)?;
+    let pat = match guard {
+        Some(cond) => {
+            let cond = Rc::new(cond);
+            pat.into_iter()
+                .map(|p| {
+                    let p = match p {
+                        MatchPattern::Value(Expression::Symbol(s)) => MatchPattern::Bind(s),
+                        p => p,
+                    };
+                    MatchPattern::Guard(Box::new(p), cond.clone())
+                })
+                .collect()
+        }
+        None => pat,
+    };
     Ok((input, pat))
 }
+
+/// 结构模式中的元素：符号绑定变量，`_` 匹配任意值
+fn parse_sub_pattern(input: Tokens<'_>) -> IResult<Tokens<'_>, MatchPattern, SyntaxErrorKind> {
+    alt((
+        parse_list_pattern,
+        parse_map_pattern,
+        parse_type_pattern,
+        map(parse_symbol_string, MatchPattern::Bind),
+        map(
+            alt((
+                parse_num_range,
+                parse_string,
+                parse_string_raw,
+                parse_value_symbol,
+                parse_regex,
+                parse_time,
+                parse_integer,
+                parse_float,
+            )),
+            MatchPattern::Value,
+        ),
+    ))(input)
+}
+
+// `_: Int` 类型测试，`x: Int` 同时绑定变量；裸类型名仍按符号值匹配
+fn parse_type_pattern(input: Tokens<'_>) -> IResult<Tokens<'_>, MatchPattern, SyntaxErrorKind> {
+    let (input, bind) = terminated(
+        alt((map(parse_symbol_string, Some), value(None, text("_")))),
+        text(":"),
+    )(input)?;
+    let (input, ty) = cut(|inp| {
+        verify(parse_symbol_string, |s: &String| {
+            TYPE_NAMES.contains(&s.as_str())
+        })(inp)
+        .map_err(|_: nom::Err<SyntaxErrorKind>| {
+            SyntaxErrorKind::failure(
+                inp.get_str_slice(),
+                "type name",
+                inp.first().map(|t| t.text(inp).to_string()),
+                Some("type patterns look like `_: Int` or `x: Map`"),
+            )
+        })
+    })(input)?;
+    Ok((input, MatchPattern::Type(bind, ty)))
+}
+
+// [a, b, ...rest]
+fn parse_list_pattern(input: Tokens<'_>) -> IResult<Tokens<'_>, MatchPattern, SyntaxErrorKind> {
+    let (input, _) = text("[")(input)?;
+    let (input, items) = separated_list0(text(","), parse_sub_pattern)(input)?;
+    let (input, rest) = opt(preceded(
+        pair(
+            cond(!items.is_empty(), text(",")),
+            alt((text("..."), text("*"))),
+        ),
+        map(opt(parse_symbol_string), |s| {
+            Box::new(s.map_or(MatchPattern::Value(Expression::Blank), MatchPattern::Bind))
+        }),
+    ))(input)?;
+    let (input, _) = cut(text_close("]"))(input)?;
+    Ok((input, MatchPattern::List(items, rest)))
+}
+
+// {status: 200, body}
+fn parse_map_pattern(input: Tokens<'_>) -> IResult<Tokens<'_>, MatchPattern, SyntaxErrorKind> {
+    let (input, entries) = delimited(
+        text("{"),
+        separated_list1(
+            text(","),
+            alt((
+                separated_pair(
+                    alt((parse_symbol_string, map(parse_string, |e| e.to_string()))),
+                    text(":"),
+                    cut(parse_sub_pattern),
+                ),
+                map(parse_symbol_string, |k| (k.clone(), MatchPattern::Bind(k))),
+            )),
+        ),
+        cut(text_close("}")),
+    )(input)?;
+    Ok((input, MatchPattern::Map(entries)))
+}
+
 // only used for match arm
 fn parse_num_range(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, SyntaxErrorKind> {
     let (input, start) = alt((parse_integer, parse_blank))(input)?;
@@ -2834,29 +3253,54 @@ fn parse_index(
 
 #[derive(Debug, Clone)]
 pub struct ModuleInfo {
-    pub use_statements: Vec<(Option<String>, String)>, // (path, alias)
+    pub use_statements: Vec<ModuleUse>,
     pub functions: HashMap<String, Expression>,
+    pub exports: Option<HashSet<String>>, // pub 标记的函数；None 表示未使用 pub，函数全部导出
+}
+
+#[derive(Debug, Clone)]
+pub struct ModuleUse {
+    pub alias: Option<String>,
+    pub path: String,
+    pub names: Option<Vec<String>>, // use mod::{a, b}
+    pub public: bool,               // pub use 再导出
 }
 
 // 优化的模块解析器 - 只解析 fn 和 use
 fn parse_module_selective(input: Tokens<'_>) -> IResult<Tokens<'_>, ModuleInfo, SyntaxErrorKind> {
     let mut use_statements = Vec::new();
     let mut functions = HashMap::new();
+    let mut exports: Option<HashSet<String>> = None;
     let mut remaining = input;
 
     while !remaining.is_empty() {
+        // pub 标记导出
+        let (rest, public) = opt(text("pub"))(remaining)?;
+        let public = public.is_some();
+        if public {
+            exports.get_or_insert_with(HashSet::new);
+        }
+
         // 尝试解析 use 语句
-        if let Ok((rest, use_stmt)) = parse_use_statement(remaining) {
-            if let Expression::Use(alias, path) = use_stmt {
-                use_statements.push((alias, path));
+        if let Ok((rest, use_stmt)) = parse_use_statement(rest) {
+            if let Expression::Use(alias, path, names) = use_stmt {
+                use_statements.push(ModuleUse {
+                    alias,
+                    path,
+                    names,
+                    public,
+                });
             }
             remaining = rest;
             continue;
         }
 
         // 尝试解析函数声明
-        if let Ok((rest, func)) = parse_fn_declare(remaining) {
+        if let Ok((rest, func)) = parse_fn_declare(rest) {
             if let Expression::Function(name, ..) = &func {
+                if let Some(exports) = exports.as_mut().filter(|_| public) {
+                    exports.insert(name.clone());
+                }
                 functions.insert(name.clone(), func);
                 remaining = rest;
             }
@@ -2878,6 +3322,7 @@ fn parse_module_selective(input: Tokens<'_>) -> IResult<Tokens<'_>, ModuleInfo,
         ModuleInfo {
             use_statements,
             functions,
+            exports,
         },
     ))
 }
@@ -2888,8 +3333,22 @@ fn parse_use_statement(input: Tokens<'_>) -> IResult<Tokens<'_>, Expression, Syn
         parse_string_common(input, TokenKind::StringRaw, false, false)
             .map(|(tk, s)| (tk, s.into_owned()))
     })))(input)?;
-    let (input, alias) = opt(preceded(text("as"), parse_symbol_string))(input)?;
+    // 选择性导入 use mod::{a, b}
+    let (input, names) = opt(preceded(
+        pair(text("::"), text("{")),
+        cut(terminated(
+            separated_list1(
+                terminated(text(","), opt(kind(TokenKind::LineBreak))),
+                parse_symbol_string,
+            ),
+            text_close("}"),
+        )),
+    ))(input)?;
+    let (input, alias) = match names {
+        Some(_) => (input, None),
+        None => opt(preceded(text("as"), parse_symbol_string))(input)?,
+    };
 
     // 暂时创建空环境，后续会被替换
-    Ok((input, Expression::Use(alias, module_path)))
+    Ok((input, Expression::Use(alias, module_path, names)))
 }
@@ -0,0 +1,178 @@
+// 脚本性能分析：--prof 时统计用户函数、内置函数与外部命令的调用次数与耗时，
+// 退出时按自身耗时输出报表，可另写出供火焰图工具使用的 folded-stack 文件
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Kind {
+    Function,
+    Builtin,
+    Command,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Function => "fn",
+            Self::Builtin => "builtin",
+            Self::Command => "cmd",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Stat {
+    calls: u64,
+    total: Duration,
+    self_time: Duration,
+}
+
+struct Frame {
+    key: (Kind, String),
+    start: Instant,
+    children: Duration,
+}
+
+#[derive(Default)]
+pub(crate) struct Profiler {
+    stack: Vec<Frame>,
+    stats: HashMap<(Kind, String), Stat>,
+    // 调用栈路径 -> 自身耗时
+    folded: HashMap<String, Duration>,
+    folded_path: Option<PathBuf>,
+}
+
+thread_local! {
+    static PROFILER: RefCell<Option<Profiler>> = const { RefCell::new(None) };
+}
+
+/// 开启统计；给出 folded 路径时退出时另写 folded-stack 文件
+pub fn enable(folded: Option<PathBuf>) {
+    PROFILER.with_borrow_mut(|p| {
+        *p = Some(Profiler {
+            folded_path: folded,
+            ..Default::default()
+        })
+    });
+}
+
+/// 调用计时守卫，离开作用域时（含出错提前返回）结束计时
+pub struct Span(());
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        PROFILER.with_borrow_mut(|p| {
+            if let Some(p) = p {
+                p.exit();
+            }
+        });
+    }
+}
+
+/// 开始一次调用计时；未开启时返回 None，名字只在开启时生成
+#[inline]
+pub fn span(kind: Kind, name: impl FnOnce() -> String) -> Option<Span> {
+    PROFILER.with_borrow_mut(|p| {
+        let p = p.as_mut()?;
+        p.stack.push(Frame {
+            key: (kind, name()),
+            start: Instant::now(),
+            children: Duration::ZERO,
+        });
+        Some(Span(()))
+    })
+}
+
+/// 结束统计，报表输出到 stderr，并写出 folded-stack 文件
+pub fn report() {
+    let Some(mut p) = take() else {
+        return;
+    };
+    eprint!("{}", p.table());
+    if let Some(path) = p.folded_path.take()
+        && let Err(e) = std::fs::write(&path, p.folded_lines())
+    {
+        eprintln!("failed to write profile `{}`: {e}", path.display());
+    }
+}
+
+/// 取出统计结果并关闭统计，仍在执行中的调用（如 exit 退出）计时到此刻
+pub(crate) fn take() -> Option<Profiler> {
+    let mut p = PROFILER.with_borrow_mut(|p| p.take())?;
+    while !p.stack.is_empty() {
+        p.exit();
+    }
+    Some(p)
+}
+
+impl Profiler {
+    fn exit(&mut self) {
+        let Some(frame) = self.stack.pop() else {
+            return;
+        };
+        let total = frame.start.elapsed();
+        let self_time = total.saturating_sub(frame.children);
+        if let Some(parent) = self.stack.last_mut() {
+            parent.children += total;
+        }
+
+        if self.folded_path.is_some() {
+            let mut path = String::new();
+            for (kind, name) in self.stack.iter().map(|f| &f.key).chain([&frame.key]) {
+                if !path.is_empty() {
+                    path.push(';');
+                }
+                let _ = write!(path, "{}:{}", kind.as_str(), name);
+            }
+            *self.folded.entry(path).or_default() += self_time;
+        }
+
+        // 递归调用只在最外层计入总耗时，避免重复累计
+        let outermost = !self.stack.iter().any(|f| f.key == frame.key);
+        let stat = self.stats.entry(frame.key).or_default();
+        stat.calls += 1;
+        stat.self_time += self_time;
+        if outermost {
+            stat.total += total;
+        }
+    }
+
+    /// 按自身耗时降序排列的报表
+    pub(crate) fn table(&self) -> String {
+        let mut rows = self.stats.iter().collect::<Vec<_>>();
+        rows.sort_by(|(ka, a), (kb, b)| b.self_time.cmp(&a.self_time).then(ka.cmp(kb)));
+
+        let ms = |d: &Duration| format!("{:.3}", d.as_secs_f64() * 1000.0);
+        let mut out = format!(
+            "{:>10} {:>12} {:>12}  {:<7}  name\n",
+            "calls", "self(ms)", "total(ms)", "kind"
+        );
+        for ((kind, name), stat) in rows {
+            let _ = writeln!(
+                out,
+                "{:>10} {:>12} {:>12}  {:<7}  {}",
+                stat.calls,
+                ms(&stat.self_time),
+                ms(&stat.total),
+                kind.as_str(),
+                name
+            );
+        }
+        out
+    }
+
+    /// folded-stack 格式：`fn:main;builtin:print 123`，数值为自身耗时（微秒）
+    pub(crate) fn folded_lines(&self) -> String {
+        let mut lines = self
+            .folded
+            .iter()
+            .map(|(path, d)| format!("{path} {}\n", d.as_micros()))
+            .collect::<Vec<_>>();
+        lines.sort();
+        lines.concat()
+    }
+}
@@ -206,6 +206,8 @@ impl Hinter for LumeHinter {
 pub fn run_repl(env: &mut Environment) {
     // 安装全局 SIGINT 处理器：仅设置标志，不杀死 lume
     childman::install_sigint_handler();
+    // 作业控制：独立进程组并占有终端
+    childman::init_job_control();
 
     match env.get("LUME_WELCOME") {
         Some(wel) => {
@@ -328,14 +330,6 @@ pub fn run_repl(env: &mut Environment) {
         theme: theme_merged,
     }));
 
-    // Set up hinter (hint from command history)
-    // let hint_theme = theme_merged.clone();
-    editor.set_hinter(Box::new(LumeHinter {
-        hinter: Some(Box::new(move |line: &str, pos: usize| {
-            hint_for_line(line, pos)
-        })),
-    }));
-
     // Editor theme from LUME_EDITOR_THEME config
     if let Some(Expression::Map(theme_map)) = env.get("LUME_EDITOR_THEME") {
         let mut theme = EditorTheme::default();
@@ -391,6 +385,15 @@ pub fn run_repl(env: &mut Environment) {
     // can fork a live snapshot of the current environment at hotkey time.
     let shared_env = Rc::new(Mutex::new(env.clone()));
 
+    // Set up hinter (hint from command history)
+    // let hint_theme = theme_merged.clone();
+    let hint_env = shared_env.clone();
+    editor.set_hinter(Box::new(LumeHinter {
+        hinter: Some(Box::new(move |line: &str, pos: usize| {
+            hint_for_line(line, pos).or_else(|| hint_for_fn(line, pos, &*hint_env.lock().ok()?))
+        })),
+    }));
+
     // Custom hotkeys LUME_HOT_BINDINGS
     let hotkey_bindings = env.get("LUME_HOT_BINDINGS");
     env.undefine("LUME_HOT_BINDINGS");
@@ -473,6 +476,8 @@ pub fn run_repl(env: &mut Environment) {
     env.undefine("LUME_SLASH_MENU");
     // =======main loop=======
     loop {
+        // 报告已结束的后台作业
+        childman::report_jobs();
         let prompt = pe.get_prompt();
 
         let line = match editor.readline(&prompt) {
@@ -628,6 +633,8 @@ pub fn run_repl(env: &mut Environment) {
             }
         }
 
+        // 确保终端回到 shell 手中
+        childman::reclaim_terminal();
         // 检查命令执行期间是否收到 SIGINT（Ctrl+C）
         if childman::check_and_clear_sigint() {
             println!("^C");
@@ -722,6 +729,32 @@ fn hint_for_line(line: &str, pos: usize) -> Option<String> {
     None
 }
 
+/// 自定义函数的签名提示，含类型标注
+fn hint_for_fn(line: &str, pos: usize, env: &Environment) -> Option<String> {
+    let prefix = &line[..pos];
+    let segment = &prefix[find_command_pos(prefix)..];
+    let name = segment.trim_end_matches(['(', ' ']);
+    if name.is_empty() {
+        return None;
+    }
+    let mut fns = env
+        .get_bindings_map()
+        .into_iter()
+        .filter(|(f, v)| f.starts_with(name) && matches!(v, Expression::Function(..)))
+        .collect::<Vec<_>>();
+    fns.sort_by_key(|(f, _)| f.len());
+    let (f, func) = fns.into_iter().next()?;
+    let sig = func.fn_signature()?;
+    let tip = if segment.ends_with('(') {
+        format!("{f}(\0{}", &sig[1..])
+    } else {
+        format!("{f} \0{sig}")
+    };
+    tip.get(segment.len()..)
+        .filter(|suffix| !suffix.is_empty())
+        .map(|suffix| suffix.to_string())
+}
+
 fn parse_hot_key(modifier_str: &str, key_char: char) -> KeyEvent {
     match modifier_str {
         "CTRL_ALT" => KeyEvent::CtrlAlt(key_char),
@@ -1,6 +1,6 @@
 use lumesh::runtime::run_file;
 use lumesh::{Environment, Expression, VERSION, set_print_direct};
-use lumesh::{parse_and_eval, set_strict_enabled};
+use lumesh::{parse_and_eval, profiler, set_ast_cache_enabled, set_strict_enabled};
 use std::path::PathBuf;
 // use std::path::PathBuf;
 
@@ -14,6 +14,9 @@ fn main() {
     let mut script_args = Vec::new(); // 存储脚本参数
     let mut is_command_mode = false; // 是否处于 `-c` 模式
     let mut is_script_mode = false; // 是否处于脚本模式
+    let mut profile = false; // 是否统计耗时
+    let mut profile_folded = None; // folded-stack 输出文件
+    let mut is_folded_arg = false; // 下一个参数为 folded-stack 输出文件
     let mut env = Environment::new();
 
     // 判断是否为登录 shell
@@ -30,7 +33,9 @@ fn main() {
 
     if args.len() <= 1 {
         println!("This is Lume Script Executor. Version: {}", VERSION);
-        println!("Usage:\n\tlume-se <file-name>\n\tlume-se -c <cmd>");
+        println!(
+            "Usage:\n\tlume-se [--no-cache] [--prof] [--prof-folded <out-file>] <file-name>\n\tlume-se -c <cmd>"
+        );
     }
     // 如果不是登录 shell，加载环境变量
     // if !is_login_shell {
@@ -47,6 +52,17 @@ fn main() {
         } else if arg == "--" {
             // 遇到 `--`，切换到脚本参数模式
             is_script_mode = true;
+        } else if is_folded_arg {
+            profile_folded = Some(PathBuf::from(arg));
+            is_folded_arg = false;
+        } else if arg == "--no-cache" && file.is_none() {
+            // 跳过解析缓存
+            set_ast_cache_enabled(false);
+        } else if arg == "--prof" && file.is_none() {
+            // 退出时输出耗时报表
+            profile = true;
+        } else if arg == "--prof-folded" && file.is_none() {
+            is_folded_arg = true;
         } else if arg == "-c" {
             // 处理 `-c` 参数
             is_command_mode = true;
@@ -84,6 +100,10 @@ fn main() {
         ),
     );
 
+    if profile || profile_folded.is_some() {
+        profiler::enable(profile_folded);
+    }
+
     // run
     if let Some(cmd_part) = cmd {
         parse_and_eval(&cmd_part.join(" "), &mut runner_env);
@@ -91,4 +111,6 @@ fn main() {
         let pathbf = PathBuf::from(file_path);
         run_file(pathbf, &mut runner_env);
     }
+    lumesh::libs::run_exit_trap(&mut runner_env);
+    profiler::report();
 }
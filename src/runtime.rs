@@ -1,3 +1,4 @@
+use crate::ast_cache;
 use crate::libs::pretty_printer;
 use crate::{CFM_ENABLED, set_cfm_enabled, set_print_direct};
 
@@ -8,7 +9,7 @@ use crate::{SyntaxErrorKind, parse_script};
 use std::collections::HashSet;
 use std::fs::{create_dir, read_to_string, write};
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub fn run_file(pb: PathBuf, env: &mut Environment) -> bool {
     match read_to_string(pb.clone()) {
@@ -17,7 +18,10 @@ pub fn run_file(pb: PathBuf, env: &mut Environment) -> bool {
                 "SCRIPT",
                 Expression::String(pb.to_string_lossy().to_string()),
             );
-            parse_and_eval(&prelude, env)
+            if prelude.is_empty() {
+                return true;
+            }
+            eval_parsed(parse_file(&pb, &prelude), env)
         }
         Err(e) => {
             eprintln!(
@@ -30,6 +34,16 @@ pub fn run_file(pb: PathBuf, env: &mut Environment) -> bool {
     }
 }
 
+/// 优先读取解析缓存，未命中时解析并写入缓存
+fn parse_file(pb: &Path, text: &str) -> Result<Expression, SyntaxError> {
+    if let Some(expr) = ast_cache::load_script(pb) {
+        return Ok(expr);
+    }
+    let parsed = parse_with_mode(text)?;
+    ast_cache::store_script(pb, &parsed);
+    Ok(parsed)
+}
+
 pub fn parse_with_mode(text: &str) -> Result<Expression, SyntaxError> {
     let temp_cfm = if text.starts_with(">") && CFM_ENABLED.with_borrow(|cfm| cfm == &false) {
         set_cfm_enabled(true);
@@ -46,7 +60,8 @@ pub fn parse_with_mode(text: &str) -> Result<Expression, SyntaxError> {
 pub fn parse(input: &str) -> Result<Expression, SyntaxError> {
     // dbg!(&input);
     match parse_script(input) {
-        Ok(result) => Ok(result),
+        // 为局部变量分配槽位
+        Ok(result) => Ok(result.resolve_slots()),
         Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => Err(SyntaxError {
             source: format!("{input}   ").into(),
             kind: e,
@@ -80,8 +95,10 @@ pub fn parse_and_eval(text: &str, env: &mut Environment) -> bool {
         return true;
     };
 
-    let parsed = parse_with_mode(text);
+    eval_parsed(parse_with_mode(text), env)
+}
 
+fn eval_parsed(parsed: Result<Expression, SyntaxError>, env: &mut Environment) -> bool {
     match parsed {
         Ok(expr) => {
             // rl.add_history_entry(text.as_str());
@@ -16,7 +16,7 @@ use std::rc::Rc;
 // PartialOrd	3	String/Int/Float comparison, BSet/HMap/Map content comparison
 // Environment	8	Scoped binding, fork chain, root operations, iteration
 // Evaluator	49	All expression eval paths, control flow, assignment, range ops, binary ops
-// Bug Reproduction	12	String * 0, String * negative, String - i64::MIN, AddAssign overflow, MulAssign wrap, % on float/int
+// Bug Reproduction	12	String * 0, String * negative, String - i64::MIN, AddAssign/MulAssign overflow promotion, % on float/int
 // Type Conversions	5	From impls for Expression, FileSize
 // FileSize	4	to_bytes, to_human_readable, parse
 // ============================================================
@@ -375,6 +375,48 @@ mod tokenizer_tests {
         assert!(diags.iter().all(|d| d == &Diagnostic::Valid));
         // This should tokenize as 3 tokens: Symbol("a"), Operator("+"), Symbol("b")
     }
+
+    #[test]
+    fn test_redirect_tokens() {
+        for (input, raw) in [
+            ("ls 2> e.log", "2>"),
+            ("ls 2>&1", "2>&1"),
+            ("ls &> a.log", "&>"),
+        ] {
+            let (tokens, _) = tokenize(input);
+            assert_eq!(tokens[2].kind, crate::TokenKind::StringRaw, "{input}");
+            assert_eq!(tokens[2].range.to_str(input), raw);
+        }
+        // numbers outside command args are untouched
+        let (tokens, _) = tokenize("2>1");
+        assert_eq!(tokens[0].kind, crate::TokenKind::IntegerLiteral);
+    }
+
+    #[test]
+    fn test_job_spec_vs_modulo() {
+        // `%1` is a job spec only in kill/wait/fg/bg args, `a % 2` stays modulo
+        let (tokens, _) = tokenize("fg %1");
+        assert_eq!(tokens[2].kind, crate::TokenKind::StringRaw);
+        let (tokens, _) = tokenize("kill %+");
+        assert_eq!(tokens[2].kind, crate::TokenKind::StringRaw);
+        let (tokens, _) = tokenize("a % 2");
+        assert_eq!(tokens[2].kind, crate::TokenKind::Operator);
+        let (tokens, _) = tokenize("print(x %2)");
+        assert_eq!(tokens[4].kind, crate::TokenKind::Operator);
+        let (tokens, _) = tokenize("x %2; kill -9 %2");
+        assert_eq!(tokens[2].kind, crate::TokenKind::Operator);
+        assert_eq!(tokens[11].kind, crate::TokenKind::StringRaw);
+    }
+
+    #[test]
+    fn test_process_substitution_tokens() {
+        // `<(` after whitespace opens a process substitution, `a < (b)` stays a comparison
+        let (tokens, _) = tokenize("diff <(ls a) >(wc)");
+        assert_eq!(tokens[2].kind, crate::TokenKind::OperatorPrefix);
+        assert_eq!(tokens[2].range.to_str("diff <(ls a) >(wc)"), "<");
+        let (tokens, _) = tokenize("a < (b)");
+        assert_eq!(tokens[2].kind, crate::TokenKind::Operator);
+    }
 }
 
 // ============================================================
@@ -569,12 +611,12 @@ mod parser_tests {
     #[test]
     fn test_parse_function_def() {
         match parse_script("fn add(a, b) { a + b }").unwrap() {
-            Expression::Function(ref name, ref params, ref collector, ref body, ref decos) => {
+            Expression::Function(ref name, ref params, ref collector, ref body, ref decos, _) => {
                 assert_eq!(name, "add");
                 assert_eq!(params.len(), 2);
-                assert_eq!(params[0].0, "a");
+                assert_eq!(params[0].0.to_string(), "a");
                 assert!(params[0].1.is_none());
-                assert_eq!(params[1].0, "b");
+                assert_eq!(params[1].0.to_string(), "b");
                 assert!(params[1].1.is_none());
                 assert!(collector.is_none());
                 assert!(decos.is_empty());
@@ -767,6 +809,42 @@ mod parser_tests {
         // let x = should fail
         assert_parse_fail("let x =");
     }
+
+    #[test]
+    fn test_parse_cache_codec_roundtrip() {
+        use crate::expression::codec::{Decoder, Encoder};
+        let src = r#"
+let a = [1, 2.5, "s", true, none, {k: 1}]
+let s = 3K
+fn f(x: Int, y = 2, *rest) -> Int { return x + y }
+struct P { x: Int, y = 0, fn len(self) { self.x } }
+let {x, y: z} = {x: 1, y: 2}
+let [p, ...q] = [1, 2]
+for i in 0..10 { if i > 3 { break } else { continue } }
+match a {
+  [1, ...t] if t => 1
+  _: Int => 2
+  n: Map => n
+  r'\d' => 3
+  _ => 4
+}
+try { f(1) } catch e { e } finally { 0 }
+defer { echo done }
+ls -l | grep x
+a.map(x -> x * 2)
+let t = `v: {a:>8}`
+use m::{q}
+x ?: 1
+"#;
+        let expr = parse_script(src).unwrap();
+        let mut enc = Encoder::new();
+        enc.expr(&expr).unwrap();
+        let mut dec = Decoder::new(&enc.buf);
+        assert_eq!(dec.expr(), Some(expr));
+        assert!(dec.is_end());
+        // truncated data is rejected rather than misread
+        assert_eq!(Decoder::new(&enc.buf[..enc.buf.len() / 2]).expr(), None);
+    }
 }
 
 // ============================================================
@@ -786,9 +864,13 @@ mod operator_tests {
 
     #[test]
     fn test_add_int_overflow() {
-        let result = Expression::Integer(i64::MAX) + Expression::Integer(1);
-        assert!(result.is_err());
-        assert!(matches!(result, Err(RuntimeErrorKind::Overflow(_))));
+        // overflow promotes to a big integer
+        let result = (Expression::Integer(i64::MAX) + Expression::Integer(1)).unwrap();
+        assert!(matches!(result, Expression::BigInt(_)));
+        assert_eq!(result.to_string(), "9223372036854775808");
+        // and demotes once it fits again
+        let result = (result - Expression::Integer(1)).unwrap();
+        assert_eq!(result, Expression::Integer(i64::MAX));
     }
 
     #[test]
@@ -864,9 +946,8 @@ mod operator_tests {
 
     #[test]
     fn test_sub_int_overflow() {
-        let result = Expression::Integer(i64::MIN) - Expression::Integer(1);
-        assert!(result.is_err());
-        assert!(matches!(result, Err(RuntimeErrorKind::Overflow(_))));
+        let result = (Expression::Integer(i64::MIN) - Expression::Integer(1)).unwrap();
+        assert_eq!(result.to_string(), "-9223372036854775809");
     }
 
     #[test]
@@ -913,9 +994,8 @@ mod operator_tests {
 
     #[test]
     fn test_mul_int_overflow() {
-        let result = Expression::Integer(i64::MAX) * Expression::Integer(2);
-        assert!(result.is_err());
-        assert!(matches!(result, Err(RuntimeErrorKind::Overflow { .. })));
+        let result = (Expression::Integer(i64::MAX) * Expression::Integer(2)).unwrap();
+        assert_eq!(result.to_string(), "18446744073709551614");
     }
 
     #[test]
@@ -1022,13 +1102,20 @@ mod operator_tests {
     #[test]
     fn test_rem_int_int() {
         let result = Expression::Integer(10) % Expression::Integer(3);
-        assert_eq!(result, Expression::Integer(1));
+        assert_eq!(result.unwrap(), Expression::Integer(1));
     }
 
     #[test]
     fn test_rem_float_int() {
         let result = Expression::Float(10.5) % Expression::Integer(3);
-        assert_eq!(result, Expression::Float(1.5));
+        assert_eq!(result.unwrap(), Expression::Float(1.5));
+    }
+
+    #[test]
+    fn test_rem_by_zero() {
+        let result = Expression::Integer(5) % Expression::Integer(0);
+        assert!(matches!(result, Err(RuntimeErrorKind::CustomError(_))));
+        assert!((Expression::Float(5.5) % Expression::Float(0.0)).is_err());
     }
 
     // --- Neg ---
@@ -1188,8 +1275,15 @@ mod truthiness_tests {
     #[test]
     fn test_function_is_truthy() {
         assert!(
-            Expression::Function("f".into(), vec![], None, Rc::new(Expression::None), vec![],)
-                .is_truthy()
+            Expression::Function(
+                "f".into(),
+                vec![],
+                None,
+                Rc::new(Expression::None),
+                vec![],
+                None
+            )
+            .is_truthy()
         );
     }
 }
@@ -1665,6 +1759,9 @@ mod evaluator_tests {
     fn test_eval_modulo() {
         let result = eval_str("10 % 3").unwrap();
         assert_eq!(result, Expression::Integer(1));
+        // `%2` after a space is not a job spec outside kill/wait/fg/bg
+        let result = eval_str("let x = 7; x %2").unwrap();
+        assert_eq!(result, Expression::Integer(1));
     }
 
     #[test]
@@ -1787,14 +1884,10 @@ mod evaluator_tests {
 
     #[test]
     fn test_eval_neg_overflow() {
-        // -i64::MIN overflows (i64::MIN = -9223372036854775808)
-        let result = eval_str("-(-9223372036854775808)");
-        assert!(
-            result.is_err() || {
-                // If it doesn't error, check result is correct
-                matches!(&result, Ok(Expression::Integer(v)) if *v == i64::MIN)
-            }
-        );
+        // -i64::MIN no longer fits in i64 and promotes
+        let result = eval_str("-(-9223372036854775808)").unwrap();
+        assert!(matches!(result, Expression::BigInt(_)));
+        assert_eq!(result.to_string(), "9223372036854775808");
     }
 
     #[test]
@@ -1804,6 +1897,8 @@ mod evaluator_tests {
             result.is_err(),
             "Division by zero should error, got {result:?}"
         );
+        // modulo by zero errors instead of panicking
+        assert!(eval_str("5 % 0").is_err());
     }
 
     #[test]
@@ -1936,6 +2031,497 @@ mod evaluator_tests {
         let result = eval_str("_").unwrap();
         assert_eq!(result, Expression::Blank);
     }
+
+    #[test]
+    fn test_eval_nested_destructure() {
+        let result = eval_str(
+            "let data = {user: {name: 'bob'}, tags: ['a', 'b', 'c']}
+let {user: {name, age = 0}, tags: [first, ...rest]} = data
+[name, age, first, rest]",
+        )
+        .unwrap();
+        assert_eq!(result.to_string(), "[bob, 0, a, [b, c]]");
+        // `*rest` is the same as `...rest`
+        let result = eval_str("let [a, *rest] = [1, 2, 3]; rest").unwrap();
+        assert_eq!(result.to_string(), "[2, 3]");
+
+        // for heads and fn params use the same patterns
+        let result = eval_str(
+            "let r = for {n, v = 10} in [{n: 1, v: 2}, {n: 3}] { n * v }
+fn f([a, b], {c = 4}) { a + b + c }
+r + [f([1, 2], {z: 0})]",
+        )
+        .unwrap();
+        assert_eq!(result.to_string(), "[2, 30, 7]");
+
+        // patterns stay in the signature and bind nothing else
+        let func = eval_str("fn f([a, b], n = a + b) { n }").unwrap();
+        assert_eq!(func.fn_signature().unwrap(), "((a, b), n = a + b)");
+        assert_eq!(
+            eval_str("fn f([a, b], n = a + b) { n }; f([1, 2])").unwrap(),
+            Expression::Integer(3)
+        );
+        assert!(eval_str("fn f([a, b]) { __arg0 }; f([1, 2])").is_err());
+
+        // missing required keys are errors
+        assert!(eval_str("let {x: {y}} = {x: {}}").is_err());
+        assert!(eval_str("let [a, b] = [1]").is_err());
+    }
+
+    #[test]
+    fn test_eval_match_structural_patterns() {
+        let script = "fn f(v) {
+  match v {
+    [a, b, ...rest] => [a + b, rest]
+    {status: 200, body} => body
+    {status: s} => s
+    x if x > 10 => x * 2
+    _: Int, _: Float => 'number'
+    m: Map => m.len()
+    _ => 'other'
+  }
+}
+";
+        let cases = [
+            ("f([1, 2, 3])", "[3, [3]]"),
+            ("f([1])", "other"),
+            ("f({status: 200, body: 'ok'})", "ok"),
+            ("f({status: 404})", "404"),
+            ("f({code: 1})", "1"),
+            ("f(11)", "22"),
+            ("f(3)", "number"),
+        ];
+        for (call, expected) in cases {
+            let result = eval_str(&format!("{script}{call}")).unwrap();
+            assert_eq!(result.to_string(), expected, "{call}");
+        }
+        // bindings do not leak out of the arm
+        let result = eval_str(&format!("let a = 0\n{script}f([5, 6])\na")).unwrap();
+        assert_eq!(result, Expression::Integer(0));
+        // bare type names are plain symbol values, not type tests
+        let result =
+            eval_str("let s = 'List'\nmatch s {\n  List => 'type'\n  _ => 'other'\n}").unwrap();
+        assert_eq!(result, Expression::String("type".into()));
+        assert!(parse_script("match 1 {\n  _: Nope => 1\n}").is_err());
+        // `*rest` is the same as `...rest`
+        let result = eval_str("match [1, 2, 3] {\n  [a, *rest] => rest\n  _ => 0\n}").unwrap();
+        assert_eq!(result.to_string(), "[2, 3]");
+    }
+
+    #[test]
+    fn test_eval_try_catch_finally_and_defer() {
+        let result = eval_str("try { 1 / 0 } catch e { e.code }").unwrap();
+        assert_eq!(result, Expression::Integer(9));
+        // finally runs, its value is discarded
+        let result = eval_str("try { 1 } finally { 2 }").unwrap();
+        assert_eq!(result, Expression::Integer(1));
+        // return passes through catch
+        let result = eval_str("fn f() { try { return 7 } catch { 0 } }\nf()").unwrap();
+        assert_eq!(result, Expression::Integer(7));
+
+        // defers run in reverse order; the first error wins
+        let result = eval_str(
+            "fn f() { defer throw('a'); defer throw('b'); 1 }\ntry { f() } catch e { e.msg }",
+        )
+        .unwrap();
+        assert_eq!(result, Expression::String("b".into()));
+        let result =
+            eval_str("fn g() { defer throw('cleanup'); 1 / 0 }\ntry { g() } catch e { e.msg }")
+                .unwrap();
+        assert_eq!(result, Expression::String("can't divide 1 by zero".into()));
+    }
+
+    #[test]
+    fn test_eval_fn_type_annotations() {
+        let result = eval_str(
+            "fn deploy(host: String, port: Int = 22, files: List) -> Map { {host: host, port: port} }\ndeploy('a', 80, []).port",
+        )
+        .unwrap();
+        assert_eq!(result, Expression::Integer(80));
+        // the error names the offending argument
+        let result = eval_str(
+            "fn f(host: String, port: Int = 22) { port }\ntry { f('a', '80') } catch e { e.msg }",
+        )
+        .unwrap();
+        assert!(
+            result
+                .to_string()
+                .contains("argument `port` expects `Int`, found `String`")
+        );
+        let result =
+            eval_str("fn g(x: Int) -> String { x }\ntry { g(1) } catch e { e.code }").unwrap();
+        assert_eq!(result, Expression::Integer(19));
+        // aliases and Any are accepted
+        let result =
+            eval_str("fn h(a: Number, b: Any) -> Fn { () -> a }\nlet k = h(1.5, none); k()")
+                .unwrap();
+        assert_eq!(result, Expression::Float(1.5));
+    }
+
+    #[test]
+    fn test_eval_struct_methods_and_overloads() {
+        let src = "struct Point {\n  x: Int, y = 0\n  fn new(x) { Point(x, x) }\n  fn dist(self, q) { (self.x - q.x) ^ 2 + (self.y - q.y) ^ 2 }\n  fn add(self, q) { Point(self.x + q.x, self.y + q.y) }\n  fn eq(self, q) { self.x == q.x }\n  fn to_string(self) { 'P' + self.x }\n}\n";
+        // constructor, defaults, fields and methods
+        let result = eval_str(&format!("{src}let p = Point(1)\np.dist(Point.new(4))")).unwrap();
+        assert_eq!(result, Expression::Integer(25));
+        // operators dispatch to the struct methods
+        let result = eval_str(&format!("{src}let p = Point(1, 2) + Point(3)\np.y")).unwrap();
+        assert_eq!(result, Expression::Integer(2));
+        let result = eval_str(&format!(
+            "{src}[Point(1, 2) == Point(1, 9), Point(1) != Point(1, 9)]"
+        ))
+        .unwrap();
+        assert_eq!(
+            result,
+            Expression::from(vec![Expression::Boolean(true), Expression::Boolean(false)])
+        );
+        let result = eval_str(&format!("{src}let p = Point(7)\n`<${{p}}>`")).unwrap();
+        assert!(result.to_string().contains("<P7>"));
+        // eq also applies with the struct on the right, `+` with a string uses to_string
+        let result = eval_str(&format!("{src}'at ' + Point(7)")).unwrap();
+        assert_eq!(result, Expression::String("at P7".into()));
+        let result = eval_str("struct V { x, fn eq(self, o) { self.x == o } }\n3 == V(3)").unwrap();
+        assert_eq!(result, Expression::Boolean(true));
+        // `struct` is only a keyword in front of `Name {`
+        let result = eval_str("let struct = 3\nstruct + 1").unwrap();
+        assert_eq!(result, Expression::Integer(4));
+        // instances are typed by struct name
+        let result = eval_str(&format!("{src}fn f(p: Point) {{ p.x }}\nf(Point(5))")).unwrap();
+        assert_eq!(result, Expression::Integer(5));
+        let result = eval_str(&format!("{src}try {{ Point('a') }} catch e {{ e.msg }}")).unwrap();
+        assert!(result.to_string().contains("field `x` expects `Int`"));
+    }
+
+    #[test]
+    fn test_eval_format_specs() {
+        let result = eval_str(
+            "let size = 42; let pct = 3.14159; let n = 255; let x = 1234567\n`[{size:>6}][{pct:.2}][{n:08x}][{x:,}][${'ab':*^6}]`",
+        )
+        .unwrap();
+        // templates still keep their quotes (see test_eval_template_string)
+        let unquote = |e: Expression| e.to_string().trim_matches('"').to_string();
+        assert_eq!(
+            unquote(result),
+            "[    42][3.14][000000ff][1,234,567][**ab**]"
+        );
+        // a ':' closing a ternary is not a spec separator
+        let result = eval_str("let x = true; let a = 1\n`{x ?a:5}|{x ? a : 2:>3}`").unwrap();
+        assert_eq!(unquote(result), "1|  1");
+        // fill, width, precision and radix combined
+        let result = eval_str("let n = 255\nformat '{n:_<10b}|{:0>7.3}|{n:o}|{n:X}' 2.5").unwrap();
+        assert_eq!(
+            result,
+            Expression::String("11111111__|002.500|377|FF".into())
+        );
+        // named and positional placeholders in format
+        let result = eval_str("let n = 7\nformat '{n:<3}|{:>6.1}|{}|{:+}' 2.0 'z' 5").unwrap();
+        assert_eq!(result, Expression::String("7  |   2.0|z|+5".into()));
+        let result = eval_str("format '{:.1}|{:,}' 1.5K 2K").unwrap();
+        assert_eq!(result, Expression::String("1.5K|2,048".into()));
+        let result = eval_str("try { string.fmt(1.5, 'x') } catch e { e.msg }").unwrap();
+        assert!(result.to_string().contains("needs an integer"));
+    }
+
+    #[test]
+    fn test_eval_module_visibility() {
+        let dir = std::env::temp_dir().join(format!("lumesh_mods_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("helper.lm"),
+            "pub fn hh() { 'hh' + inner() }\nfn inner() { '!' }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("m.lm"),
+            "use helper\npub use helper::{hh}\nfn g(x) { x * 2 }\npub fn f(x) { g(x) + 1 }\n",
+        )
+        .unwrap();
+        let m = dir.join("m");
+        let m = m.display();
+        // public fns may call private helpers
+        let result = eval_str(&format!("use '{m}'\n[m::f(3), m::hh()]")).unwrap();
+        assert_eq!(
+            result,
+            Expression::from(vec![
+                Expression::Integer(7),
+                Expression::String("hh!".into())
+            ])
+        );
+        // private fns and transitive imports stay hidden
+        let result =
+            eval_str(&format!("use '{m}'\ntry {{ m::g(3) }} catch e {{ e.msg }}")).unwrap();
+        assert!(result.to_string().contains("`g` is private in module `m`"));
+        let result = eval_str(&format!(
+            "use '{m}'\ntry {{ m::helper::hh() }} catch e {{ e.msg }}"
+        ))
+        .unwrap();
+        assert!(result.to_string().contains("`helper` is private"));
+        // selective import binds names directly
+        let result = eval_str(&format!("use '{m}'::{{f}}\nf(1)"));
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(result.unwrap(), Expression::Integer(3));
+    }
+
+    #[test]
+    fn test_ast_cache_store_load_and_evict() {
+        use crate::ast_cache;
+        let dir = std::env::temp_dir().join(format!("lumesh_cache_{}", std::process::id()));
+        let cache = dir.join("cache");
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("s.lm");
+        std::fs::write(&script, "fn f([a, b], n = a + b) { n }\nf([1, 2])\n").unwrap();
+        ast_cache::set_cache_dir(Some(cache.clone()));
+
+        // --no-cache (the test default) neither writes nor reads
+        assert!(crate::runtime::run_file(
+            script.clone(),
+            &mut Environment::new()
+        ));
+        assert!(!cache.exists());
+
+        // the first run stores the parsed AST, later runs load it
+        crate::set_ast_cache_enabled(true);
+        assert!(ast_cache::load_script(&script).is_none());
+        assert!(crate::runtime::run_file(
+            script.clone(),
+            &mut Environment::new()
+        ));
+        let cached = ast_cache::load_script(&script).unwrap();
+        crate::set_ast_cache_enabled(false);
+        assert!(ast_cache::load_script(&script).is_none());
+        assert_eq!(
+            cached.eval(&mut Environment::new()).unwrap(),
+            Expression::Integer(3)
+        );
+
+        // file names use a fixed hash, stable across Rust releases
+        assert_eq!(ast_cache::fnv1a(b"a".iter()), 0xaf63dc4c8601ec8c);
+
+        // over the size bound the oldest entries go first
+        let cache = dir.join("evict");
+        std::fs::create_dir_all(&cache).unwrap();
+        for (i, name) in ["a.ast", "b.ast", "c.ast"].iter().enumerate() {
+            let file = cache.join(name);
+            std::fs::write(&file, [0u8; 100]).unwrap();
+            let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(i as u64 + 1);
+            std::fs::File::options()
+                .write(true)
+                .open(&file)
+                .unwrap()
+                .set_modified(mtime)
+                .unwrap();
+        }
+        ast_cache::evict(&cache, 250);
+        let mut left = std::fs::read_dir(&cache)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        left.sort();
+        ast_cache::set_cache_dir(None);
+        std::fs::remove_dir_all(&dir).ok();
+        assert!(!left.contains(&"a.ast".to_string()));
+        assert!(!left.contains(&"b.ast".to_string()));
+        assert!(left.contains(&"c.ast".to_string()));
+    }
+
+    #[test]
+    fn test_eval_stream_is_lazy() {
+        // adapters only pull what the sink needs, even from a huge range
+        let result = eval_str(
+            "let s = stream.from(0..1000000000) | .map(x -> x * 2) | .filter(x -> x % 3 == 0)\ns.skip(1).take(3).collect()",
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            Expression::from(vec![
+                Expression::Integer(6),
+                Expression::Integer(12),
+                Expression::Integer(18)
+            ])
+        );
+        let result = eval_str("stream.from([1, 2, 3, 4, 5]).chunk(2).count()").unwrap();
+        assert_eq!(result, Expression::Integer(3));
+        let result =
+            eval_str("let r = for x in stream.from(1..1000000000).take(3) { x + 1 }; r").unwrap();
+        assert_eq!(
+            result,
+            Expression::from(vec![
+                Expression::Integer(2),
+                Expression::Integer(3),
+                Expression::Integer(4)
+            ])
+        );
+        // errors raised while pulling reach the sink
+        assert!(eval_str("stream.from([1, 0]).map(x -> 1 / x).collect()").is_err());
+    }
+
+    #[test]
+    fn test_eval_bigint_promotion() {
+        let src = "let f = 1\nfor i in 1..=21 { set f = f * i }\nf";
+        assert_eq!(eval_str(src).unwrap().to_string(), "51090942171709440000");
+        let result =
+            eval_str("let x = 2 ^ 64; [x - 2 ^ 63 * 2 + 7, x > 5, x == 18446744073709551616]")
+                .unwrap();
+        assert_eq!(
+            result,
+            Expression::from(vec![
+                Expression::Integer(7),
+                Expression::Boolean(true),
+                Expression::Boolean(true)
+            ])
+        );
+        let result = eval_str(
+            "[math.pow(3, 50), math.bit_shl(1, 64), math.bit_and(2 ^ 70 + 5, 7), 99999999999999999999 % 7]",
+        )
+        .unwrap();
+        assert_eq!(
+            result.to_string(),
+            eval_str("[717897987691852588770249, 18446744073709551616, 5, 1]")
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(
+            eval_str("format '{:x}' (2 ^ 64)").unwrap(),
+            Expression::String("10000000000000000".into())
+        );
+    }
+
+    #[test]
+    fn test_eval_slot_resolved_locals() {
+        let src = "let out = []\nfor a in [1, 2] {\n  let t = a * 10\n  for i, b in [7, 8, 9] { set out = out + [t + i] }\n}\nlet s = 0\nwhile s < 5 { let step = 2; set s = s + step }\n[out, s, s >= 6 && s <= 6]";
+        let expr = parse_script(src).unwrap();
+        let resolved = expr.resolve_slots();
+        // slot references print like the variables they replace
+        assert_eq!(resolved.to_string(), expr.to_string());
+        let plain = expr.eval(&mut Environment::new()).unwrap();
+        let result = resolved.eval(&mut Environment::new()).unwrap();
+        assert_eq!(result, plain);
+        // the inner loop index restarts at 0 for every outer iteration
+        assert_eq!(
+            result,
+            eval_str("[[10, 11, 12, 20, 21, 22], 6, true]").unwrap()
+        );
+
+        // params live in the frame and shadow the caller's locals;
+        // lambdas capture locals when they are created
+        let src = "fn g(x) { x * 2 }
+fn k() { let b = 3; y -> b + y }
+let add = (a, b) -> a + b
+{
+  let x = 5
+  let f = x -> x + 1
+  let kk = k()
+  let inc = add(1)
+  [g(1), f(1), x, kk(1), inc(5)]
+}";
+        assert_eq!(eval_str(src).unwrap(), eval_str("[2, 2, 5, 4, 6]").unwrap());
+
+        // plain assignment in a block updates the outer variable
+        let src = "let s = 0\nlet i = 0\nwhile i < 5 { s = s + i; i = i + 1 }\n[s, i]";
+        assert_eq!(eval_str(src).unwrap(), eval_str("[10, 5]").unwrap());
+    }
+
+    #[test]
+    fn test_eval_tail_call_beyond_recursion_limit() {
+        // self calls in tail position (if branch, match arm, return) run in a loop,
+        // so they go far past MAX_RUNTIME_RECURSION
+        let src = "fn sum(n, acc) { if n == 0 { acc } else { sum(n - 1, acc + n) } }
+fn cnt(n) {
+  match n {
+    0 => 'done'
+    _ => cnt(n - 1)
+  }
+}
+fn back(n) { if n <= 0 { return 'back' }; let m = n - 1; return back(m) }
+[sum(3000, 0), cnt(3000), back(3000)]";
+        assert_eq!(
+            eval_str(src).unwrap(),
+            eval_str("[4501500, 'done', 'back']").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_eval_profiler_counts_calls() {
+        // folded stacks are only collected with an output path; take() never writes it
+        crate::profiler::enable(Some("unused.folded".into()));
+        let src = "fn twice(x) { x * 2 }\nfn outer(n) { twice(n) + math.abs(n) }\n[outer(1), outer(2), outer(3)]\nfn down(n) { if n == 0 { 0 } else { down(n - 1) } }\ndown(4)";
+        eval_str(src).unwrap();
+        let profile = crate::profiler::take().unwrap();
+        assert!(crate::profiler::take().is_none());
+
+        let calls = |kind: &str, name: &str| {
+            profile
+                .table()
+                .lines()
+                .map(|l| l.split_whitespace().collect::<Vec<_>>())
+                .find(|cols| cols[3..] == [kind, name])
+                .map(|cols| cols[0].to_string())
+        };
+        assert_eq!(calls("fn", "outer").as_deref(), Some("3"));
+        assert_eq!(calls("fn", "twice").as_deref(), Some("3"));
+        assert_eq!(calls("builtin", "math.abs").as_deref(), Some("3"));
+        // each trampolined tail call counts as a call
+        assert_eq!(calls("fn", "down").as_deref(), Some("5"));
+        // nested calls show up under their caller in the folded stacks
+        let folded = profile.folded_lines();
+        assert!(folded.lines().any(|l| l.starts_with("fn:outer;fn:twice ")));
+        assert!(
+            folded
+                .lines()
+                .any(|l| l.starts_with("fn:outer;builtin:math.abs "))
+        );
+    }
+
+    #[test]
+    fn test_eval_yaml_from_into() {
+        // merge key and alias both expand the anchored map
+        let src = r#"let y = from.yaml("base: &b\n  image: nginx\n  port: 80\nweb:\n  <<: *b\n  port: 8080\ncopy: *b\nbig: 12345678901234567890\n")
+[y.web, y.copy, y.big]"#;
+        assert_eq!(
+            eval_str(src).unwrap(),
+            eval_str(
+                "[{image: 'nginx', port: 8080}, {image: 'nginx', port: 80}, 12345678901234567890]"
+            )
+            .unwrap()
+        );
+
+        // a multi-document stream of maps becomes a table
+        let docs = eval_str(r#"from.yaml("---\nname: a\nn: 1\n---\nname: b\n---\n")"#).unwrap();
+        match docs {
+            Expression::Table(t) => {
+                assert_eq!(t.headers(), &["n".to_string(), "name".to_string()]);
+                assert_eq!(
+                    t.rows(),
+                    &[
+                        vec![Expression::Integer(1), Expression::String("a".into())],
+                        vec![Expression::None, Expression::String("b".into())],
+                    ]
+                );
+            }
+            other => panic!("Expected Table, got {other:?}"),
+        }
+        assert_eq!(
+            eval_str(r#"from.yaml("--- 5\n---\n- 1\n- 2\n")"#).unwrap(),
+            eval_str("[5, [1, 2]]").unwrap()
+        );
+
+        // strings that look like other scalars stay strings on the way back
+        let src = r#"let b = 2 ^ 70
+let m = {name: 'web', ports: [80, 443], n: '123', f: 2.0, big: b}
+let s = into.yaml(m)
+[from.yaml(s) == m, s]"#;
+        assert_eq!(
+            eval_str(src).unwrap(),
+            Expression::from(vec![
+                Expression::Boolean(true),
+                Expression::String(
+                    "big: 1180591620717411303424\nf: 2.0\n\"n\": \"123\"\nname: web\nports:\n  - 80\n  - 443"
+                        .into()
+                ),
+            ])
+        );
+        assert!(eval_str(r#"from.yaml("a: [1")"#).is_err());
+    }
 }
 
 // ============================================================
@@ -2016,25 +2602,24 @@ mod bug_reproduction_tests {
     }
 
     #[test]
-    fn bug_addassign_overflow_wraps() {
+    fn bug_addassign_overflow_promotes() {
         let mut val = Expression::Integer(i64::MAX);
         val += Expression::Integer(1);
         assert_eq!(
-            val,
-            Expression::Integer(i64::MIN),
-            "AddAssign should wrap on overflow"
+            val.to_string(),
+            "9223372036854775808",
+            "AddAssign should promote on overflow"
         );
     }
 
     #[test]
-    fn bug_mulassign_overflow_wraps() {
+    fn bug_mulassign_overflow_promotes() {
         let mut val = Expression::Integer(i64::MAX);
         val *= Expression::Integer(2);
-        // wrapping_mul: i64::MAX * 2 = -2
         assert_eq!(
-            val,
-            Expression::Integer(-2),
-            "MulAssign should wrap on overflow"
+            val.to_string(),
+            "18446744073709551614",
+            "MulAssign should promote on overflow"
         );
     }
 
@@ -2248,3 +2833,336 @@ mod filesize_tests {
         assert_eq!(fs.to_human_readable(), "2K");
     }
 }
+
+// ============================================================
+// 11. EXTERNAL COMMAND PIPELINE TESTS
+// ============================================================
+
+#[cfg(unix)]
+mod pipeline_tests {
+    use super::*;
+
+    #[test]
+    fn test_pipe_external_chain_captured() {
+        let result = eval_str("let x = echo hello | tr a-z A-Z | rev; x").unwrap();
+        assert_eq!(result, Expression::String("OLLEH".into()));
+    }
+
+    #[test]
+    fn test_pipe_early_exit_downstream() {
+        // upstream must receive SIGPIPE instead of blocking forever
+        let result = eval_str("let x = yes | head -n 2; x").unwrap();
+        assert_eq!(result, Expression::String("y\ny".into()));
+    }
+
+    #[test]
+    fn test_pipe_builtin_then_external() {
+        let result = eval_str("let x = 'abc' | tr a-z A-Z; x").unwrap();
+        assert_eq!(result, Expression::String("ABC".into()));
+    }
+
+    #[test]
+    fn test_pipe_missing_downstream_program() {
+        let result = eval_str("echo hi | no_such_program_lumesh");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_redirect_here_string() {
+        let result = eval_str("let y = tr a-z A-Z <<< abc; y").unwrap();
+        assert_eq!(result, Expression::String("ABC".into()));
+    }
+
+    #[test]
+    fn test_redirect_stdin_and_stderr_files() {
+        let dir = std::env::temp_dir().join(format!("lumesh_redir_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("in.txt");
+        let err = dir.join("err.log");
+        std::fs::write(&input, "b\na\n").unwrap();
+
+        let script = format!("let x = sort < '{}'; x", input.display());
+        assert_eq!(
+            eval_str(&script).unwrap(),
+            Expression::String("a\nb".into())
+        );
+        // inside expressions and call args `<` stays a comparison, not a redirect
+        let script = format!(
+            "let f = (v) -> v; [f(nosuch < 5), f(sort < '{}')]",
+            input.display()
+        );
+        assert_eq!(
+            eval_str(&script).unwrap(),
+            Expression::from(vec![Expression::None, Expression::None])
+        );
+
+        let script = format!("ls /no_such_dir_lumesh 2> '{}' ?.", err.display());
+        eval_str(&script).unwrap();
+        let logged = std::fs::read_to_string(&err).unwrap();
+        assert!(logged.contains("no_such_dir_lumesh"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_redirect_stderr_to_pipe() {
+        let result = eval_str("let x = ls /no_such_dir_lumesh 2>&1 | grep -c no_such; x").unwrap();
+        assert_eq!(result, Expression::String("1".into()));
+    }
+
+    #[test]
+    fn test_last_status_map() {
+        let result = eval_str("let x = echo hi there\nsys.last_status()").unwrap();
+        let Expression::HMap(m) = result else {
+            panic!("expected map, got {result:?}");
+        };
+        assert_eq!(m.get("code"), Some(&Expression::Integer(0)));
+        assert_eq!(m.get("signal"), Some(&Expression::None));
+        assert_eq!(
+            m.get("argv").map(|a| a.to_string()),
+            Some("[echo, hi, there]".to_string())
+        );
+
+        // a background command has no exit code until it is reaped
+        let result = eval_str("sleep 0.05 &\nsys.last_status().code").unwrap();
+        assert_eq!(result, Expression::None);
+    }
+
+    #[test]
+    fn test_catch_carries_exit_code() {
+        let result = eval_str("let e = ls /no_such_dir_lumesh ?>\ne.exit_code").unwrap();
+        assert_eq!(result, Expression::Integer(2));
+    }
+
+    #[test]
+    fn test_sys_run_captures_both_streams() {
+        let result = eval_str("sys.run('ls', '-d', '/', '/no_such_dir_lumesh')").unwrap();
+        let Expression::HMap(m) = result else {
+            panic!("expected map, got {result:?}");
+        };
+        assert_eq!(m.get("stdout"), Some(&Expression::String("/\n".into())));
+        assert!(
+            m.get("stderr")
+                .unwrap()
+                .to_string()
+                .contains("no_such_dir_lumesh")
+        );
+        assert_eq!(m.get("code"), Some(&Expression::Integer(2)));
+        assert_eq!(m.get("signal"), Some(&Expression::None));
+    }
+
+    #[test]
+    fn test_par_map_keeps_order_and_errors() {
+        let result = eval_str("[3, 1, 2].par_map(x -> echo $x, 2)").unwrap();
+        assert_eq!(result.to_string(), "[3, 1, 2]");
+        // a failing item becomes an error map, the rest still run
+        let result = eval_str(
+            "let r = ['/', '/no_such_dir_lumesh'].par_map(d -> ls -d $d &?); r[1].exit_code",
+        )
+        .unwrap();
+        assert_eq!(result, Expression::Integer(2));
+        // non-command bodies are evaluated in place
+        let result = eval_str("[1, 2].par_map(x -> x * 10)").unwrap();
+        assert_eq!(result.to_string(), "[10, 20]");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_process_substitution() {
+        let result = eval_str("let x = cat <(echo hi) <(printf 'a\\nb'); x").unwrap();
+        assert_eq!(result, Expression::String("hi\na\nb".into()));
+        // identical inputs: diff succeeds with no output
+        let result = eval_str("let d = diff <(seq 3) <(seq 3); d").unwrap();
+        assert_eq!(result, Expression::String("".into()));
+        // non-command expressions are written into the pipe
+        let result = eval_str("let n = 42; let x = cat <(n); x").unwrap();
+        assert_eq!(result, Expression::String("42".into()));
+        // after a value operand or inside call args `<(`/`>(` is a comparison
+        let result =
+            eval_str("let a = 3; let b = 5; let f = (v) -> v; [f(a <(b)), 3 <(5)]").unwrap();
+        assert_eq!(
+            result,
+            Expression::from(vec![Expression::Boolean(true), Expression::Boolean(true)])
+        );
+        let result = eval_str("let x = 7; let f = (v) -> v; [f(x >(5)), $x >(5)]").unwrap();
+        assert_eq!(
+            result,
+            Expression::from(vec![Expression::Boolean(true), Expression::Boolean(true)])
+        );
+    }
+
+    #[test]
+    fn test_process_substitution_in_pipeline() {
+        let dir = std::env::temp_dir();
+        let path = |n: &str| dir.join(format!("lumesh_psub_{n}_{}", std::process::id()));
+        let (a, b) = (path("a"), path("b"));
+        // `a | b >(c)`: the stage keeps its piped stdin and its own stdout
+        let script = format!(
+            "let x = seq 1 3 | tee >(dd 'of={}' 'status=none'); x",
+            a.display()
+        );
+        assert_eq!(
+            eval_str(&script).unwrap(),
+            Expression::String("1\n2\n3".into())
+        );
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "1\n2\n3\n");
+        // `a | b >(c) | d`
+        let script = format!(
+            "let x = seq 1 3 | tee >(dd 'of={}' 'status=none') | sed 's/^/o/'; x",
+            b.display()
+        );
+        assert_eq!(
+            eval_str(&script).unwrap(),
+            Expression::String("o1\no2\no3".into())
+        );
+        assert_eq!(std::fs::read_to_string(&b).unwrap(), "1\n2\n3\n");
+        // `<(..)` next to piped stdin
+        let result = eval_str("let x = seq 1 2 | cat <(echo x) -; x").unwrap();
+        assert_eq!(result, Expression::String("x\n1\n2".into()));
+        for p in [a, b] {
+            let _ = std::fs::remove_file(p);
+        }
+    }
+
+    #[test]
+    fn test_pipefail_and_pipestatus() {
+        let result =
+            eval_str("let x = env false | env sh -c 'exit 3' | cat; sys.pipestatus()").unwrap();
+        assert_eq!(
+            result,
+            Expression::from(vec![
+                Expression::Integer(1),
+                Expression::Integer(3),
+                Expression::Integer(0)
+            ])
+        );
+        let expr =
+            parse_script("sys.set_pipefail(true); let x = env false | env true | cat; x").unwrap();
+        let result = expr.eval(&mut Environment::new());
+        crate::set_pipefail_enabled(false);
+        // the error points at the failing stage, not at the whole pipeline
+        let err = result.unwrap_err();
+        assert!(matches!(
+            err.kind,
+            RuntimeErrorKind::CommandExited { code: Some(1), .. }
+        ));
+        assert_eq!(err.context.to_string(), "env false");
+    }
+
+    #[test]
+    fn test_with_env_scopes_overrides() {
+        let result = eval_str(
+            "with_env({LUME_T: 'a b', HOME: none}, () -> {let r = env | grep -E '^(LUME_T|HOME)='; r})",
+        )
+        .unwrap();
+        assert_eq!(result, Expression::String("LUME_T=a b".into()));
+        // overrides are gone once the func returns
+        let result =
+            eval_str("with_env({LUME_T: 1}, () -> 0); let x = env | grep -c '^LUME_T='; x");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_stream_pipes_into_commands() {
+        // items are written line by line; the stream stops once head exits
+        let result = eval_str("let x = stream.from(1..1000000000) | head -n 2 | wc -l; x").unwrap();
+        assert_eq!(result, Expression::String("2".into()));
+        let result =
+            eval_str("stream.cmd('seq', '1', '1000000000') | .take(2) | .collect()").unwrap();
+        assert_eq!(
+            result,
+            Expression::from(vec![
+                Expression::String("1".into()),
+                Expression::String("2".into())
+            ])
+        );
+    }
+}
+
+// ============================================================
+// 12. JOB CONTROL TESTS
+// ============================================================
+
+#[cfg(unix)]
+mod job_tests {
+    use super::*;
+
+    // the job table is shared with other test threads, so address jobs by pid
+    #[test]
+    fn test_background_jobs() {
+        let result = eval_str("ls /no_such_dir_lumesh &\nwait (sys.last_status().pid)").unwrap();
+        assert_eq!(result, Expression::Integer(2));
+
+        let result = eval_str(
+            "sleep 0.2 | cat &\nlet p = sys.last_status().pid\nlet j = jobs() | where($cmd == 'sleep 0.2 | cat') | len()\nwait $p\nj",
+        )
+        .unwrap();
+        assert_eq!(result, Expression::Integer(1));
+
+        let result =
+            eval_str("sleep 5 &\nlet p = sys.last_status().pid\nkill -KILL $p\nwait $p").unwrap();
+        assert_eq!(result, Expression::Integer(128 + 9));
+
+        // blocking wait returns as soon as the job ends
+        let start = std::time::Instant::now();
+        let result = eval_str("sleep 0.1 &\nwait (sys.last_status().pid)").unwrap();
+        assert_eq!(result, Expression::Integer(0));
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+
+        // forms the builtin does not handle go to the external kill
+        let result = eval_str("kill -l").unwrap();
+        assert!(result.to_string().contains("KILL"));
+    }
+
+    #[test]
+    fn test_no_such_job() {
+        assert!(eval_str("fg %99").is_err());
+    }
+
+    #[test]
+    fn test_trap_runs_handler_at_safe_point() {
+        let mark = std::env::temp_dir().join(format!("lumesh_trap_{}", std::process::id()));
+        let _ = std::fs::remove_file(&mark);
+        // the signal may land on another thread; wait for the handler before
+        // `trap 'USR1'` restores the default (fatal) disposition
+        let script = format!(
+            "trap 'SIGUSR1' (s -> touch '{m}')\nkill -USR1 {}\nlet i = 0\nwhile !(fs.exists '{m}') && i < 500 {{ sleep 0.01; i += 1 }}\nlet n = len(trap())\ntrap 'USR1'\nn",
+            std::process::id(),
+            m = mark.display(),
+        );
+        assert_eq!(eval_str(&script).unwrap(), Expression::Integer(1));
+        assert!(mark.exists());
+        let _ = std::fs::remove_file(&mark);
+        assert!(eval_str("trap 'QUIT' (() -> 1)").is_err());
+    }
+
+    #[test]
+    fn test_timeout_kills_process_group() {
+        let started = std::time::Instant::now();
+        let result = eval_str("sys.timeout 0.3 (() -> {sleep 5 | cat}) ?: e -> e.code").unwrap();
+        assert_eq!(result, Expression::Integer(21));
+        assert!(started.elapsed() < std::time::Duration::from_secs(3));
+
+        let err = eval_str("sys.timeout 0.2 (() -> sleep 5)").unwrap_err();
+        assert!(format!("{err:?}").contains("Timeout"));
+        // finishing in time returns the func's result
+        let result = eval_str("sys.timeout 2 (() -> 1 + 2)").unwrap();
+        assert_eq!(result, Expression::Integer(3));
+        // a pure script body is interrupted as well
+        let started = std::time::Instant::now();
+        let err = eval_str("sys.timeout 0.2 (() -> { loop { let x = 1 } })").unwrap_err();
+        assert!(format!("{err:?}").contains("Timeout"));
+        assert!(started.elapsed() < std::time::Duration::from_secs(3));
+        // try/catch and ?: inside the body do not swallow the expiry
+        let started = std::time::Instant::now();
+        let script = "sys.timeout 0.2 (() -> { loop { try { sleep 0.05 } catch e { 1 }; sleep 0.05 ?: 0 } })";
+        assert!(eval_str(script).is_err());
+        assert!(started.elapsed() < std::time::Duration::from_secs(3));
+        // once out of the timed body it is an ordinary error
+        let result =
+            eval_str("try { sys.timeout 0.1 (() -> sleep 1) } catch e { 'caught' }").unwrap();
+        assert_eq!(result, Expression::String("caught".into()));
+        // `timeout` itself is left to the system command
+        let result = eval_str("let x = timeout 5 echo hi; x").unwrap();
+        assert_eq!(result, Expression::String("hi".into()));
+    }
+}
@@ -71,6 +71,7 @@ fn parse_token_dispatch(
     ctx: Ctx,
     last_ctx: Ctx,
     is_cfm: bool,
+    in_job_cmd: bool,
 ) -> TokenizationResult<'_, (Token, Diagnostic)> {
     let first = match input.chars().next() {
         Some(c) => c,
@@ -111,7 +112,7 @@ fn parse_token_dispatch(
 
         '(' | ')' | '[' | ']' | '{' | '}' | ',' => paren_dispatch(input, ctx, first),
 
-        '%' => percent_dispatch(input, ctx),
+        '%' => percent_dispatch(input, ctx, in_job_cmd),
 
         '!' => bang_dispatch(input, ctx), // context-aware: prefix negate vs postfix call
 
@@ -126,8 +127,8 @@ fn parse_token_dispatch(
         '&' => and_dispatch(input, ctx),
         '|' => m!(pipe_parser, TokenKind::Operator),
         '=' => m!(equal_parser, TokenKind::Operator),
-        '<' => m!(less_parser, TokenKind::Operator),
-        '>' => m!(greater_parser, TokenKind::Operator),
+        '<' => less_dispatch(input, ctx),
+        '>' => greater_dispatch(input, ctx),
 
         '+' => plus_dispatch(input, ctx, is_cfm),
 
@@ -143,7 +144,7 @@ fn parse_token_dispatch(
 
         '_' => underscore_dispatch(input, ctx), // standalone _ vs _ in symbol
 
-        '0'..='9' => number_literal(input),
+        '0'..='9' => digit_dispatch(input, ctx),
 
         'a'..='z' | 'A'..='Z' => alpha_dispatch(input, ctx, last_ctx, first, is_cfm), // keyword / value_symbol / string / symbol
 
@@ -203,6 +204,18 @@ fn equal_parser(input: Input<'_>) -> TokenizationResult<'_> {
         // punctuation_tag("<="),\
     ))(input)
 }
+/// `<<<` here-string for commands, otherwise comparison / `<<` redirect operators
+fn less_dispatch(input: Input<'_>, ctx: Ctx) -> TokenizationResult<'_, (Token, Diagnostic)> {
+    match ctx {
+        Ctx::Space => alt((
+            map_valid_token(space_followed_tag("<<<"), TokenKind::StringRaw),
+            // 进程替换 <(cmd)
+            map_valid_token(paren_followed_tag("<"), TokenKind::OperatorPrefix),
+            map_valid_token(less_parser, TokenKind::Operator),
+        ))(input),
+        _ => map_valid_token(less_parser, TokenKind::Operator)(input),
+    }
+}
 fn less_parser(input: Input<'_>) -> TokenizationResult<'_> {
     alt((
         punctuation_tag("<="),
@@ -210,6 +223,16 @@ fn less_parser(input: Input<'_>) -> TokenizationResult<'_> {
         punctuation_tag("<"),
     ))(input)
 }
+fn greater_dispatch(input: Input<'_>, ctx: Ctx) -> TokenizationResult<'_, (Token, Diagnostic)> {
+    match ctx {
+        // 进程替换 >(cmd)
+        Ctx::Space => alt((
+            map_valid_token(paren_followed_tag(">"), TokenKind::OperatorPrefix),
+            map_valid_token(greater_parser, TokenKind::Operator),
+        ))(input),
+        _ => map_valid_token(greater_parser, TokenKind::Operator)(input),
+    }
+}
 fn greater_parser(input: Input<'_>) -> TokenizationResult<'_> {
     alt((
         punctuation_tag(">="),
@@ -267,6 +290,8 @@ fn and_dispatch(input: Input<'_>, ctx: Ctx) -> TokenizationResult<'_, (Token, Di
         } //NEVER USE
         Ctx::Space | Ctx::Open => alt((
             map_valid_token(operator_tag("&&"), TokenKind::Operator),
+            map_valid_token(space_followed_tag("&>>"), TokenKind::StringRaw), // &>> all.log
+            map_valid_token(space_followed_tag("&>"), TokenKind::StringRaw),  // &> all.log
             map_valid_token(postfix_break_tag("&+"), TokenKind::StringRaw),
             map_valid_token(postfix_break_tag("&-"), TokenKind::StringRaw),
             map_valid_token(postfix_break_tag("&?"), TokenKind::StringRaw),
@@ -277,6 +302,20 @@ fn and_dispatch(input: Input<'_>, ctx: Ctx) -> TokenizationResult<'_, (Token, Di
     }
 }
 
+/// Command fd redirections after whitespace: `2>&1` `1>&2` `2>> f` `2> f`; otherwise a number.
+fn digit_dispatch(input: Input<'_>, ctx: Ctx) -> TokenizationResult<'_, (Token, Diagnostic)> {
+    match ctx {
+        Ctx::Space => alt((
+            map_valid_token(postfix_break_tag("2>&1"), TokenKind::StringRaw),
+            map_valid_token(postfix_break_tag("1>&2"), TokenKind::StringRaw),
+            map_valid_token(space_followed_tag("2>>"), TokenKind::StringRaw),
+            map_valid_token(space_followed_tag("2>"), TokenKind::StringRaw),
+            number_literal,
+        ))(input),
+        _ => number_literal(input),
+    }
+}
+
 fn star_dispatch(input: Input<'_>, ctx: Ctx) -> TokenizationResult<'_, (Token, Diagnostic)> {
     match ctx {
         Ctx::Letter | Ctx::Word | Ctx::Number => {
@@ -335,6 +374,7 @@ fn dot_dispatch(input: Input<'_>, ctx: Ctx) -> TokenizationResult<'_, (Token, Di
             map_valid_token(alpha_followed_tag("."), TokenKind::OperatorPostfix), //call
         ))(input),
         Ctx::Start | Ctx::Space | Ctx::Open => alt((
+            map_valid_token(prefix_range_tag("..."), TokenKind::OperatorPrefix), // [a, ...rest] destructure
             map_valid_token(prefix_range_tag("..="), TokenKind::OperatorPrefix), // ..=b range
             map_valid_token(prefix_range_tag(".."), TokenKind::OperatorPrefix),  // ..b range
             number_literal,                                                      //.5
@@ -436,8 +476,18 @@ fn circum_dispatch(input: Input<'_>, ctx: Ctx) -> TokenizationResult<'_, (Token,
     }
 }
 
-fn percent_dispatch(input: Input<'_>, ctx: Ctx) -> TokenizationResult<'_, (Token, Diagnostic)> {
+fn percent_dispatch(
+    input: Input<'_>,
+    ctx: Ctx,
+    in_job_cmd: bool,
+) -> TokenizationResult<'_, (Token, Diagnostic)> {
     match ctx {
+        // 仅在 kill/wait/fg/bg 的参数中识别作业号，`x %2` 仍是取模
+        Ctx::Space if in_job_cmd => alt((
+            map_valid_token(punctuation_tag("%{"), TokenKind::Punctuation),
+            map_valid_token(job_spec_tag, TokenKind::StringRaw), // `fg %1`
+            map_valid_token(punctuation_tag("%"), TokenKind::Operator),
+        ))(input),
         Ctx::Number => alt((map_valid_token(
             punctuation_tag("%"),
             TokenKind::OperatorPostfix,
@@ -447,13 +497,36 @@ fn percent_dispatch(input: Input<'_>, ctx: Ctx) -> TokenizationResult<'_, (Token
             map_valid_token(punctuation_tag("%"), TokenKind::Operator),
         ))(input), //a%b
 
-        Ctx::Start | Ctx::Space | Ctx::Open => alt((
+        Ctx::Space | Ctx::Start | Ctx::Open => alt((
             map_valid_token(punctuation_tag("%{"), TokenKind::Punctuation),
             map_valid_token(punctuation_tag("%"), TokenKind::Operator),
         ))(input),
     }
 }
 
+/// Commands whose arguments may be job specs.
+const JOB_COMMANDS: [&str; 4] = ["kill", "wait", "fg", "bg"];
+
+/// Matches a job spec argument: `%1` `%+` `%-` `%%`, followed by delimiter or end-of-input.
+fn job_spec_tag(input: Input<'_>) -> TokenizationResult<'_> {
+    let spec_len = match input.strip_prefix("%") {
+        Some((rest, _)) => match rest.chars().next() {
+            Some('+' | '-' | '%') => 2,
+            Some(c) if c.is_ascii_digit() => {
+                1 + rest.chars().take_while(|c| c.is_ascii_digit()).count()
+            }
+            _ => return Err(NOT_FOUND),
+        },
+        None => return Err(NOT_FOUND),
+    };
+    let (rest, spec) = input.split_at(spec_len);
+    if rest.is_empty() || rest.starts_with(is_path_delimiter) {
+        Ok((rest, spec))
+    } else {
+        Err(NOT_FOUND)
+    }
+}
+
 /// Context-aware `!` dispatch:
 /// - Word context: `!=`/`!==` comparison, `!~:` pattern match, or `!` postfix (flat call)
 /// - Start/Space/Open: same operators but `!` as prefix (negation) instead of postfix
@@ -496,13 +569,13 @@ fn underscore_dispatch(input: Input<'_>, ctx: Ctx) -> TokenizationResult<'_, (To
                     .strip_prefix("_")
                     .filter(|(rest, _)| {
                         rest.is_empty()
-                            || rest.starts_with(&[' ', '\n', ')', ']', '}', ';'])
+                            || rest.starts_with(&[' ', '\n', ')', ']', '}', ';', ':'])
                             || rest.starts_with("..")
                     })
                     .ok_or(NOT_FOUND)
             },
             TokenKind::ValueSymbol,
-        )(input), //`ls _` `[0.._]` `[_..9]`
+        )(input), //`ls _` `[0.._]` `[_..9]` `_: Int`
     }
 }
 
@@ -577,10 +650,12 @@ fn colon_dispatch(input: Input<'_>, ctx: Ctx) -> TokenizationResult<'_, (Token,
     match ctx {
         Ctx::Letter => alt((
             map_valid_token(alpha_followed_tag("::"), TokenKind::OperatorInfix),
+            map_valid_token(brace_followed_tag("::"), TokenKind::OperatorInfix), // use mod::{a, b}
             map_valid_token(punctuation_tag(":="), TokenKind::Operator),
             map_valid_token(operator_tag(":"), TokenKind::Operator), //{k:v} a?b:c
         ))(input),
         _ => alt((
+            map_valid_token(brace_followed_tag("::"), TokenKind::OperatorInfix), // use 'path'::{a}
             map_valid_token(punctuation_tag(":="), TokenKind::Operator),
             map_valid_token(operator_tag(":"), TokenKind::Operator),
         ))(input),
@@ -628,6 +703,8 @@ fn any_keyword(input: Input<'_>) -> TokenizationResult<'_> {
         space_followed_tag("in"),
         space_brace_followed_tag("while"),
         space_brace_followed_tag("loop"),
+        space_brace_followed_tag("try"),
+        space_followed_tag("defer"),
         postfix_break_tag("break"),
         postfix_break_tag("continue"),
         postfix_break_tag("return"),
@@ -1185,6 +1262,14 @@ fn alpha_followed_tag(keyword: &str) -> impl '_ + Fn(Input<'_>) -> TokenizationR
             .ok_or(NOT_FOUND)
     }
 }
+fn brace_followed_tag(keyword: &str) -> impl '_ + Fn(Input<'_>) -> TokenizationResult<'_> {
+    move |input: Input<'_>| {
+        input
+            .strip_prefix(keyword)
+            .filter(|(rest, _)| rest.starts_with('{'))
+            .ok_or(NOT_FOUND)
+    }
+}
 fn space_brace_followed_tag(keyword: &str) -> impl '_ + Fn(Input<'_>) -> TokenizationResult<'_> {
     move |input: Input<'_>| {
         input
@@ -1257,6 +1342,16 @@ fn prefix_tag(keyword: &str) -> impl '_ + Fn(Input<'_>) -> TokenizationResult<'_
     }
 }
 
+/// Matches a prefix operator immediately followed by `(`, as in process substitution `<(cmd)`.
+fn paren_followed_tag(keyword: &str) -> impl '_ + Fn(Input<'_>) -> TokenizationResult<'_> {
+    move |input: Input<'_>| {
+        input
+            .strip_prefix(keyword)
+            .filter(|(rest, _)| rest.starts_with("("))
+            .ok_or(NOT_FOUND)
+    }
+}
+
 /// Matches a postfix operator that must be followed by whitespace or dilimeter or end-of-input.
 /// Used for postfix `!` and `^` to prevent merging with following characters.
 fn postfix_break_tag(keyword: &str) -> impl '_ + Fn(Input<'_>) -> TokenizationResult<'_> {
@@ -1325,6 +1420,8 @@ pub(crate) fn parse_tokens(input: Input<'_>) -> (Vec<Token>, Vec<Diagnostic>) {
     let mut diagnostics = Vec::new();
     let mut ctx = Ctx::Start;
     let mut last_ctx = Ctx::Start;
+    // 当前语句是否以作业控制命令开头
+    let mut in_job_cmd = false;
     let mut input = input;
 
     // skip multiline mode prefix `:`
@@ -1338,11 +1435,19 @@ pub(crate) fn parse_tokens(input: Input<'_>) -> (Vec<Token>, Vec<Diagnostic>) {
 
     // tokenize one by one with context tracking
     loop {
-        match parse_token_dispatch(input, ctx, last_ctx, is_cfm) {
+        match parse_token_dispatch(input, ctx, last_ctx, is_cfm, in_job_cmd) {
             Err(_) => break,
             Ok((new_input, (token, diagnostic))) => {
+                let is_cmd_ctx = ctx == Ctx::Start || last_ctx == Ctx::Start && ctx == Ctx::Space;
+                if is_cmd_ctx && token.kind == TokenKind::Symbol {
+                    in_job_cmd =
+                        JOB_COMMANDS.contains(&token.range.to_str(input.as_original_str()));
+                }
                 last_ctx = ctx;
                 ctx = Ctx::after_token(&token, input.as_original_str());
+                if ctx == Ctx::Start {
+                    in_job_cmd = false;
+                }
                 input = new_input;
                 tokens.push(token);
                 diagnostics.push(diagnostic);